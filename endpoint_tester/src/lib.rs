@@ -1,394 +1,8181 @@
 // src/lib.rs
 use anyhow::Context;
-use clap::Parser;
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use bytes::Bytes;
+use clap::{parser::ValueSource, ArgMatches, CommandFactory, FromArgMatches, Parser, ValueEnum};
 use hdrhistogram::Histogram;
-use reqwest::{Method, Url};
+use hmac::{Hmac, Mac};
+use http_body_util::BodyExt;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use reqwest::{header::HeaderName, Method, RequestBuilder, Url};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use sha2::{Sha256, Sha512};
 use std::{
-    collections::BTreeMap,
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, BinaryHeap, VecDeque},
+    ffi::OsString,
+    future::Future,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    pin::Pin,
     sync::{
-        atomic::{AtomicBool, AtomicU64, Ordering},
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
         Arc,
     },
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime},
 };
+use tokio::sync::mpsc::Sender;
 use tokio::sync::Mutex;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::Stream;
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+
+#[cfg(feature = "tui")]
+mod tui;
 
 /* ================================ CLI ================================ */
 
+/// User-Agent sent unless overridden by --user-agent or an explicit --header.
+pub const DEFAULT_USER_AGENT: &str = concat!("endpoint_tester/", env!("CARGO_PKG_VERSION"));
+
 #[derive(Parser, Debug, Clone)]
 #[command(name = "endpoint_tester")]
 pub struct Args {
+    /// Read options from a TOML (.toml) or YAML file, keyed by flag name
+    /// ("concurrency = 20"); flags and env vars on the command line win over the file
     #[arg(long)]
-    pub url: String,
+    pub config: Option<String>,
+
+    /// Target URL; falls back to $ET_URL
+    #[arg(long, env = "ET_URL", required_unless_present_any = ["merge", "trend"])]
+    pub url: Option<String>,
+
+    /// Percent-encode spaces, braces and other characters the URL parser would
+    /// otherwise encode or rewrite on its own (they are only warned about by default)
+    #[arg(long)]
+    pub auto_encode_url: bool,
 
     #[arg(long, default_value = "GET")]
     pub method: String,
 
-    #[arg(long, default_value_t = 4)]
+    /// Number of workers; falls back to $ET_CONCURRENCY
+    #[arg(long, env = "ET_CONCURRENCY", default_value_t = 4)]
     pub concurrency: usize,
 
-    /// Run exactly N requests total (across all workers)
+    /// OS threads driving the async runtime (default: one per core)
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub worker_threads: Option<u64>,
+
+    /// Safety cap: each worker waits at least 1/N seconds between requests
+    /// (latency is then also reported corrected for coordinated omission)
     #[arg(long)]
-    pub requests: Option<u64>,
+    pub max_rps_per_worker: Option<f64>,
 
-    /// Run for a duration like 500ms, 10s, 2m, 1h
+    /// Open-loop load: start requests on a Poisson schedule (needs --arrival-rate) whether or
+    /// not earlier ones finished; --concurrency caps how many are in flight
     #[arg(long)]
-    pub duration: Option<String>,
+    pub open_loop: bool,
 
-    /// Per-request timeout like 500ms, 2s
-    #[arg(long, default_value = "2s")]
-    pub timeout: String,
+    /// Mean request starts per second for --open-loop
+    #[arg(long)]
+    pub arrival_rate: Option<f64>,
 
-    /// Repeatable headers: --header 'Key: Value'
-    #[arg(long = "header")]
-    pub headers: Vec<String>,
+    /// --open-loop: most arrivals let in flight at once, counting those still queued for a
+    /// free worker; --on-overload decides what happens to the ones past it
+    #[arg(long)]
+    pub max_inflight: Option<u64>,
 
-    /// Optional API key convenience (adds Authorization: Bearer <token>)
+    /// drop: skip arrivals past --max-inflight (reported as dropped); block: hold the
+    /// arrival schedule until a request finishes
+    #[arg(long, value_enum, default_value_t = OnOverload::Drop)]
+    pub on_overload: OnOverload,
+
+    /// Replay a recorded schedule open-loop: one 'offset_ms METHOD /path' line per request,
+    /// started that long after the run begins (paths are appended to --url)
     #[arg(long)]
-    pub api_key: Option<String>,
+    pub trace_file: Option<String>,
 
-    /// Inline JSON payload (for POST/PUT/PATCH)
+    /// Run exactly N requests total (across all workers)
     #[arg(long)]
-    pub json: Option<String>,
+    pub requests: Option<u64>,
 
-    /// JSON payload file path (for POST/PUT/PATCH)
+    /// Smoke test: send one request and print it in full (request and response headers,
+    /// timing, body preview) instead of load statistics
     #[arg(long)]
-    pub json_file: Option<String>,
+    pub once: bool,
 
-    /// Print progress every N completions (0 disables)
-    #[arg(long, default_value_t = 1000)]
-    pub progress_every: u64,
-}
+    /// With --once: send the request again on a connection of its own and show where its
+    /// latency went (DNS, connect, TLS, time to first byte, transfer) as a waterfall. The
+    /// server sees the request twice, so methods other than GET, HEAD, OPTIONS and TRACE
+    /// need --latency-budget-allow-unsafe
+    #[arg(long)]
+    pub latency_budget: bool,
 
-/* ============================= PUBLIC API ============================= */
+    /// Let --latency-budget repeat a POST, PUT, PATCH or DELETE request
+    #[arg(long)]
+    pub latency_budget_allow_unsafe: bool,
 
-pub async fn main_entry() -> anyhow::Result<()> {
-    let args = Args::parse();
-    let run_args = RunArgs::from(args);
-    let result = run(run_args).await?;
-    print!("{}", render_report(&result));
-    Ok(())
-}
+    /// Instead of --requests: every worker sends exactly N (concurrency x N in total),
+    /// counting on its own
+    #[arg(long)]
+    pub requests_per_worker: Option<u64>,
 
-#[derive(Debug, Clone)]
-pub struct RunArgs {
-    pub url: String,
-    pub method: String,
-    pub concurrency: usize,
-    pub requests: Option<u64>,
-    pub duration: Option<String>,
-    pub timeout: String,
-    pub headers: Vec<String>,
-    pub api_key: Option<String>,
-    pub json: Option<String>,
-    pub json_file: Option<String>,
-    pub progress_every: u64,
-}
+    /// Stop once every worker has walked the scenario steps (or paths, hosts, ...) K
+    /// times; faster workers keep going meanwhile. --requests and --duration become
+    /// optional caps.
+    #[arg(long)]
+    pub cycles: Option<u64>,
 
-impl From<Args> for RunArgs {
-    fn from(a: Args) -> Self {
-        Self {
-            url: a.url,
-            method: a.method,
-            concurrency: a.concurrency,
-            requests: a.requests,
-            duration: a.duration,
-            timeout: a.timeout,
-            headers: a.headers,
-            api_key: a.api_key,
-            json: a.json,
-            json_file: a.json_file,
-            progress_every: a.progress_every,
-        }
-    }
-}
+    /// Run for a duration like 500ms, 10s, 2m, 1h; falls back to $ET_DURATION
+    #[arg(long, env = "ET_DURATION")]
+    pub duration: Option<String>,
 
-#[derive(Debug, Clone)]
-pub struct RunResult {
-    pub url: String,
-    pub method: String,
-    pub concurrency: usize,
-    pub requests_target: Option<u64>,
-    pub duration_target: Option<String>,
+    /// Per-request timeout like 500ms, 2s
+    #[arg(long, default_value = "2s")]
     pub timeout: String,
-    pub elapsed_sec: f64,
-    pub sent: u64,
-    pub completed: u64,
-    pub aggregates: Aggregates,
-}
 
-/* ============================= AGGREGATES ============================= */
+    /// Belt-and-braces cap on each request attempt, body reads included, enforced outside
+    /// reqwest and counted as a timeout (default: off)
+    #[arg(long)]
+    pub hard_timeout: Option<String>,
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum NetErrKind {
-    Timeout,
-    Connect,
-    Request,
-    Body,
-    Decode,
-    Other,
-}
+    /// Give up early on a wrong endpoint: 'N/M' aborts the run as soon as more than N of
+    /// the first M requests fail to resolve, connect or time out
+    #[arg(long)]
+    pub abort_if_initial_failures: Option<String>,
 
-#[derive(Debug, Default, Clone)]
-pub struct StatusClassCounts {
-    pub c1xx: u64,
-    pub c2xx: u64,
-    pub c3xx: u64,
-    pub c4xx: u64,
-    pub c5xx: u64,
-    pub other: u64,
-}
+    /// How long after --duration (or a stage) ends before stuck requests are aborted
+    /// (default: the request timeout plus 1s)
+    #[arg(long)]
+    pub shutdown_grace: Option<String>,
 
-impl StatusClassCounts {
-    pub fn record(&mut self, code: u16) {
-        match code / 100 {
-            1 => self.c1xx += 1,
-            2 => self.c2xx += 1,
-            3 => self.c3xx += 1,
-            4 => self.c4xx += 1,
-            5 => self.c5xx += 1,
-            _ => self.other += 1,
-        }
-    }
-}
+    /// Repeatable headers: --header 'Key: Value'; falls back to $ET_HEADERS, one header
+    /// per line (values may contain commas, so only newlines separate them). Values support
+    /// {{seq}}, {{rand}}, {{uuid}} and {{now_ms}}, expanded per request
+    #[arg(long = "header", env = "ET_HEADERS", value_delimiter = '\n')]
+    pub headers: Vec<String>,
 
-#[derive(Debug, Default, Clone)]
-pub struct NetErrCounts {
-    pub timeout: u64,
-    pub connect: u64,
-    pub request: u64,
-    pub body: u64,
-    pub decode: u64,
-    pub other: u64,
-}
+    /// Optional API key convenience (adds Authorization: Bearer <token>).
+    /// Falls back to $ET_API_KEY; the flag wins when both are set.
+    #[arg(long, env = "ET_API_KEY", hide_env_values = true)]
+    pub api_key: Option<String>,
 
-impl NetErrCounts {
-    pub fn record(&mut self, k: NetErrKind) {
-        match k {
-            NetErrKind::Timeout => self.timeout += 1,
-            NetErrKind::Connect => self.connect += 1,
-            NetErrKind::Request => self.request += 1,
-            NetErrKind::Body => self.body += 1,
-            NetErrKind::Decode => self.decode += 1,
-            NetErrKind::Other => self.other += 1,
-        }
-    }
+    /// Sign each request: 'header=X-Signature,secret=...,algo=sha256' puts the hex HMAC
+    /// (sha256 or sha512) of "METHOD\npath?query\nbody" into the header
+    #[arg(long)]
+    pub hmac_sign: Option<String>,
 
-    pub fn total(&self) -> u64 {
-        self.timeout + self.connect + self.request + self.body + self.decode + self.other
-    }
-}
+    /// HTTP basic auth user (adds Authorization: Basic ...)
+    #[arg(long)]
+    pub basic_user: Option<String>,
 
-#[derive(Debug, Clone)]
-pub struct Aggregates {
-    pub status_exact: BTreeMap<u16, u64>,
-    pub status_class: StatusClassCounts,
-    pub net_errors: NetErrCounts,
-    pub latency_micros: Histogram<u64>,
-}
+    /// HTTP basic auth password; falls back to $ET_BASIC_PASS
+    #[arg(long, env = "ET_BASIC_PASS", hide_env_values = true)]
+    pub basic_pass: Option<String>,
 
-impl Aggregates {
-    pub fn new() -> anyhow::Result<Self> {
-        Ok(Self {
-            status_exact: BTreeMap::new(),
-            status_class: StatusClassCounts::default(),
-            net_errors: NetErrCounts::default(),
-            latency_micros: Histogram::<u64>::new(3)?,
-        })
-    }
+    /// Send all requests through this proxy URL
+    #[arg(long)]
+    pub proxy: Option<String>,
 
-    pub fn record_status(&mut self, code: u16) {
-        *self.status_exact.entry(code).or_insert(0) += 1;
-        self.status_class.record(code);
-    }
+    /// Connect to this Unix domain socket instead of the --url host (Unix only);
+    /// the URL still supplies the scheme, path and Host header
+    #[arg(long)]
+    pub unix_socket: Option<String>,
 
-    pub fn record_error(&mut self, kind: NetErrKind) {
-        self.net_errors.record(kind);
-    }
+    /// Disable Nagle's algorithm on connections (on by default; --tcp-nodelay false to turn off)
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+    pub tcp_nodelay: bool,
 
-    pub fn record_latency(&mut self, micros: u64) {
-        let _ = self.latency_micros.record(micros.max(1));
-    }
-}
+    /// TCP keepalive probe interval like 30s (default: off)
+    #[arg(long)]
+    pub tcp_keepalive: Option<String>,
 
-pub fn classify_reqwest_error(e: &reqwest::Error) -> NetErrKind {
-    if e.is_timeout() {
-        NetErrKind::Timeout
-    } else if e.is_connect() {
-        NetErrKind::Connect
-    } else if e.is_request() {
-        NetErrKind::Request
-    } else if e.is_body() {
-        NetErrKind::Body
-    } else if e.is_decode() {
-        NetErrKind::Decode
-    } else {
-        NetErrKind::Other
-    }
-}
+    /// Give every worker its own client and rebuild it after each N requests, dropping
+    /// all pooled connections (default: one shared client for the whole run)
+    #[arg(long)]
+    pub recycle_client_every: Option<u64>,
 
-/* ================================ RUN ================================ */
+    /// Connect over IPv4 or IPv6 only (auto: whatever the resolver and happy eyeballs pick)
+    #[arg(long, value_enum, default_value_t = AddressFamily::Auto)]
+    pub address_family: AddressFamily,
 
-pub async fn run(args: RunArgs) -> anyhow::Result<RunResult> {
-    // validate url
-    let url = Url::parse(&args.url).map_err(|e| anyhow::anyhow!("Invalid --url: {e}"))?;
+    /// Proxy credentials as 'user:pass'; falls back to $ET_PROXY_AUTH
+    #[arg(long, env = "ET_PROXY_AUTH", hide_env_values = true)]
+    pub proxy_auth: Option<String>,
 
-    // validate method (explicit allow-list; reqwest accepts extension methods)
-    let method = parse_http_method(&args.method)
-        .ok_or_else(|| anyhow::anyhow!("Invalid --method: {}", args.method))?;
+    /// Send a fresh UUID v4 per request in the idempotency key header
+    #[arg(long)]
+    pub with_idempotency_key: bool,
 
-    if args.requests.is_none() && args.duration.is_none() {
-        return Err(anyhow::anyhow!(
-            "You must provide either --requests or --duration"
-        ));
-    }
+    /// Header name used by --with-idempotency-key
+    #[arg(long, default_value = "Idempotency-Key")]
+    pub idempotency_key_header: String,
 
-    // validate timeout and optional duration
-    let timeout_dur = parse_duration(&args.timeout)
-        .ok_or_else(|| anyhow::anyhow!("Invalid --timeout: {}", args.timeout))?;
+    /// Send a fresh W3C traceparent header with each request (a new trace per request,
+    /// shared by its retries); --events-file lines carry the trace ID
+    #[arg(long)]
+    pub inject_trace_header: bool,
 
-    let duration_target = if let Some(d) = &args.duration {
-        Some(parse_duration(d).ok_or_else(|| anyhow::anyhow!("Invalid --duration: {d}"))?)
-    } else {
-        None
-    };
+    /// Send "Expect: 100-continue" with the request body (upload interop check)
+    #[arg(long)]
+    pub expect_100_continue: bool,
 
-    // parse headers
-    let mut header_map: BTreeMap<String, String> = BTreeMap::new();
-    for h in &args.headers {
-        let (k, v) = parse_header(h).ok_or_else(|| {
-            anyhow::anyhow!("Invalid --header format: {h} (expected \"Key: Value\")")
-        })?;
-        header_map.insert(k, v);
-    }
-    if let Some(token) = &args.api_key {
-        header_map.insert("Authorization".to_string(), format!("Bearer {token}"));
-    }
+    /// Inline JSON payload (for POST/PUT/PATCH); {{env:NAME}} and {{env:NAME:default}} are
+    /// replaced with environment variables once at startup (also in --json-file, --header
+    /// and --query-param)
+    #[arg(long)]
+    pub json: Option<String>,
 
-    // JSON payload
-    let json_payload = load_json_payload(&args)?;
+    /// JSON payload file path (for POST/PUT/PATCH)
+    #[arg(long)]
+    pub json_file: Option<String>,
 
-    // build client
-    let client = reqwest::Client::builder()
-        .timeout(timeout_dur)
-        .build()
-        .context("Failed to build reqwest client")?;
+    /// Stream this file as the request body (re-opened per request, never fully loaded)
+    #[arg(long)]
+    pub stream_body_file: Option<String>,
 
-    // shared state
-    let agg = Arc::new(Mutex::new(Aggregates::new()?));
-    let sent = Arc::new(AtomicU64::new(0));
-    let completed = Arc::new(AtomicU64::new(0));
-    let stop = Arc::new(AtomicBool::new(false));
+    /// Send this file's bytes as the request body (read once at startup)
+    #[arg(long)]
+    pub body_file: Option<String>,
 
-    let start = Instant::now();
-    let deadline = duration_target.map(|d| start + d);
+    /// Shell command whose stdout is the request body, run per request
+    /// (at most one per CPU at a time)
+    #[arg(long)]
+    pub payload_command: Option<String>,
 
-    let mut handles = Vec::with_capacity(args.concurrency.max(1));
-    let conc = args.concurrency.max(1);
+    /// Run --payload-command once per worker and reuse its output
+    #[arg(long)]
+    pub payload_command_cache: bool,
 
-    for _ in 0..conc {
-        let client = client.clone();
-        let url = url.clone();
-        let method = method.clone();
-        let headers = header_map.clone();
-        let json_payload = json_payload.clone();
-        let agg = agg.clone();
-        let sent = sent.clone();
-        let completed = completed.clone();
-        let stop = stop.clone();
-        let limit = args.requests;
-        let progress_every = args.progress_every;
-        let deadline = deadline;
+    /// Repeatable multipart file part: --form-file 'field=@path' (re-read per request)
+    #[arg(long = "form-file")]
+    pub form_files: Vec<String>,
 
-        handles.push(tokio::spawn(async move {
-            loop {
-                if stop.load(Ordering::Relaxed) {
-                    break;
-                }
+    /// Repeatable multipart text part: --form-field 'name=value'
+    #[arg(long = "form-field")]
+    pub form_fields: Vec<String>,
 
-                if let Some(dl) = deadline {
-                    if Instant::now() >= dl {
-                        stop.store(true, Ordering::Relaxed);
-                        break;
-                    }
-                }
+    /// Send a generated body of this size (e.g. 1048576, 64k, 1m) as application/octet-stream
+    #[arg(long)]
+    pub body_size: Option<String>,
 
-                // exact limit without overshoot
-                if let Some(n) = limit {
-                    let cur = sent.load(Ordering::Relaxed);
-                    if cur >= n {
-                        stop.store(true, Ordering::Relaxed);
-                        break;
-                    }
-                    // reserve one slot
-                    if sent
-                        .compare_exchange(cur, cur + 1, Ordering::SeqCst, Ordering::Relaxed)
-                        .is_err()
-                    {
-                        continue; // retry
-                    }
-                } else {
-                    sent.fetch_add(1, Ordering::Relaxed);
-                }
+    /// Content of the --body-size payload
+    #[arg(long, value_enum, default_value_t = BodyFill::Random)]
+    pub body_fill: BodyFill,
 
-                let t0 = Instant::now();
-                let mut req = client.request(method.clone(), url.clone());
+    /// Compress the --json/--json-file/--body-file/--body-size payload once at startup
+    /// and send it with Content-Encoding
+    #[arg(long, value_enum)]
+    pub compress_body: Option<BodyCompression>,
 
-                for (k, v) in &headers {
-                    req = req.header(k, v);
-                }
-                if let Some(j) = &json_payload {
-                    req = req.json(j);
-                }
+    /// Unary gRPC calls: HTTP/2 POSTs of the length-prefixed --body-file message, judged
+    /// by the grpc-status trailer instead of the HTTP status
+    #[arg(long)]
+    pub grpc: bool,
 
-                let resp = req.send().await;
-                let micros = t0.elapsed().as_micros().min(u128::from(u64::MAX)) as u64;
+    /// YAML/JSON file with steps (method, path, headers, body, expect_status) that every
+    /// worker runs in order, looping; paths are resolved against --url
+    #[arg(long)]
+    pub scenario: Option<String>,
 
-                let mut a = agg.lock().await;
-                a.record_latency(micros);
+    /// File with one path per line, each appended to --url; requests spread over them
+    /// and the report breaks results down per path
+    #[arg(long)]
+    pub paths_file: Option<String>,
 
-                match resp {
-                    Ok(r) => a.record_status(r.status().as_u16()),
-                    Err(e) => a.record_error(classify_reqwest_error(&e)),
-                }
+    /// YAML/JSON list of hosts (url, weight, headers) sharing the load by weight; each
+    /// keeps the --url path and the report breaks results down per host
+    #[arg(long)]
+    pub hosts_file: Option<String>,
 
-                drop(a);
+    /// CRUD mix: 'METHOD=body.json' entries ('GET' alone sends no body); requests spread
+    /// over them (repeat an entry to weight it) and the report breaks results down per entry
+    #[arg(long = "method-body", num_args = 1..)]
+    pub method_bodies: Vec<String>,
+
+    /// How workers pick the next request from the --paths-file, --hosts-file or --method-body pool
+    #[arg(long, alias = "path-select", value_enum, default_value_t = Select::RoundRobin)]
+    pub select: Select,
+
+    /// Print progress every N completions (0 disables)
+    #[arg(long, default_value_t = 1000)]
+    pub progress_every: u64,
+
+    /// Progress lines on stderr: "progress: completed=N" or one JSON object per line
+    #[arg(long, value_enum, default_value_t = ProgressFormat::Text)]
+    pub progress_format: ProgressFormat,
+
+    /// For write methods, send and time a CORS OPTIONS preflight before each request
+    #[arg(long)]
+    pub measure_preflight: bool,
+
+    /// Before the run, time N HEAD requests that each open a new connection
+    /// (DNS + TCP + TLS + one round trip) and report them as handshake_ms
+    #[arg(long)]
+    pub measure_handshake: Option<u64>,
+
+    /// Before the run, time N TLS handshakes with session resumption off and N that
+    /// resume a primed session, each on a new connection (https only; trusts the
+    /// bundled webpki roots)
+    #[arg(long)]
+    pub measure_tls_resumption: Option<u64>,
+
+    /// Read each response body to the end: latency then covers the download and the
+    /// wait for response headers is reported separately as ttfb_ms
+    #[arg(long)]
+    pub measure_ttfb: bool,
+
+    /// Also report latency per KiB of response body (latency_per_kb_ms), for endpoints
+    /// where size dominates timing; pair with --measure-ttfb so latency covers the body
+    #[arg(long)]
+    pub normalize_latency_by_payload_size: bool,
+
+    /// Collect the dur= values of Server-Timing response headers per metric name and
+    /// report them next to the client-side latency
+    #[arg(long)]
+    pub parse_server_timing: bool,
+
+    /// Keep the distinct network error messages (with their causes) per error kind and
+    /// show the most frequent ones under error_examples
+    #[arg(long)]
+    pub dedupe_errors: bool,
+
+    /// Drop response bodies unread instead of draining them after the headers; saves
+    /// bandwidth, but the connection can't be reused when a body is left behind
+    #[arg(long)]
+    pub no_body_download: bool,
+
+    /// Remember the latest ETag and send it as If-None-Match; 304s are tallied as cache hits
+    #[arg(long)]
+    pub capture_etag: bool,
+
+    /// On 429/503 the worker pauses for the response's Retry-After (in seconds, 1s if
+    /// absent, at most 300s, never past the deadline)
+    #[arg(long)]
+    pub respect_retry_after: bool,
+
+    /// Retry network errors and 5xx responses up to N times; the recorded latency
+    /// spans all attempts and backoffs
+    #[arg(long, default_value_t = 0)]
+    pub retries: u32,
+
+    /// Base pause before a retry
+    #[arg(long, default_value = "100ms")]
+    pub retry_backoff: String,
+
+    /// fixed: always --retry-backoff; exponential: random up to base * 2^attempt (full jitter)
+    #[arg(long, value_enum, default_value_t = BackoffMode::Fixed)]
+    pub retry_backoff_mode: BackoffMode,
+
+    /// Cap on an exponential retry pause
+    #[arg(long, default_value = "10s")]
+    pub retry_backoff_max: String,
+
+    /// Only print the report (and warnings); no progress output
+    #[arg(long, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Also log worker start/stop and the resolved client configuration
+    #[arg(long)]
+    pub verbose: bool,
+
+    /// Repeatable query params: --query-param 'id={{seq}}' (values support {{seq}}, {{rand}},
+    /// {{uuid}} and {{now_ms}})
+    #[arg(long = "query-param")]
+    pub query_params: Vec<String>,
+
+    /// Repeatable result annotation: --tag env=staging --tag commit=abc123
+    #[arg(long = "tag")]
+    pub tags: Vec<String>,
+
+    /// Warn when a run produces (or is configured for) fewer than N completions
+    #[arg(long)]
+    pub min_samples: Option<u64>,
+
+    /// Run the whole test N times and report per-run and combined results
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub repeat: u32,
+
+    /// Run the whole test once per method (e.g. GET,HEAD) and print them side by side
+    #[arg(long, value_delimiter = ',')]
+    pub compare_methods: Vec<String>,
+
+    /// User-Agent for every request; an explicit --header 'User-Agent: ...' takes precedence
+    #[arg(long, default_value = DEFAULT_USER_AGENT)]
+    pub user_agent: String,
+
+    /// Send N requests (total) before measuring; their results are discarded
+    #[arg(long, default_value_t = 0)]
+    pub warmup: u64,
+
+    /// Send N priming requests first and report them as "cold" next to the main ("warm") run
+    #[arg(long)]
+    pub warm_compare: Option<u64>,
+
+    /// Step-load profile run back to back: '10:30s,50:30s,100:60s' (concurrency:duration)
+    #[arg(long)]
+    pub stages: Option<String>,
+
+    /// Alternate load and idle phases for the whole --duration: 'on=10s,off=20s';
+    /// workers pause while off and the report breaks results down per burst
+    #[arg(long)]
+    pub burst: Option<String>,
+
+    /// Vary the number of sending workers over the --duration, replacing --concurrency:
+    /// 'sine:min=10,max=100,period=60s' or 'linear:10..100'; the report shows the
+    /// target and realized concurrency each second
+    #[arg(long)]
+    pub concurrency_profile: Option<String>,
+
+    /// Repeatable response header assertion: --expect-header 'Name: Value' (or just 'Name')
+    #[arg(long = "expect-header")]
+    pub expect_headers: Vec<String>,
+
+    /// Count a response body check as passed only when the body contains this text
+    #[arg(long)]
+    pub success_body_contains: Option<String>,
+
+    /// Count a response body check as passed only when the body matches this regex
+    #[arg(long)]
+    pub success_body_regex: Option<String>,
+
+    /// JSON Schema every JSON response body must conform to; violations count as
+    /// validation failures
+    #[arg(long)]
+    pub response_schema: Option<String>,
+
+    /// How much of each body the --success-body-* checks read (e.g. 64k); larger bodies
+    /// skip --response-schema
+    #[arg(long, default_value = "64k")]
+    pub max_validate_bytes: String,
+
+    /// Count a response whose body size falls outside 'min:max' bytes (e.g. 1k:64k, or
+    /// 100: for no upper bound) as a validation failure; Content-Length is trusted when
+    /// sent, otherwise the body is counted as it is read
+    #[arg(long)]
+    pub expect_body_size_range: Option<String>,
+
+    /// Fraction of responses (0..1) the response checks run on, drawn per request from the
+    /// worker RNG; every response still counts towards latency and status
+    #[arg(long, default_value_t = 1.0)]
+    pub validate_sample_rate: f64,
+
+    /// Exit with an error when any response validation failed
+    #[arg(long)]
+    pub fail_on_validation: bool,
+
+    /// Exit with an error when a worker task panicked (the run still reports)
+    #[arg(long)]
+    pub fail_on_worker_panic: bool,
+
+    /// Report format; json prints the serialized RunResult, summary a single key=value line
+    /// (both the combined one with --repeat)
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+
+    /// Color the text report: auto colors it only on a terminal (and not with $NO_COLOR);
+    /// with --apdex-threshold, percentiles near or over T are highlighted
+    #[arg(long, value_enum, default_value_t = ColorMode::Auto)]
+    pub color: ColorMode,
+
+    /// Merge previously saved JSON results (from --output json) into one report instead of running
+    #[arg(long, num_args = 1..)]
+    pub merge: Vec<String>,
+
+    /// Write the report to this file instead of stdout (parent directories are created)
+    #[arg(long)]
+    pub report_file: Option<String>,
+
+    /// Write one JSON line per finished request (seq, latency, status or error, trace ID)
+    /// to this file while the run goes
+    #[arg(long)]
+    pub events_file: Option<String>,
+
+    /// Print NDJSON to stdout instead of the report: a {"type":"request",...} object per
+    /// finished request as it happens, then a {"type":"summary",...} object with the
+    /// result (see README "NDJSON stream")
+    #[arg(long)]
+    pub stream_ndjson: bool,
+
+    /// Write a latency heatmap as CSV: one row per --sample-interval window, one column
+    /// of request counts per --heatmap-bucket latency bucket
+    #[arg(long)]
+    pub heatmap_csv: Option<String>,
+
+    /// Width of the --heatmap-csv latency buckets
+    #[arg(long, default_value = "10ms")]
+    pub heatmap_bucket: String,
+
+    /// Append each result as one JSON line (with a timestamp) to this file, for --trend
+    #[arg(long)]
+    pub append_results: Option<String>,
+
+    /// Show p99 and throughput over the runs in an --append-results file instead of running
+    #[arg(long)]
+    pub trend: Option<String>,
+
+    /// How many of the latest runs --trend shows
+    #[arg(long, default_value_t = 20, value_parser = clap::value_parser!(u64).range(1..))]
+    pub trend_last: u64,
+
+    /// Keep status, headers and a body snippet of requests slower than this (last 50 kept)
+    #[arg(long)]
+    pub capture_slow_over: Option<String>,
+
+    /// List the N slowest requests with when they finished and their status (latency only,
+    /// no headers or bodies; each worker keeps its own N)
+    #[arg(long)]
+    pub top_slow: Option<usize>,
+
+    /// Write sampled requests and responses (headers, timings, body preview) to this
+    /// HAR 1.2 file for browser devtools or Charles; the first 1000 sampled are kept
+    #[arg(long)]
+    pub har_file: Option<String>,
+
+    /// Fraction of requests recorded in --har-file, spread evenly (0.1 = every 10th)
+    #[arg(long, default_value_t = 1.0)]
+    pub har_sample_rate: f64,
+
+    /// Latency histogram precision in significant figures (1..5)
+    #[arg(long, default_value_t = 3)]
+    pub hist_sigfigs: u8,
+
+    /// Highest trackable latency like 30s (fixed-size histogram; larger values saturate).
+    /// Without it the histogram auto-resizes.
+    #[arg(long)]
+    pub hist_max: Option<String>,
+
+    /// Record a time series with one sample (throughput, p50, p99) per interval like 10s
+    #[arg(long)]
+    pub sample_interval: Option<String>,
+
+    /// Root seed for all random choices ({{rand}}, idempotency keys, ...); per-worker
+    /// generators are derived from it. Network timing is never reproducible.
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Soak test: report p99 drift per hour and throughput stability (needs --sample-interval)
+    #[arg(long)]
+    pub soak: bool,
+
+    /// Count completions per elapsed second and show them as a sparkline in the report
+    #[arg(long)]
+    pub rps_timeline: bool,
+
+    /// With --soak, warn when p99 drifts upward by more than this many ms per hour
+    #[arg(long, default_value_t = 10.0)]
+    pub soak_max_drift: f64,
+
+    /// Stop once p99 settles: two consecutive --stable-window windows within
+    /// --stable-tolerance of each other. --requests and --duration become optional caps;
+    /// without either, the run gives up after 60 windows.
+    #[arg(long)]
+    pub until_stable: bool,
+
+    /// Window length for --until-stable (also the --sample-interval of the run)
+    #[arg(long, default_value = "5s")]
+    pub stable_window: String,
+
+    /// Largest p99 change between windows, in percent, that --until-stable calls stable
+    #[arg(long, default_value_t = 5.0)]
+    pub stable_tolerance: f64,
+
+    /// Report an Apdex score for this target latency T like 200ms
+    /// (satisfied <= T, tolerating <= 4T)
+    #[arg(long)]
+    pub apdex_threshold: Option<String>,
+
+    /// Live dashboard (throughput, p50/p99, error rate) instead of progress lines;
+    /// needs a build with `--features tui`
+    #[arg(long)]
+    pub tui: bool,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressFamily {
+    Auto,
+    Ipv4,
+    Ipv6,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackoffMode {
+    Fixed,
+    Exponential,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnOverload {
+    Drop,
+    Block,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyFill {
+    Random,
+    Zero,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyCompression {
+    Gzip,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Summary,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Select {
+    /// each worker cycles through the pool, starting at a different entry
+    RoundRobin,
+    /// uniform pick per request; short runs can leave entries under-sampled
+    Random,
+    /// one shared index: requests walk the pool in order across all workers
+    Sequential,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressFormat {
+    Text,
+    /// {"completed":N,"sent":M,"elapsed_sec":X,"rps":Y}
+    Json,
+}
+
+/* ============================= PUBLIC API ============================= */
+
+pub fn main_entry() -> anyhow::Result<()> {
+    let (args, matches) = match parse_args(std::env::args_os()) {
+        Ok(parsed) => parsed,
+        Err(e) => match e.downcast::<clap::Error>() {
+            Ok(e) => e.exit(),
+            Err(e) => return Err(e),
+        },
+    };
+    // built by hand so --worker-threads can pin the thread count
+    let mut runtime = tokio::runtime::Builder::new_multi_thread();
+    if let Some(n) = args.worker_threads {
+        runtime.worker_threads(n as usize);
+    }
+    let runtime = runtime
+        .enable_all()
+        .build()
+        .context("Failed to start the tokio runtime")?;
+    runtime.block_on(run_main(args, &matches))
+}
+
+async fn run_main(args: Args, matches: &ArgMatches) -> anyhow::Result<()> {
+    let merge_files = args.merge.clone();
+    let report_file = args.report_file.clone();
+    let events_file = args.events_file.clone();
+    let heatmap_csv = args.heatmap_csv.clone();
+    let har_file = args.har_file.clone();
+    let stream_ndjson = args.stream_ndjson;
+    let append_results = args.append_results.clone();
+    let trend = args.trend.clone();
+    let trend_last = args.trend_last as usize;
+    let tui = args.tui;
+    if stream_ndjson
+        && (tui || events_file.is_some() || !args.merge.is_empty() || args.trend.is_some())
+    {
+        return Err(anyhow::anyhow!(
+            "--stream-ndjson cannot be combined with --tui, --events-file, --merge or --trend"
+        ));
+    }
+    let color = args.output == OutputFormat::Text
+        && report_file.is_none()
+        && !stream_ndjson
+        && match args.color {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => {
+                use std::io::IsTerminal;
+                std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+            }
+        };
+    // --apdex-threshold doubles as the latency target the colors are judged against
+    let target_ms = args
+        .apdex_threshold
+        .as_deref()
+        .and_then(parse_duration)
+        .map(|d| d.as_secs_f64() * 1000.0);
+    let paint = |text: String| {
+        if color {
+            colorize_report(&text, target_ms)
+        } else {
+            text
+        }
+    };
+    let mut run_args = RunArgs::from(args);
+    run_args.credential_sources = credential_sources(matches);
+    let fail_on_validation = run_args.fail_on_validation;
+    let fail_on_worker_panic = run_args.fail_on_worker_panic;
+    let output = run_args.output;
+
+    if !merge_files.is_empty() {
+        let results = merge_files
+            .iter()
+            .map(|p| load_result(p))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let merged = merge_results(&results)?;
+        let text = render_output(&merged, output)?;
+        match &report_file {
+            Some(path) => write_report_file(path, &text)?,
+            None => print!("{}", paint(text)),
+        }
+        return Ok(());
+    }
+
+    if let Some(path) = &trend {
+        let records = load_trend(path)?;
+        let skip = records.len().saturating_sub(trend_last);
+        let text = render_trend(&records[skip..], records.len());
+        match &report_file {
+            Some(path) => write_report_file(path, &text)?,
+            None => print!("{text}"),
+        }
+        return Ok(());
+    }
+
+    // reports are held back while the dashboard owns the terminal, when they go to a file
+    // or to be colored; --stream-ndjson keeps them off stdout
+    let mut held: Option<String> =
+        (report_file.is_some() || color || stream_ndjson).then(String::new);
+    #[cfg(feature = "tui")]
+    let dashboard = if tui {
+        let live = Arc::new(LiveStats::new());
+        run_args.live = Some(live.clone());
+        run_args.progress_every = 0;
+        held = Some(String::new());
+        Some(tui::start(live, run_args.url.clone()))
+    } else {
+        None
+    };
+    #[cfg(not(feature = "tui"))]
+    if tui {
+        return Err(anyhow::anyhow!(
+            "--tui needs a build with the tui feature (cargo build --features tui)"
+        ));
+    }
+
+    let events_writer = match &events_file {
+        Some(path) => Some(spawn_events_writer(path, &mut run_args)?),
+        None if stream_ndjson => Some(spawn_ndjson_writer(&mut run_args)),
+        None => None,
+    };
+
+    let outcome = run_all(run_args, output, &mut held).await;
+    if let Some(w) = events_writer {
+        // run_all dropped the last sender, so the writer has seen every event
+        let flag = if stream_ndjson {
+            "--stream-ndjson"
+        } else {
+            "--events-file"
+        };
+        w.join()
+            .map_err(|_| anyhow::anyhow!("{flag} writer panicked"))?
+            .map_err(|e| anyhow::anyhow!("Failed to write {flag}: {e}"))?;
+    }
+    #[cfg(feature = "tui")]
+    if let Some(d) = dashboard {
+        d.stop()?;
+    }
+    match (held, &report_file) {
+        // a failed run leaves the previous report in place
+        (Some(text), Some(path)) if outcome.is_ok() => write_report_file(path, &text)?,
+        (Some(_), Some(_)) => {}
+        (Some(_), None) if stream_ndjson => {}
+        (Some(text), None) => print!("{}", paint(text)),
+        (None, _) => {}
+    }
+    let results = outcome?;
+    if stream_ndjson {
+        // one per result: the combined one with --repeat, one per --compare-methods method
+        for r in &results {
+            print!("{}", render_ndjson_summary(r)?);
+        }
+    }
+    if let Some(path) = &heatmap_csv {
+        // --compare-methods runs overwrite each other; the last one wins
+        if let Some(r) = results.last() {
+            let csv = render_heatmap_csv(&r.samples, r.heatmap_bucket_ms.unwrap_or(0.0));
+            std::fs::write(path, csv)
+                .map_err(|e| anyhow::anyhow!("Failed to write --heatmap-csv {path}: {e}"))?;
+        }
+    }
+    if let Some(path) = &har_file {
+        let entries: Vec<&HarEntry> = results.iter().flat_map(|r| &r.har).collect();
+        std::fs::write(path, render_har(&entries))
+            .map_err(|e| anyhow::anyhow!("Failed to write --har-file {path}: {e}"))?;
+    }
+    if let Some(path) = &append_results {
+        for r in &results {
+            append_result(path, r)?;
+        }
+    }
+
+    if fail_on_validation {
+        let failures: u64 = results
+            .iter()
+            .map(|r| r.aggregates.validation_failures())
+            .sum();
+        if failures > 0 {
+            return Err(anyhow::anyhow!("{failures} response validation failures"));
+        }
+    }
+    if fail_on_worker_panic {
+        let panics: u64 = results.iter().map(|r| r.worker_panics).sum();
+        if panics > 0 {
+            return Err(anyhow::anyhow!("{panics} worker(s) panicked"));
+        }
+    }
+    Ok(())
+}
+
+/// Streams the run's events to `path` as JSON lines on a thread of its own.
+fn spawn_events_writer(
+    path: &str,
+    run_args: &mut RunArgs,
+) -> anyhow::Result<std::thread::JoinHandle<std::io::Result<()>>> {
+    use std::io::Write;
+    let file = std::fs::File::create(path)
+        .map_err(|e| anyhow::anyhow!("Failed to create --events-file {path}: {e}"))?;
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<RequestOutcome>(EVENTS_CHANNEL_CAPACITY);
+    run_args.events = Some(tx);
+    Ok(std::thread::spawn(move || {
+        let mut w = std::io::BufWriter::new(file);
+        while let Some(o) = rx.blocking_recv() {
+            writeln!(w, "{}", o.to_json_line())?;
+        }
+        w.flush()
+    }))
+}
+
+/// Streams the run's events to stdout as --stream-ndjson request records.
+fn spawn_ndjson_writer(run_args: &mut RunArgs) -> std::thread::JoinHandle<std::io::Result<()>> {
+    use std::io::Write;
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<RequestOutcome>(EVENTS_CHANNEL_CAPACITY);
+    run_args.events = Some(tx);
+    std::thread::spawn(move || {
+        // stdout is line buffered, so each record goes out as it is written
+        let mut out = std::io::stdout().lock();
+        while let Some(o) = rx.blocking_recv() {
+            writeln!(out, "{}", o.to_ndjson_line())?;
+        }
+        out.flush()
+    })
+}
+
+/// Runs once, --repeat times or once per --compare-methods method and emits the report(s);
+/// returns the (combined) result, or one per compared method.
+async fn run_all(
+    run_args: RunArgs,
+    output: OutputFormat,
+    held: &mut Option<String>,
+) -> anyhow::Result<Vec<RunResult>> {
+    let mut emit = |text: &str| match held {
+        Some(buf) => buf.push_str(text),
+        None => print!("{text}"),
+    };
+
+    if !run_args.compare_methods.is_empty() {
+        if run_args.repeat > 1 {
+            return Err(anyhow::anyhow!(
+                "--compare-methods cannot be combined with --repeat"
+            ));
+        }
+        let results = compare_methods(&run_args).await?;
+        match output {
+            OutputFormat::Text => emit(&render_method_comparison(&results)),
+            OutputFormat::Json => {
+                let mut s =
+                    serde_json::to_string_pretty(&results).context("Failed to serialize result")?;
+                s.push('\n');
+                emit(&s);
+            }
+            OutputFormat::Summary => {
+                for r in &results {
+                    emit(&render_report_summary(r));
+                }
+            }
+        }
+        return Ok(results);
+    }
+
+    if run_args.repeat <= 1 {
+        let result = run(run_args).await?;
+        emit(&render_output(&result, output)?);
+        return Ok(vec![result]);
+    }
+
+    let total = run_args.repeat;
+    let mut runs = Vec::with_capacity(total as usize);
+    for i in 1..=total {
+        let result = run(run_args.clone()).await?;
+        if output == OutputFormat::Text {
+            emit(&format!(
+                "== Run {i} of {total} ==\n{}\n",
+                render_report(&result)
+            ));
+        }
+        runs.push(result);
+    }
+    let Some(combined) = combine_runs(&runs)? else {
+        return Ok(Vec::new());
+    };
+    if output == OutputFormat::Text {
+        emit(&format!("== Combined ({total} runs) ==\n"));
+    }
+    emit(&render_output(&combined, output)?);
+    Ok(vec![combined])
+}
+
+/// One finished request, as streamed by `run_streaming`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestOutcome {
+    /// 1-based request sequence number (across all workers)
+    pub seq: u64,
+    /// index of the --scenario step or --paths-file path (0 for a plain run)
+    pub step: usize,
+    /// when the response (or error) arrived
+    pub timestamp: SystemTime,
+    pub latency_micros: u64,
+    /// HTTP status, or the network error
+    pub status: Result<u16, NetErrKind>,
+    /// --inject-trace-header: the trace ID sent in traceparent
+    pub trace_id: Option<String>,
+}
+
+impl RequestOutcome {
+    /// The --events-file line, without the trailing newline.
+    pub fn to_json_line(&self) -> String {
+        self.to_json().to_string()
+    }
+
+    /// The --stream-ndjson request record: the --events-file fields plus "type":"request".
+    pub fn to_ndjson_line(&self) -> String {
+        let mut v = self.to_json();
+        v["type"] = "request".into();
+        v.to_string()
+    }
+
+    fn to_json(&self) -> Value {
+        let mut v = serde_json::json!({
+            "seq": self.seq,
+            "step": self.step,
+            "timestamp_ms": self
+                .timestamp
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis() as u64),
+            "latency_ms": self.latency_micros as f64 / 1000.0,
+        });
+        match self.status {
+            Ok(code) => v["status"] = code.into(),
+            Err(kind) => v["error"] = kind.as_str().into(),
+        }
+        if let Some(id) = &self.trace_id {
+            v["trace_id"] = id.as_str().into();
+        }
+        v
+    }
+}
+
+/// Outcomes buffered between the workers and an events consumer (`run_streaming`,
+/// --events-file, --stream-ndjson). Once it is full, workers wait for the consumer before
+/// sending more, so a slow reader slows the run down instead of growing memory.
+pub const EVENTS_CHANNEL_CAPACITY: usize = 1024;
+
+/// Starts `run` in the background and yields each request of the main run as it finishes.
+/// The stream ends when the run is over; the handle then holds the usual `RunResult`. The
+/// stream has to be read: workers wait once `EVENTS_CHANNEL_CAPACITY` outcomes are unread.
+pub fn run_streaming(
+    mut args: RunArgs,
+) -> (
+    impl Stream<Item = RequestOutcome> + Send + Unpin,
+    tokio::task::JoinHandle<anyhow::Result<RunResult>>,
+) {
+    let (tx, rx) = tokio::sync::mpsc::channel(EVENTS_CHANNEL_CAPACITY);
+    args.events = Some(tx);
+    (ReceiverStream::new(rx), tokio::spawn(run(args)))
+}
+
+/// Runs the configured test once per --compare-methods entry, back to back.
+pub async fn compare_methods(args: &RunArgs) -> anyhow::Result<Vec<RunResult>> {
+    for m in &args.compare_methods {
+        parse_http_method(m).ok_or_else(|| anyhow::anyhow!("Invalid --compare-methods: {m}"))?;
+    }
+    let mut results = Vec::with_capacity(args.compare_methods.len());
+    for m in &args.compare_methods {
+        let mut a = args.clone();
+        a.method = m.trim().to_ascii_uppercase();
+        results.push(run(a).await?);
+    }
+    Ok(results)
+}
+
+/// Parses a command line like the binary does: defaults < --config file < env vars
+/// and flags.
+pub fn parse_args<I, T>(argv: I) -> anyhow::Result<(Args, ArgMatches)>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let mut argv: Vec<OsString> = argv.into_iter().map(Into::into).collect();
+    // first pass only finds --config and what the command line sets itself; --url may
+    // still be missing at this point
+    let given = Args::command()
+        .ignore_errors(true)
+        .try_get_matches_from(&argv)?;
+    if let Some(path) = given.get_one::<String>("config") {
+        let extra = config_args(path, &given)?;
+        argv.extend(extra.into_iter().map(OsString::from));
+    }
+    let matches = Args::command().try_get_matches_from(&argv)?;
+    let args = Args::from_arg_matches(&matches)?;
+    Ok((args, matches))
+}
+
+/// Turns a --config file into "--flag=value" arguments, leaving out the options
+/// `given` already has from the command line or the environment.
+pub fn config_args(path: &str, given: &ArgMatches) -> anyhow::Result<Vec<String>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read --config {path}: {e}"))?;
+    let invalid = |msg: String| anyhow::anyhow!("Invalid --config {path}: {msg}");
+    let doc: BTreeMap<String, Value> = if path.ends_with(".toml") {
+        toml::from_str(&text).map_err(|e| invalid(e.to_string()))?
+    } else {
+        // YAML is a superset of JSON, so this reads both
+        serde_yaml::from_str(&text).map_err(|e| invalid(e.to_string()))?
+    };
+    let cmd = Args::command();
+    let mut out = Vec::new();
+    for (key, value) in doc {
+        let name = key.replace('-', "_");
+        let arg = cmd
+            .get_arguments()
+            .find(|a| {
+                a.get_id() == name.as_str()
+                    || a.get_long().is_some_and(|l| l.replace('-', "_") == name)
+            })
+            .filter(|a| a.get_id() != "config")
+            .ok_or_else(|| invalid(format!("unknown option {key}")))?;
+        let source = given.value_source(arg.get_id().as_str());
+        if matches!(
+            source,
+            Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+        ) {
+            continue;
+        }
+        let long = arg.get_long().unwrap_or(arg.get_id().as_str());
+        let values = match value {
+            Value::Array(items) => items,
+            Value::Null => continue,
+            v => vec![v],
+        };
+        for v in values {
+            let v = match v {
+                Value::String(s) => s,
+                Value::Number(n) => n.to_string(),
+                Value::Bool(b) if !arg.get_action().takes_values() => {
+                    if b {
+                        out.push(format!("--{long}"));
+                    }
+                    continue;
+                }
+                Value::Bool(b) => b.to_string(),
+                _ => return Err(invalid(format!("{key} must be a scalar or a list"))),
+            };
+            out.push(format!("--{long}={v}"));
+        }
+    }
+    Ok(out)
+}
+
+/// Secret-bearing flags and the env var each one falls back to.
+const SECRET_ENV_VARS: [(&str, &str); 3] = [
+    ("api_key", "ET_API_KEY"),
+    ("basic_pass", "ET_BASIC_PASS"),
+    ("proxy_auth", "ET_PROXY_AUTH"),
+];
+
+/// Records which secrets were supplied through their environment variable.
+pub fn credential_sources(matches: &ArgMatches) -> BTreeMap<String, String> {
+    let mut out = BTreeMap::new();
+    for (id, env) in SECRET_ENV_VARS {
+        if matches.value_source(id) == Some(ValueSource::EnvVariable) {
+            out.insert(id.to_string(), format!("env {env}"));
+        }
+    }
+    out
+}
+
+#[derive(Debug, Clone)]
+pub struct RunArgs {
+    pub url: String,
+    pub auto_encode_url: bool,
+    pub method: String,
+    pub concurrency: usize,
+    pub max_rps_per_worker: Option<f64>,
+    pub open_loop: bool,
+    pub arrival_rate: Option<f64>,
+    pub max_inflight: Option<u64>,
+    pub on_overload: OnOverload,
+    pub trace_file: Option<String>,
+    pub requests: Option<u64>,
+    pub once: bool,
+    pub latency_budget: bool,
+    pub latency_budget_allow_unsafe: bool,
+    pub requests_per_worker: Option<u64>,
+    pub cycles: Option<u64>,
+    pub duration: Option<String>,
+    pub timeout: String,
+    pub hard_timeout: Option<String>,
+    pub abort_if_initial_failures: Option<String>,
+    pub shutdown_grace: Option<String>,
+    pub headers: Vec<String>,
+    pub api_key: Option<String>,
+    pub hmac_sign: Option<String>,
+    pub basic_user: Option<String>,
+    pub basic_pass: Option<String>,
+    pub proxy: Option<String>,
+    pub proxy_auth: Option<String>,
+    pub unix_socket: Option<String>,
+    pub tcp_nodelay: bool,
+    pub tcp_keepalive: Option<String>,
+    pub recycle_client_every: Option<u64>,
+    pub address_family: AddressFamily,
+    /// Where each provided secret came from (e.g. "api_key" -> "env ET_API_KEY");
+    /// secrets missing here are reported as coming from their flag.
+    pub credential_sources: BTreeMap<String, String>,
+    /// Time source of the worker pool (pacing, deadlines, latencies); tests swap in a
+    /// `MockClock`
+    pub clock: Arc<dyn Clock>,
+    pub with_idempotency_key: bool,
+    pub idempotency_key_header: String,
+    pub inject_trace_header: bool,
+    pub expect_100_continue: bool,
+    pub json: Option<String>,
+    pub json_file: Option<String>,
+    pub stream_body_file: Option<String>,
+    pub body_file: Option<String>,
+    pub payload_command: Option<String>,
+    pub payload_command_cache: bool,
+    pub form_files: Vec<String>,
+    pub form_fields: Vec<String>,
+    pub body_size: Option<String>,
+    pub body_fill: BodyFill,
+    pub compress_body: Option<BodyCompression>,
+    pub grpc: bool,
+    pub scenario: Option<String>,
+    pub paths_file: Option<String>,
+    pub hosts_file: Option<String>,
+    pub method_bodies: Vec<String>,
+    pub select: Select,
+    pub progress_every: u64,
+    pub progress_format: ProgressFormat,
+    pub log_level: LogLevel,
+    pub measure_preflight: bool,
+    pub measure_handshake: Option<u64>,
+    pub measure_tls_resumption: Option<u64>,
+    pub measure_ttfb: bool,
+    pub normalize_latency_by_payload_size: bool,
+    pub parse_server_timing: bool,
+    pub dedupe_errors: bool,
+    pub no_body_download: bool,
+    pub capture_etag: bool,
+    pub respect_retry_after: bool,
+    pub retries: u32,
+    pub retry_backoff: String,
+    pub retry_backoff_mode: BackoffMode,
+    pub retry_backoff_max: String,
+    pub query_params: Vec<String>,
+    pub tags: Vec<String>,
+    pub min_samples: Option<u64>,
+    pub repeat: u32,
+    pub compare_methods: Vec<String>,
+    pub user_agent: String,
+    pub warmup: u64,
+    pub warm_compare: Option<u64>,
+    pub stages: Option<String>,
+    pub burst: Option<String>,
+    pub concurrency_profile: Option<String>,
+    pub expect_headers: Vec<String>,
+    pub success_body_contains: Option<String>,
+    pub success_body_regex: Option<String>,
+    pub response_schema: Option<String>,
+    pub max_validate_bytes: String,
+    pub expect_body_size_range: Option<String>,
+    pub validate_sample_rate: f64,
+    pub fail_on_validation: bool,
+    pub fail_on_worker_panic: bool,
+    pub output: OutputFormat,
+    pub capture_slow_over: Option<String>,
+    pub top_slow: Option<usize>,
+    /// --har-sample-rate, when --har-file is given
+    pub har_sample_rate: Option<f64>,
+    pub hist_sigfigs: u8,
+    pub hist_max: Option<String>,
+    pub sample_interval: Option<String>,
+    /// --heatmap-bucket, set when --heatmap-csv asks for per-window bucket counts
+    pub heatmap_bucket: Option<String>,
+    pub seed: Option<u64>,
+    pub soak: bool,
+    pub rps_timeline: bool,
+    pub soak_max_drift: f64,
+    pub until_stable: bool,
+    pub stable_window: String,
+    pub stable_tolerance: f64,
+    pub apdex_threshold: Option<String>,
+    /// Counters updated by the workers while the run is going (used by --tui)
+    pub live: Option<Arc<LiveStats>>,
+    /// Receives every finished request of the main run (see `run_streaming`)
+    pub events: Option<Sender<RequestOutcome>>,
+}
+
+impl From<Args> for RunArgs {
+    fn from(a: Args) -> Self {
+        Self {
+            url: a.url.unwrap_or_default(),
+            auto_encode_url: a.auto_encode_url,
+            method: a.method,
+            concurrency: a.concurrency,
+            max_rps_per_worker: a.max_rps_per_worker,
+            open_loop: a.open_loop,
+            arrival_rate: a.arrival_rate,
+            max_inflight: a.max_inflight,
+            on_overload: a.on_overload,
+            trace_file: a.trace_file,
+            requests: a.requests,
+            once: a.once,
+            latency_budget: a.latency_budget,
+            latency_budget_allow_unsafe: a.latency_budget_allow_unsafe,
+            requests_per_worker: a.requests_per_worker,
+            cycles: a.cycles,
+            duration: a.duration,
+            timeout: a.timeout,
+            hard_timeout: a.hard_timeout,
+            abort_if_initial_failures: a.abort_if_initial_failures,
+            shutdown_grace: a.shutdown_grace,
+            // blank lines of a multi-line $ET_HEADERS
+            headers: a
+                .headers
+                .into_iter()
+                .filter(|h| !h.trim().is_empty())
+                .collect(),
+            api_key: a.api_key,
+            hmac_sign: a.hmac_sign,
+            basic_user: a.basic_user,
+            basic_pass: a.basic_pass,
+            proxy: a.proxy,
+            proxy_auth: a.proxy_auth,
+            unix_socket: a.unix_socket,
+            tcp_nodelay: a.tcp_nodelay,
+            tcp_keepalive: a.tcp_keepalive,
+            recycle_client_every: a.recycle_client_every,
+            address_family: a.address_family,
+            credential_sources: BTreeMap::new(),
+            clock: Arc::new(SystemClock),
+            with_idempotency_key: a.with_idempotency_key,
+            idempotency_key_header: a.idempotency_key_header,
+            inject_trace_header: a.inject_trace_header,
+            expect_100_continue: a.expect_100_continue,
+            json: a.json,
+            json_file: a.json_file,
+            stream_body_file: a.stream_body_file,
+            body_file: a.body_file,
+            payload_command: a.payload_command,
+            payload_command_cache: a.payload_command_cache,
+            form_files: a.form_files,
+            form_fields: a.form_fields,
+            body_size: a.body_size,
+            body_fill: a.body_fill,
+            compress_body: a.compress_body,
+            grpc: a.grpc,
+            scenario: a.scenario,
+            paths_file: a.paths_file,
+            hosts_file: a.hosts_file,
+            method_bodies: a.method_bodies,
+            select: a.select,
+            progress_every: a.progress_every,
+            progress_format: a.progress_format,
+            log_level: if a.quiet {
+                LogLevel::Quiet
+            } else if a.verbose {
+                LogLevel::Verbose
+            } else {
+                LogLevel::Normal
+            },
+            measure_preflight: a.measure_preflight,
+            measure_handshake: a.measure_handshake,
+            measure_tls_resumption: a.measure_tls_resumption,
+            measure_ttfb: a.measure_ttfb,
+            normalize_latency_by_payload_size: a.normalize_latency_by_payload_size,
+            parse_server_timing: a.parse_server_timing,
+            dedupe_errors: a.dedupe_errors,
+            no_body_download: a.no_body_download,
+            capture_etag: a.capture_etag,
+            respect_retry_after: a.respect_retry_after,
+            retries: a.retries,
+            retry_backoff: a.retry_backoff,
+            retry_backoff_mode: a.retry_backoff_mode,
+            retry_backoff_max: a.retry_backoff_max,
+            query_params: a.query_params,
+            tags: a.tags,
+            min_samples: a.min_samples,
+            repeat: a.repeat,
+            compare_methods: a.compare_methods,
+            user_agent: a.user_agent,
+            warmup: a.warmup,
+            warm_compare: a.warm_compare,
+            stages: a.stages,
+            burst: a.burst,
+            concurrency_profile: a.concurrency_profile,
+            expect_headers: a.expect_headers,
+            success_body_contains: a.success_body_contains,
+            success_body_regex: a.success_body_regex,
+            response_schema: a.response_schema,
+            max_validate_bytes: a.max_validate_bytes,
+            expect_body_size_range: a.expect_body_size_range,
+            validate_sample_rate: a.validate_sample_rate,
+            fail_on_validation: a.fail_on_validation,
+            fail_on_worker_panic: a.fail_on_worker_panic,
+            output: a.output,
+            capture_slow_over: a.capture_slow_over,
+            top_slow: a.top_slow,
+            har_sample_rate: a.har_file.as_ref().map(|_| a.har_sample_rate),
+            hist_sigfigs: a.hist_sigfigs,
+            hist_max: a.hist_max,
+            sample_interval: a.sample_interval,
+            heatmap_bucket: a.heatmap_csv.as_ref().map(|_| a.heatmap_bucket),
+            seed: a.seed,
+            soak: a.soak,
+            rps_timeline: a.rps_timeline,
+            soak_max_drift: a.soak_max_drift,
+            until_stable: a.until_stable,
+            stable_window: a.stable_window,
+            stable_tolerance: a.stable_tolerance,
+            apdex_threshold: a.apdex_threshold,
+            live: None,
+            events: None,
+        }
+    }
+}
+
+impl RunArgs {
+    /// Starts a builder with the same defaults as the CLI.
+    pub fn builder(url: impl Into<String>) -> RunArgsBuilder {
+        RunArgsBuilder {
+            args: RunArgs {
+                url: url.into(),
+                auto_encode_url: false,
+                method: "GET".to_string(),
+                concurrency: 4,
+                max_rps_per_worker: None,
+                open_loop: false,
+                arrival_rate: None,
+                max_inflight: None,
+                on_overload: OnOverload::Drop,
+                trace_file: None,
+                requests: None,
+                once: false,
+                latency_budget: false,
+                latency_budget_allow_unsafe: false,
+                requests_per_worker: None,
+                cycles: None,
+                duration: None,
+                timeout: "2s".to_string(),
+                hard_timeout: None,
+                abort_if_initial_failures: None,
+                shutdown_grace: None,
+                headers: Vec::new(),
+                api_key: None,
+                hmac_sign: None,
+                basic_user: None,
+                basic_pass: None,
+                proxy: None,
+                proxy_auth: None,
+                unix_socket: None,
+                tcp_nodelay: true,
+                tcp_keepalive: None,
+                recycle_client_every: None,
+                address_family: AddressFamily::Auto,
+                credential_sources: BTreeMap::new(),
+                clock: Arc::new(SystemClock),
+                with_idempotency_key: false,
+                idempotency_key_header: "Idempotency-Key".to_string(),
+                inject_trace_header: false,
+                expect_100_continue: false,
+                json: None,
+                json_file: None,
+                stream_body_file: None,
+                body_file: None,
+                payload_command: None,
+                payload_command_cache: false,
+                form_files: Vec::new(),
+                form_fields: Vec::new(),
+                body_size: None,
+                body_fill: BodyFill::Random,
+                compress_body: None,
+                grpc: false,
+                scenario: None,
+                paths_file: None,
+                hosts_file: None,
+                method_bodies: Vec::new(),
+                select: Select::RoundRobin,
+                progress_every: 1000,
+                progress_format: ProgressFormat::Text,
+                log_level: LogLevel::Normal,
+                measure_preflight: false,
+                measure_handshake: None,
+                measure_tls_resumption: None,
+                measure_ttfb: false,
+                normalize_latency_by_payload_size: false,
+                parse_server_timing: false,
+                dedupe_errors: false,
+                no_body_download: false,
+                capture_etag: false,
+                respect_retry_after: false,
+                retries: 0,
+                retry_backoff: "100ms".to_string(),
+                retry_backoff_mode: BackoffMode::Fixed,
+                retry_backoff_max: "10s".to_string(),
+                query_params: Vec::new(),
+                tags: Vec::new(),
+                min_samples: None,
+                repeat: 1,
+                compare_methods: Vec::new(),
+                user_agent: DEFAULT_USER_AGENT.to_string(),
+                warmup: 0,
+                warm_compare: None,
+                stages: None,
+                burst: None,
+                concurrency_profile: None,
+                expect_headers: Vec::new(),
+                success_body_contains: None,
+                success_body_regex: None,
+                response_schema: None,
+                max_validate_bytes: "64k".to_string(),
+                expect_body_size_range: None,
+                validate_sample_rate: 1.0,
+                fail_on_validation: false,
+                fail_on_worker_panic: false,
+                output: OutputFormat::Text,
+                capture_slow_over: None,
+                top_slow: None,
+                har_sample_rate: None,
+                hist_sigfigs: 3,
+                hist_max: None,
+                sample_interval: None,
+                heatmap_bucket: None,
+                seed: None,
+                soak: false,
+                rps_timeline: false,
+                soak_max_drift: 10.0,
+                until_stable: false,
+                stable_window: "5s".into(),
+                stable_tolerance: 5.0,
+                apdex_threshold: None,
+                live: None,
+                events: None,
+            },
+        }
+    }
+}
+
+/// Fluent construction of `RunArgs`; see `RunArgs::builder`.
+#[derive(Debug, Clone)]
+pub struct RunArgsBuilder {
+    args: RunArgs,
+}
+
+impl RunArgsBuilder {
+    pub fn method(mut self, method: impl Into<String>) -> Self {
+        self.args.method = method.into();
+        self
+    }
+
+    pub fn concurrency(mut self, n: usize) -> Self {
+        self.args.concurrency = n;
+        self
+    }
+
+    pub fn max_rps_per_worker(mut self, n: f64) -> Self {
+        self.args.max_rps_per_worker = Some(n);
+        self
+    }
+
+    /// Open-loop Poisson arrivals at `rps` (sets --open-loop and --arrival-rate).
+    pub fn open_loop(mut self, rps: f64) -> Self {
+        self.args.open_loop = true;
+        self.args.arrival_rate = Some(rps);
+        self
+    }
+
+    /// --max-inflight with what to do past it.
+    pub fn max_inflight(mut self, n: u64, on_overload: OnOverload) -> Self {
+        self.args.max_inflight = Some(n);
+        self.args.on_overload = on_overload;
+        self
+    }
+
+    pub fn trace_file(mut self, path: impl Into<String>) -> Self {
+        self.args.trace_file = Some(path.into());
+        self
+    }
+
+    pub fn requests(mut self, n: u64) -> Self {
+        self.args.requests = Some(n);
+        self
+    }
+
+    pub fn once(mut self, yes: bool) -> Self {
+        self.args.once = yes;
+        self
+    }
+
+    pub fn latency_budget(mut self, yes: bool) -> Self {
+        self.args.latency_budget = yes;
+        self
+    }
+
+    pub fn latency_budget_allow_unsafe(mut self, yes: bool) -> Self {
+        self.args.latency_budget_allow_unsafe = yes;
+        self
+    }
+
+    pub fn cycles(mut self, k: u64) -> Self {
+        self.args.cycles = Some(k);
+        self
+    }
+
+    pub fn requests_per_worker(mut self, n: u64) -> Self {
+        self.args.requests_per_worker = Some(n);
+        self
+    }
+
+    pub fn duration(mut self, d: impl Into<String>) -> Self {
+        self.args.duration = Some(d.into());
+        self
+    }
+
+    pub fn timeout(mut self, t: impl Into<String>) -> Self {
+        self.args.timeout = t.into();
+        self
+    }
+
+    pub fn hard_timeout(mut self, d: impl Into<String>) -> Self {
+        self.args.hard_timeout = Some(d.into());
+        self
+    }
+
+    /// Aborts when more than `n` of the first `m` requests fail to connect or time out.
+    pub fn abort_if_initial_failures(mut self, n: u64, m: u64) -> Self {
+        self.args.abort_if_initial_failures = Some(format!("{n}/{m}"));
+        self
+    }
+
+    pub fn shutdown_grace(mut self, d: impl Into<String>) -> Self {
+        self.args.shutdown_grace = Some(d.into());
+        self
+    }
+
+    /// Adds one "Key: Value" header (repeatable).
+    pub fn header(mut self, h: impl Into<String>) -> Self {
+        self.args.headers.push(h.into());
+        self
+    }
+
+    pub fn api_key(mut self, token: impl Into<String>) -> Self {
+        self.args.api_key = Some(token.into());
+        self
+    }
+
+    pub fn hmac_sign(mut self, spec: impl Into<String>) -> Self {
+        self.args.hmac_sign = Some(spec.into());
+        self
+    }
+
+    pub fn basic_auth(mut self, user: impl Into<String>, pass: impl Into<String>) -> Self {
+        self.args.basic_user = Some(user.into());
+        self.args.basic_pass = Some(pass.into());
+        self
+    }
+
+    pub fn proxy(mut self, url: impl Into<String>) -> Self {
+        self.args.proxy = Some(url.into());
+        self
+    }
+
+    /// Proxy credentials as "user:pass".
+    pub fn proxy_auth(mut self, auth: impl Into<String>) -> Self {
+        self.args.proxy_auth = Some(auth.into());
+        self
+    }
+
+    pub fn unix_socket(mut self, path: impl Into<String>) -> Self {
+        self.args.unix_socket = Some(path.into());
+        self
+    }
+
+    pub fn tcp_nodelay(mut self, on: bool) -> Self {
+        self.args.tcp_nodelay = on;
+        self
+    }
+
+    pub fn tcp_keepalive(mut self, d: impl Into<String>) -> Self {
+        self.args.tcp_keepalive = Some(d.into());
+        self
+    }
+
+    pub fn recycle_client_every(mut self, n: u64) -> Self {
+        self.args.recycle_client_every = Some(n);
+        self
+    }
+
+    pub fn address_family(mut self, family: AddressFamily) -> Self {
+        self.args.address_family = family;
+        self
+    }
+
+    /// Sends a fresh UUID per request in `header` (e.g. "Idempotency-Key").
+    pub fn idempotency_key(mut self, header: impl Into<String>) -> Self {
+        self.args.with_idempotency_key = true;
+        self.args.idempotency_key_header = header.into();
+        self
+    }
+
+    pub fn inject_trace_header(mut self, yes: bool) -> Self {
+        self.args.inject_trace_header = yes;
+        self
+    }
+
+    pub fn expect_100_continue(mut self) -> Self {
+        self.args.expect_100_continue = true;
+        self
+    }
+
+    pub fn json(mut self, j: impl Into<String>) -> Self {
+        self.args.json = Some(j.into());
+        self
+    }
+
+    pub fn json_file(mut self, path: impl Into<String>) -> Self {
+        self.args.json_file = Some(path.into());
+        self
+    }
+
+    pub fn stream_body_file(mut self, path: impl Into<String>) -> Self {
+        self.args.stream_body_file = Some(path.into());
+        self
+    }
+
+    pub fn body_file(mut self, path: impl Into<String>) -> Self {
+        self.args.body_file = Some(path.into());
+        self
+    }
+
+    /// Shell command printing the body; `cache` runs it once per worker.
+    pub fn payload_command(mut self, cmd: impl Into<String>, cache: bool) -> Self {
+        self.args.payload_command = Some(cmd.into());
+        self.args.payload_command_cache = cache;
+        self
+    }
+
+    /// Unary gRPC mode (see `--grpc`).
+    pub fn grpc(mut self, yes: bool) -> Self {
+        self.args.grpc = yes;
+        self
+    }
+
+    /// Adds one multipart file part, "field=@path" (repeatable).
+    pub fn form_file(mut self, f: impl Into<String>) -> Self {
+        self.args.form_files.push(f.into());
+        self
+    }
+
+    /// Adds one multipart text part, "name=value" (repeatable).
+    pub fn form_field(mut self, f: impl Into<String>) -> Self {
+        self.args.form_fields.push(f.into());
+        self
+    }
+
+    /// Generated body size like "64k" (see `parse_byte_size`).
+    pub fn body_size(mut self, size: impl Into<String>) -> Self {
+        self.args.body_size = Some(size.into());
+        self
+    }
+
+    pub fn body_fill(mut self, fill: BodyFill) -> Self {
+        self.args.body_fill = fill;
+        self
+    }
+
+    pub fn compress_body(mut self, compression: BodyCompression) -> Self {
+        self.args.compress_body = Some(compression);
+        self
+    }
+
+    pub fn scenario(mut self, path: impl Into<String>) -> Self {
+        self.args.scenario = Some(path.into());
+        self
+    }
+
+    pub fn paths_file(mut self, path: impl Into<String>) -> Self {
+        self.args.paths_file = Some(path.into());
+        self
+    }
+
+    pub fn hosts_file(mut self, path: impl Into<String>) -> Self {
+        self.args.hosts_file = Some(path.into());
+        self
+    }
+
+    /// Adds one --method-body entry, "METHOD=body.json" or just "METHOD".
+    pub fn method_body(mut self, entry: impl Into<String>) -> Self {
+        self.args.method_bodies.push(entry.into());
+        self
+    }
+
+    pub fn select(mut self, select: Select) -> Self {
+        self.args.select = select;
+        self
+    }
+
+    pub fn progress_every(mut self, n: u64) -> Self {
+        self.args.progress_every = n;
+        self
+    }
+
+    pub fn progress_format(mut self, f: ProgressFormat) -> Self {
+        self.args.progress_format = f;
+        self
+    }
+
+    pub fn log_level(mut self, level: LogLevel) -> Self {
+        self.args.log_level = level;
+        self
+    }
+
+    pub fn measure_preflight(mut self, yes: bool) -> Self {
+        self.args.measure_preflight = yes;
+        self
+    }
+
+    /// Number of fresh-connection HEAD requests timed before the run.
+    pub fn measure_handshake(mut self, n: u64) -> Self {
+        self.args.measure_handshake = Some(n);
+        self
+    }
+
+    pub fn measure_tls_resumption(mut self, n: u64) -> Self {
+        self.args.measure_tls_resumption = Some(n);
+        self
+    }
+
+    pub fn measure_ttfb(mut self, yes: bool) -> Self {
+        self.args.measure_ttfb = yes;
+        self
+    }
+
+    pub fn normalize_latency_by_payload_size(mut self, yes: bool) -> Self {
+        self.args.normalize_latency_by_payload_size = yes;
+        self
+    }
+
+    pub fn parse_server_timing(mut self, yes: bool) -> Self {
+        self.args.parse_server_timing = yes;
+        self
+    }
+
+    pub fn dedupe_errors(mut self, yes: bool) -> Self {
+        self.args.dedupe_errors = yes;
+        self
+    }
+
+    pub fn auto_encode_url(mut self, yes: bool) -> Self {
+        self.args.auto_encode_url = yes;
+        self
+    }
+
+    pub fn no_body_download(mut self, yes: bool) -> Self {
+        self.args.no_body_download = yes;
+        self
+    }
+
+    pub fn capture_etag(mut self, yes: bool) -> Self {
+        self.args.capture_etag = yes;
+        self
+    }
+
+    pub fn respect_retry_after(mut self, yes: bool) -> Self {
+        self.args.respect_retry_after = yes;
+        self
+    }
+
+    pub fn retries(mut self, n: u32) -> Self {
+        self.args.retries = n;
+        self
+    }
+
+    /// Retry pause: `base` (e.g. "100ms"), growth `mode` and the cap for exponential mode.
+    pub fn retry_backoff(
+        mut self,
+        base: impl Into<String>,
+        mode: BackoffMode,
+        max: impl Into<String>,
+    ) -> Self {
+        self.args.retry_backoff = base.into();
+        self.args.retry_backoff_mode = mode;
+        self.args.retry_backoff_max = max.into();
+        self
+    }
+
+    /// Adds one "key=value" query param (repeatable).
+    pub fn query_param(mut self, q: impl Into<String>) -> Self {
+        self.args.query_params.push(q.into());
+        self
+    }
+
+    /// Adds one "key=value" result tag (repeatable).
+    pub fn tag(mut self, t: impl Into<String>) -> Self {
+        self.args.tags.push(t.into());
+        self
+    }
+
+    pub fn min_samples(mut self, n: u64) -> Self {
+        self.args.min_samples = Some(n);
+        self
+    }
+
+    pub fn repeat(mut self, n: u32) -> Self {
+        self.args.repeat = n;
+        self
+    }
+
+    /// Adds one method to --compare-methods (repeatable).
+    pub fn compare_method(mut self, m: impl Into<String>) -> Self {
+        self.args.compare_methods.push(m.into());
+        self
+    }
+
+    pub fn user_agent(mut self, ua: impl Into<String>) -> Self {
+        self.args.user_agent = ua.into();
+        self
+    }
+
+    pub fn warmup(mut self, n: u64) -> Self {
+        self.args.warmup = n;
+        self
+    }
+
+    pub fn warm_compare(mut self, n: u64) -> Self {
+        self.args.warm_compare = Some(n);
+        self
+    }
+
+    /// Load/idle cycle like "on=10s,off=20s" (see `parse_burst`).
+    pub fn burst(mut self, spec: impl Into<String>) -> Self {
+        self.args.burst = Some(spec.into());
+        self
+    }
+
+    pub fn concurrency_profile(mut self, spec: impl Into<String>) -> Self {
+        self.args.concurrency_profile = Some(spec.into());
+        self
+    }
+
+    pub fn stages(mut self, st: impl Into<String>) -> Self {
+        self.args.stages = Some(st.into());
+        self
+    }
+
+    /// Adds one "Name: Value" (or "Name") response header assertion (repeatable).
+    pub fn expect_header(mut self, h: impl Into<String>) -> Self {
+        self.args.expect_headers.push(h.into());
+        self
+    }
+
+    pub fn success_body_contains(mut self, text: impl Into<String>) -> Self {
+        self.args.success_body_contains = Some(text.into());
+        self
+    }
+
+    pub fn success_body_regex(mut self, pattern: impl Into<String>) -> Self {
+        self.args.success_body_regex = Some(pattern.into());
+        self
+    }
+
+    pub fn response_schema(mut self, path: impl Into<String>) -> Self {
+        self.args.response_schema = Some(path.into());
+        self
+    }
+
+    /// Body read limit of the --success-body-* and --response-schema checks like "64k" (see `parse_byte_size`).
+    pub fn max_validate_bytes(mut self, size: impl Into<String>) -> Self {
+        self.args.max_validate_bytes = size.into();
+        self
+    }
+
+    pub fn expect_body_size_range(mut self, range: impl Into<String>) -> Self {
+        self.args.expect_body_size_range = Some(range.into());
+        self
+    }
+
+    pub fn validate_sample_rate(mut self, rate: f64) -> Self {
+        self.args.validate_sample_rate = rate;
+        self
+    }
+
+    pub fn fail_on_validation(mut self, yes: bool) -> Self {
+        self.args.fail_on_validation = yes;
+        self
+    }
+
+    pub fn fail_on_worker_panic(mut self, yes: bool) -> Self {
+        self.args.fail_on_worker_panic = yes;
+        self
+    }
+
+    pub fn output(mut self, format: OutputFormat) -> Self {
+        self.args.output = format;
+        self
+    }
+
+    pub fn capture_slow_over(mut self, d: impl Into<String>) -> Self {
+        self.args.capture_slow_over = Some(d.into());
+        self
+    }
+
+    pub fn top_slow(mut self, n: usize) -> Self {
+        self.args.top_slow = Some(n);
+        self
+    }
+
+    /// Record this fraction of requests as HAR entries (see `render_har`).
+    pub fn har_sample_rate(mut self, rate: f64) -> Self {
+        self.args.har_sample_rate = Some(rate);
+        self
+    }
+
+    pub fn hist_sigfigs(mut self, sigfigs: u8) -> Self {
+        self.args.hist_sigfigs = sigfigs;
+        self
+    }
+
+    pub fn hist_max(mut self, d: impl Into<String>) -> Self {
+        self.args.hist_max = Some(d.into());
+        self
+    }
+
+    pub fn sample_interval(mut self, d: impl Into<String>) -> Self {
+        self.args.sample_interval = Some(d.into());
+        self
+    }
+
+    /// Record per-window latency bucket counts of this width (see `render_heatmap_csv`).
+    pub fn heatmap_bucket(mut self, d: impl Into<String>) -> Self {
+        self.args.heatmap_bucket = Some(d.into());
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.args.seed = Some(seed);
+        self
+    }
+
+    pub fn soak(mut self, yes: bool) -> Self {
+        self.args.soak = yes;
+        self
+    }
+
+    pub fn rps_timeline(mut self, yes: bool) -> Self {
+        self.args.rps_timeline = yes;
+        self
+    }
+
+    /// Shares live counters with the caller, e.g. for a dashboard.
+    pub fn live(mut self, live: Arc<LiveStats>) -> Self {
+        self.args.live = Some(live);
+        self
+    }
+
+    /// Sends one `RequestOutcome` per finished request to `tx`, waiting while it is full.
+    pub fn events(mut self, tx: Sender<RequestOutcome>) -> Self {
+        self.args.events = Some(tx);
+        self
+    }
+
+    /// p99 drift in ms per hour above which --soak warns.
+    pub fn soak_max_drift(mut self, ms_per_hour: f64) -> Self {
+        self.args.soak_max_drift = ms_per_hour;
+        self
+    }
+
+    /// Stops once p99 changes by at most `tolerance_pct` between two `window`s.
+    pub fn until_stable(mut self, window: impl Into<String>, tolerance_pct: f64) -> Self {
+        self.args.until_stable = true;
+        self.args.stable_window = window.into();
+        self.args.stable_tolerance = tolerance_pct;
+        self
+    }
+
+    pub fn apdex_threshold(mut self, d: impl Into<String>) -> Self {
+        self.args.apdex_threshold = Some(d.into());
+        self
+    }
+
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.args.clock = clock;
+        self
+    }
+
+    pub fn build(self) -> RunArgs {
+        self.args
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunResult {
+    pub url: String,
+    pub method: String,
+    pub concurrency: usize,
+    pub requests_target: Option<u64>,
+    /// --requests-per-worker; `requests_target` then holds the total
+    #[serde(default)]
+    pub requests_per_worker: Option<u64>,
+    pub duration_target: Option<String>,
+    pub timeout: String,
+    /// --seed, when given
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// --tag annotations
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    /// Which secrets were used and where they came from (never the values)
+    #[serde(default)]
+    pub credential_sources: BTreeMap<String, String>,
+    /// --measure-handshake requests, each on a new connection
+    #[serde(default)]
+    pub handshake: Option<Aggregates>,
+    /// --measure-tls-resumption handshakes
+    #[serde(default)]
+    pub tls_resumption: Option<TlsResumption>,
+    /// --warm-compare priming requests (the main run is the "warm" side)
+    #[serde(default)]
+    pub cold: Option<Aggregates>,
+    /// --unix-socket the requests went through
+    #[serde(default)]
+    pub unix_socket: Option<String>,
+    /// Effective socket options, e.g. "tcp_nodelay=on tcp_keepalive=off"
+    #[serde(default)]
+    pub socket: String,
+    /// OS threads of the async runtime the run was driven by
+    #[serde(default)]
+    pub runtime_threads: usize,
+    /// --recycle-client-every, when given
+    #[serde(default)]
+    pub recycle_client_every: Option<u64>,
+    /// Per-worker clients rebuilt under --recycle-client-every
+    #[serde(default)]
+    pub client_recycles: u64,
+    /// --address-family: "auto", "ipv4" or "ipv6"
+    #[serde(default)]
+    pub address_family: String,
+    /// Response bodies were read to the end (off with --no-body-download)
+    #[serde(default)]
+    pub bodies_drained: bool,
+    /// --expect-100-continue was sent
+    #[serde(default)]
+    pub expect_100_continue: bool,
+    pub elapsed_sec: f64,
+    pub sent: u64,
+    pub completed: u64,
+    pub warmup_discarded: u64,
+    /// Workers still stuck after the deadline plus grace and aborted
+    #[serde(default)]
+    pub aborted_workers: u64,
+    /// Workers that panicked; their requests stopped counting from that point on
+    #[serde(default)]
+    pub worker_panics: u64,
+    pub aggregates: Aggregates,
+    /// Per-stage results when --stages is used (the fields above hold the combined totals)
+    #[serde(default)]
+    pub stages: Vec<StageResult>,
+    /// Time series from --sample-interval, oldest first
+    #[serde(default)]
+    pub samples: Vec<IntervalSample>,
+    /// --burst on-phases, oldest first
+    #[serde(default)]
+    pub bursts: Vec<BurstPhase>,
+    /// --concurrency-profile: target and realized concurrency each second
+    #[serde(default)]
+    pub concurrency_timeline: Vec<ConcurrencyPoint>,
+    /// Width of the `IntervalSample::buckets` latency buckets
+    #[serde(default)]
+    pub heatmap_bucket_ms: Option<f64>,
+    /// Stability metrics from --soak
+    #[serde(default)]
+    pub soak: Option<SoakStats>,
+    /// --stages: the concurrency past which throughput stops paying for latency
+    #[serde(default)]
+    pub knee: Option<Knee>,
+    /// Where --until-stable stopped
+    #[serde(default)]
+    pub stable: Option<Stability>,
+    /// Score for --apdex-threshold
+    #[serde(default)]
+    pub apdex: Option<Apdex>,
+    /// --rps-timeline: completions in each elapsed second
+    #[serde(default)]
+    pub rps_timeline: Vec<u64>,
+    /// --open-loop target arrivals per second
+    #[serde(default)]
+    pub arrival_rate: Option<f64>,
+    /// Rate the run aimed for (--arrival-rate, or --max-rps-per-worker times the workers)
+    #[serde(default)]
+    pub target_rps: Option<f64>,
+    /// --max-inflight: arrivals dropped or held back at the cap
+    #[serde(default)]
+    pub overload: Option<OverloadStats>,
+    /// --cycles: passes the workers made
+    #[serde(default)]
+    pub cycles: Option<CycleStats>,
+    /// How closely a --trace-file replay kept to its schedule
+    #[serde(default)]
+    pub replay: Option<ReplayStats>,
+    /// --compress-body payload sizes
+    #[serde(default)]
+    pub body_compression: Option<BodyCompressionStats>,
+    /// --top-slow: the slowest requests, slowest first
+    #[serde(default)]
+    pub top_slow: Vec<TopSlowRequest>,
+    /// --har-file entries in the order they finished; written to their own file, not
+    /// to the JSON result
+    #[serde(skip)]
+    pub har: Vec<HarEntry>,
+    /// --once: the single request in full
+    #[serde(default)]
+    pub once: Option<OnceDetail>,
+    /// Most requests in flight at once
+    #[serde(default)]
+    pub max_in_flight: u64,
+    /// Per-step results when --scenario is used (the fields above hold the totals)
+    #[serde(default)]
+    pub steps: Vec<StepResult>,
+    /// Per-path results when --paths-file or --trace-file is used
+    #[serde(default)]
+    pub paths: Vec<StepResult>,
+    /// Per-entry results when --method-body is used
+    #[serde(default)]
+    pub methods: Vec<StepResult>,
+    /// Per-host results when --hosts-file is used
+    #[serde(default)]
+    pub hosts: Vec<StepResult>,
+}
+
+/// A --scenario file: the journey every worker (virtual user) repeats.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Scenario {
+    pub steps: Vec<ScenarioStep>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScenarioStep {
+    /// label in the report; defaults to "METHOD path"
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default = "default_step_method")]
+    pub method: String,
+    /// resolved against --url like a link ("/login" is host-absolute, "login" relative)
+    pub path: String,
+    /// added to (and overriding) the --header values
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// JSON body
+    #[serde(default)]
+    pub body: Option<Value>,
+    /// responses with another status count as failed checks
+    #[serde(default)]
+    pub expect_status: Option<u16>,
+    /// variables taken from the JSON response body, e.g. `token: $.auth.token`;
+    /// later steps use them as `{{token}}` in header and query param values
+    #[serde(default)]
+    pub capture: BTreeMap<String, String>,
+}
+
+fn default_step_method() -> String {
+    "GET".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepResult {
+    pub name: String,
+    pub method: String,
+    pub url: String,
+    pub aggregates: Aggregates,
+}
+
+/// One --sample-interval window.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IntervalSample {
+    /// end of the window, seconds since the run started
+    pub t_sec: f64,
+    /// window length (the last one may be shorter than the interval)
+    pub width_sec: f64,
+    /// requests completed in the window
+    pub requests: u64,
+    pub p50_ms: f64,
+    pub p99_ms: f64,
+    /// --heatmap-bucket request counts, lowest latency bucket first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub buckets: Vec<u64>,
+}
+
+impl IntervalSample {
+    pub fn rps(&self) -> f64 {
+        if self.width_sec > 0.0 {
+            self.requests as f64 / self.width_sec
+        } else {
+            0.0
+        }
+    }
+}
+
+/// One --burst on-phase; requests still in flight when it ended count toward it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BurstPhase {
+    /// seconds since the run started
+    pub start_sec: f64,
+    /// how long workers were sending (the last one may be cut short by --duration)
+    pub on_sec: f64,
+    pub requests: u64,
+    pub p50_ms: f64,
+    pub p99_ms: f64,
+}
+
+/// How often the --concurrency-profile shaper moves the target.
+const PROFILE_TICK: Duration = Duration::from_millis(100);
+
+/// A --concurrency-profile: how many workers send at each moment of the run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConcurrencyProfile {
+    /// Starts at `min`, peaks at `max` half a period in, back at `min` after a period.
+    Sine {
+        min: usize,
+        max: usize,
+        period: Duration,
+    },
+    /// From `from` to `to` in a straight line over the whole --duration.
+    Linear { from: usize, to: usize },
+}
+
+impl ConcurrencyProfile {
+    /// The most workers the profile ever asks for.
+    pub fn peak(&self) -> usize {
+        match *self {
+            Self::Sine { max, .. } => max,
+            Self::Linear { from, to } => from.max(to),
+        }
+    }
+
+    /// Workers wanted `elapsed` into a run of `total`.
+    pub fn target(&self, elapsed: Duration, total: Duration) -> usize {
+        let (lo, hi, frac) = match *self {
+            Self::Sine { min, max, period } => {
+                let phase = elapsed.as_secs_f64() / period.as_secs_f64();
+                let wave = (1.0 - (std::f64::consts::TAU * phase).cos()) / 2.0;
+                (min as f64, max as f64, wave)
+            }
+            Self::Linear { from, to } => {
+                let frac = if total.is_zero() {
+                    1.0
+                } else {
+                    (elapsed.as_secs_f64() / total.as_secs_f64()).min(1.0)
+                };
+                (from as f64, to as f64, frac)
+            }
+        };
+        (lo + (hi - lo) * frac).round() as usize
+    }
+}
+
+/// One second of a --concurrency-profile run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConcurrencyPoint {
+    /// seconds since the run started
+    pub t_sec: f64,
+    pub target: u64,
+    /// workers not parked: sending, or finishing a request the target no longer covers
+    pub active: u64,
+}
+
+/// Stability over a soak test, derived from the time series.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SoakStats {
+    /// slope of a least-squares fit of p99 over time
+    pub p99_drift_ms_per_hour: f64,
+    /// stddev / mean of per-window throughput
+    pub throughput_cv: f64,
+}
+
+/// Request payload size before and after --compress-body.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BodyCompressionStats {
+    pub encoding: String,
+    pub original_bytes: u64,
+    pub compressed_bytes: u64,
+}
+
+/// Dispatch accuracy of a --trace-file replay: how late requests started compared
+/// with their recorded offsets.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayStats {
+    pub scheduled: u64,
+    pub sent: u64,
+    pub lag_p50_ms: f64,
+    pub lag_p99_ms: f64,
+    pub lag_max_ms: f64,
+}
+
+/// What --max-inflight did to an --open-loop schedule.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OverloadStats {
+    pub max_inflight: u64,
+    /// arrivals skipped with --on-overload drop
+    pub dropped: u64,
+    /// arrivals that waited for a free slot with --on-overload block
+    pub blocked: u64,
+}
+
+impl OverloadStats {
+    /// Adds the arrivals of another run with the same cap.
+    fn add(&mut self, other: &OverloadStats) {
+        self.dropped += other.dropped;
+        self.blocked += other.blocked;
+    }
+}
+
+/// Full passes per worker of a --cycles run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CycleStats {
+    pub target: u64,
+    pub min: u64,
+    pub max: u64,
+    pub avg: f64,
+}
+
+impl CycleStats {
+    fn from_counts(target: u64, counts: &[u64]) -> Self {
+        Self {
+            target,
+            min: counts.iter().copied().min().unwrap_or(0),
+            max: counts.iter().copied().max().unwrap_or(0),
+            avg: counts.iter().sum::<u64>() as f64 / counts.len().max(1) as f64,
+        }
+    }
+
+    /// Folds in another run's passes; `workers` weigh the averages.
+    fn merge(&mut self, other: &CycleStats, workers: usize, other_workers: usize) {
+        let total = (workers + other_workers).max(1) as f64;
+        self.avg = (self.avg * workers as f64 + other.avg * other_workers as f64) / total;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+}
+
+/// One --hosts-file entry.
+#[derive(Debug, Clone, Deserialize)]
+pub struct HostTarget {
+    /// scheme, host and port replace those of --url; its path and query stay
+    pub url: String,
+    /// share of the requests relative to the other hosts
+    #[serde(default = "default_host_weight")]
+    pub weight: u32,
+    /// added to (and overriding) the --header values for this host
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+fn default_host_weight() -> u32 {
+    1
+}
+
+/// One --trace-file line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub offset_ms: u64,
+    pub method: Method,
+    pub path: String,
+}
+
+/// Outcome of --until-stable.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stability {
+    pub window_sec: f64,
+    pub tolerance_pct: f64,
+    /// requests completed when p99 settled; `None` when a cap ended the run first
+    pub after_requests: Option<u64>,
+    pub after_sec: Option<f64>,
+    /// the run length `STABLE_MAX_WINDOWS` imposed when neither --duration nor --requests
+    /// (nor another stop condition) was given
+    #[serde(default)]
+    pub default_cap_sec: Option<f64>,
+}
+
+/// Windows --until-stable waits for p99 to settle when nothing else caps the run.
+pub const STABLE_MAX_WINDOWS: u32 = 60;
+
+/// Apdex over the latency histogram for a target time T.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Apdex {
+    pub threshold_ms: f64,
+    /// latency <= T
+    pub satisfied: u64,
+    /// T < latency <= 4T
+    pub tolerating: u64,
+    pub frustrated: u64,
+    /// (satisfied + tolerating / 2) / total, 0..1
+    pub score: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stage {
+    pub concurrency: usize,
+    pub duration: Duration,
+    pub duration_target: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StageResult {
+    pub concurrency: usize,
+    pub duration_target: String,
+    pub elapsed_sec: f64,
+    pub sent: u64,
+    pub completed: u64,
+    pub aggregates: Aggregates,
+}
+
+/// The --stages stage with the most throughput per unit of latency.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Knee {
+    /// 1-based, as in the report
+    pub stage: usize,
+    pub concurrency: usize,
+    pub rps: f64,
+    pub mean_ms: f64,
+    /// rps per ms of mean latency, the figure the knee maximizes
+    pub rps_per_ms: f64,
+    /// the next stage with more concurrency against this one, in percent
+    pub next: Option<KneeStep>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KneeStep {
+    pub stage: usize,
+    pub concurrency: usize,
+    pub rps_gain_pct: f64,
+    pub latency_gain_pct: f64,
+}
+
+/* ============================= AGGREGATES ============================= */
+
+/// Running totals readable while a run is in progress.
+#[derive(Debug)]
+pub struct LiveStats {
+    completed: AtomicU64,
+    errors: AtomicU64,
+    /// latencies since the last `take_window`
+    window: std::sync::Mutex<Histogram<u64>>,
+}
+
+impl Default for LiveStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LiveStats {
+    pub fn new() -> Self {
+        Self {
+            completed: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            window: std::sync::Mutex::new(histogram_serde::empty()),
+        }
+    }
+
+    /// `error`: network error or 5xx.
+    pub fn record(&self, micros: u64, error: bool) {
+        if let Ok(mut w) = self.window.lock() {
+            record_micros(&mut w, micros);
+        }
+        if error {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        self.completed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn completed(&self) -> u64 {
+        self.completed.load(Ordering::Relaxed)
+    }
+
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    /// Latencies recorded since the previous call.
+    pub fn take_window(&self) -> Histogram<u64> {
+        match self.window.lock() {
+            Ok(mut w) => {
+                let h = w.clone();
+                w.reset();
+                h
+            }
+            Err(_) => histogram_serde::empty(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetErrKind {
+    Timeout,
+    /// name resolution failed (reported apart from other connect errors)
+    Dns,
+    Connect,
+    Request,
+    Body,
+    Decode,
+    Other,
+}
+
+impl NetErrKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NetErrKind::Timeout => "timeout",
+            NetErrKind::Dns => "dns",
+            NetErrKind::Connect => "connect",
+            NetErrKind::Request => "request",
+            NetErrKind::Body => "body",
+            NetErrKind::Decode => "decode",
+            NetErrKind::Other => "other",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct StatusClassCounts {
+    pub c1xx: u64,
+    pub c2xx: u64,
+    pub c3xx: u64,
+    pub c4xx: u64,
+    pub c5xx: u64,
+    pub other: u64,
+}
+
+impl StatusClassCounts {
+    pub fn record(&mut self, code: u16) {
+        match code / 100 {
+            1 => self.c1xx += 1,
+            2 => self.c2xx += 1,
+            3 => self.c3xx += 1,
+            4 => self.c4xx += 1,
+            5 => self.c5xx += 1,
+            _ => self.other += 1,
+        }
+    }
+
+    pub fn merge(&mut self, o: &StatusClassCounts) {
+        self.c1xx += o.c1xx;
+        self.c2xx += o.c2xx;
+        self.c3xx += o.c3xx;
+        self.c4xx += o.c4xx;
+        self.c5xx += o.c5xx;
+        self.other += o.other;
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct NetErrCounts {
+    pub timeout: u64,
+    #[serde(default)]
+    pub dns: u64,
+    pub connect: u64,
+    pub request: u64,
+    pub body: u64,
+    pub decode: u64,
+    pub other: u64,
+}
+
+impl NetErrCounts {
+    pub fn record(&mut self, k: NetErrKind) {
+        match k {
+            NetErrKind::Timeout => self.timeout += 1,
+            NetErrKind::Dns => self.dns += 1,
+            NetErrKind::Connect => self.connect += 1,
+            NetErrKind::Request => self.request += 1,
+            NetErrKind::Body => self.body += 1,
+            NetErrKind::Decode => self.decode += 1,
+            NetErrKind::Other => self.other += 1,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.timeout + self.dns + self.connect + self.request + self.body + self.decode + self.other
+    }
+
+    pub fn merge(&mut self, o: &NetErrCounts) {
+        self.timeout += o.timeout;
+        self.dns += o.dns;
+        self.connect += o.connect;
+        self.request += o.request;
+        self.body += o.body;
+        self.decode += o.decode;
+        self.other += o.other;
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckCounts {
+    pub pass: u64,
+    pub fail: u64,
+}
+
+impl CheckCounts {
+    pub fn record(&mut self, ok: bool) {
+        if ok {
+            self.pass += 1;
+        } else {
+            self.fail += 1;
+        }
+    }
+
+    pub fn merge(&mut self, o: &CheckCounts) {
+        self.pass += o.pass;
+        self.fail += o.fail;
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Aggregates {
+    pub status_exact: BTreeMap<u16, u64>,
+    pub status_class: StatusClassCounts,
+    pub net_errors: NetErrCounts,
+    /// --dedupe-errors: message -> count per error kind, at most `ERROR_EXAMPLE_CAP`
+    /// messages each; the rest are counted under `ERROR_EXAMPLE_OVERFLOW`
+    #[serde(default)]
+    pub error_examples: BTreeMap<String, BTreeMap<String, u64>>,
+    /// Request body bytes of requests that got a response
+    #[serde(default)]
+    pub bytes_sent: u64,
+    /// --capture-etag: conditional requests answered with 304 Not Modified
+    #[serde(default)]
+    pub cache_hits: u64,
+    /// --capture-etag: conditional requests answered with a full response
+    #[serde(default)]
+    pub cache_misses: u64,
+    /// --respect-retry-after: pauses taken after 429/503
+    #[serde(default)]
+    pub backoffs: u64,
+    #[serde(default)]
+    pub backoff_micros: u64,
+    /// --retries: how many requests needed 1, 2, ... retries
+    #[serde(default)]
+    pub retried: BTreeMap<u32, u64>,
+    /// --retries: requests still failing after the last retry
+    #[serde(default)]
+    pub retries_exhausted: u64,
+    /// --grpc: grpc-status codes of the responses
+    #[serde(default)]
+    pub grpc_status: BTreeMap<u32, u64>,
+    /// --grpc: responses without a grpc-status trailer or header
+    #[serde(default)]
+    pub grpc_status_missing: u64,
+    /// responses by the family of the peer address (neither for --unix-socket)
+    #[serde(default)]
+    pub remote_ipv4: u64,
+    #[serde(default)]
+    pub remote_ipv6: u64,
+    /// responses with `Connection: close`: the server would not keep the connection open
+    #[serde(default)]
+    pub connection_close_responses: u64,
+    #[serde(with = "histogram_serde")]
+    pub latency_micros: Histogram<u64>,
+    /// Latencies of each worker's first request (also in `latency_micros`).
+    /// reqwest does not expose connection reuse, so we infer it: a worker's first
+    /// request has to open a connection (DNS, TCP, TLS), later ones normally reuse one.
+    #[serde(with = "histogram_serde", default = "histogram_serde::empty")]
+    pub connection_setup_micros: Histogram<u64>,
+    /// --measure-ttfb: send until response headers (`latency_micros` then includes the body)
+    #[serde(with = "histogram_serde", default = "histogram_serde::empty")]
+    pub ttfb_micros: Histogram<u64>,
+    /// Latency measured from the scheduled start instead of the actual send, so queueing
+    /// behind a slow server counts (coordinated-omission correction)
+    #[serde(with = "histogram_serde", default = "histogram_serde::empty")]
+    pub corrected_micros: Histogram<u64>,
+    /// --normalize-latency-by-payload-size: latency per KiB of response body, for
+    /// responses with a known, non-empty body
+    #[serde(with = "histogram_serde", default = "histogram_serde::empty")]
+    pub latency_per_kb_micros: Histogram<u64>,
+    /// --parse-server-timing: the servers' own dur= per metric name (up to
+    /// `SERVER_TIMING_METRIC_CAP` names)
+    #[serde(with = "histogram_map_serde", default)]
+    pub server_timing_micros: BTreeMap<String, Histogram<u64>>,
+    /// --expect-header results keyed by header name as given on the CLI
+    #[serde(default)]
+    pub header_checks: BTreeMap<String, CheckCounts>,
+    /// expect_status results of --scenario steps
+    #[serde(default)]
+    pub status_checks: CheckCounts,
+    /// --success-body-contains/--success-body-regex results
+    #[serde(default)]
+    pub body_checks: CheckCounts,
+    /// --expect-body-size-range results
+    #[serde(default)]
+    pub body_size_checks: CheckCounts,
+    /// responses the checks ran on (all of them unless --validate-sample-rate is below 1)
+    #[serde(default)]
+    pub validated_responses: u64,
+    /// responses --validate-sample-rate left unchecked
+    #[serde(default)]
+    pub validation_sampled_out: u64,
+    /// --response-schema results for JSON bodies within --max-validate-bytes
+    #[serde(default)]
+    pub schema_checks: CheckCounts,
+    /// bodies --response-schema left alone: not JSON by Content-Type, empty or too large
+    #[serde(default)]
+    pub schema_skipped: u64,
+    /// the first few distinct violations, at most `SCHEMA_VIOLATION_CAP`
+    #[serde(default)]
+    pub schema_violations: Vec<String>,
+    /// --scenario captures whose path did not match the response, by variable
+    #[serde(default)]
+    pub capture_failures: BTreeMap<String, u64>,
+    /// --capture-slow-over samples, oldest first, at most `SLOW_SAMPLE_CAP`
+    #[serde(default)]
+    pub slow_samples: VecDeque<SlowSample>,
+    /// OPTIONS preflight results when --measure-preflight is active
+    #[serde(default)]
+    pub preflight: Option<Box<Aggregates>>,
+}
+
+/// How many slow-request samples are kept (the most recent win).
+pub const SLOW_SAMPLE_CAP: usize = 50;
+
+/// How many example --response-schema violations are kept (the first win).
+pub const SCHEMA_VIOLATION_CAP: usize = 5;
+const SLOW_BODY_SNIPPET_BYTES: usize = 512;
+
+/// The one request of a --once run, for the detailed report.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OnceDetail {
+    /// headers set on the request (credentials redacted); the client adds User-Agent and Accept
+    pub request_headers: Vec<(String, String)>,
+    pub status: Option<u16>,
+    /// the network error with its causes, when no response (or no full body) arrived
+    pub error: Option<String>,
+    pub version: Option<String>,
+    pub remote_addr: Option<String>,
+    pub response_headers: Vec<(String, String)>,
+    /// until the response headers were in
+    pub headers_ms: f64,
+    /// reading the body after that
+    pub body_ms: f64,
+    pub body_bytes: u64,
+    /// the first `ONCE_BODY_PREVIEW_BYTES`, lossily decoded
+    pub body_preview: String,
+    /// --latency-budget
+    #[serde(default)]
+    pub waterfall: Option<Waterfall>,
+}
+
+/// Phases of the --latency-budget request; those after a failure are missing.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Waterfall {
+    pub dns_ms: Option<f64>,
+    pub connect_ms: Option<f64>,
+    /// https only
+    pub tls_ms: Option<f64>,
+    /// from writing the request to the first byte of the response
+    pub ttfb_ms: Option<f64>,
+    /// the rest of the response, until the server closed the connection
+    pub transfer_ms: Option<f64>,
+    /// status line, headers and body
+    pub response_bytes: u64,
+    pub error: Option<String>,
+}
+
+impl Waterfall {
+    fn phases(&self) -> [(&'static str, Option<f64>); 5] {
+        [
+            ("dns", self.dns_ms),
+            ("connect", self.connect_ms),
+            ("tls", self.tls_ms),
+            ("ttfb", self.ttfb_ms),
+            ("transfer", self.transfer_ms),
+        ]
+    }
+
+    pub fn total_ms(&self) -> f64 {
+        self.phases().iter().filter_map(|(_, ms)| *ms).sum()
+    }
+}
+
+/// Width of the --latency-budget bars.
+const WATERFALL_WIDTH: usize = 40;
+
+/// How much of the --once body is shown.
+const ONCE_BODY_PREVIEW_BYTES: usize = 2048;
+
+/// How many requests --har-file keeps.
+pub const HAR_ENTRY_CAP: usize = 1000;
+
+/// How much of each response body a HAR entry keeps.
+const HAR_BODY_PREVIEW_BYTES: usize = 4096;
+
+/// One sampled request and its response, for --har-file (see `render_har`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HarEntry {
+    pub started_unix_ms: u64,
+    pub method: String,
+    pub url: String,
+    /// headers set on the request (credentials redacted); the client adds User-Agent and Accept
+    pub request_headers: Vec<(String, String)>,
+    pub request_body_bytes: u64,
+    pub status: Option<u16>,
+    /// the network error kind, when no response arrived
+    pub error: Option<String>,
+    pub http_version: String,
+    pub response_headers: Vec<(String, String)>,
+    pub body_bytes: u64,
+    /// the first `HAR_BODY_PREVIEW_BYTES`, lossily decoded
+    pub body_preview: String,
+    /// until the response headers were in
+    pub wait_ms: f64,
+    /// reading the body after that
+    pub receive_ms: f64,
+}
+
+impl HarEntry {
+    fn record_request(&mut self, req: &reqwest::Request, body_bytes: u64) {
+        self.method = req.method().to_string();
+        self.url = req.url().to_string();
+        self.request_headers = req
+            .headers()
+            .iter()
+            .map(|(k, v)| {
+                let v = String::from_utf8_lossy(v.as_bytes());
+                (k.to_string(), redact_credential(k.as_str(), &v))
+            })
+            .collect();
+        self.request_body_bytes = body_bytes;
+    }
+
+    /// `body` holds at least the preview; `body_bytes` is the full size.
+    fn record_response(&mut self, r: &reqwest::Response, body: &[u8], body_bytes: u64) {
+        self.status = Some(r.status().as_u16());
+        self.http_version = format!("{:?}", r.version());
+        self.response_headers = r
+            .headers()
+            .iter()
+            .map(|(k, v)| (k.to_string(), String::from_utf8_lossy(v.as_bytes()).into()))
+            .collect();
+        self.body_bytes = body_bytes;
+        let n = body.len().min(HAR_BODY_PREVIEW_BYTES);
+        self.body_preview = String::from_utf8_lossy(&body[..n]).into_owned();
+    }
+}
+
+/// One of the --top-slow requests.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TopSlowRequest {
+    pub seq: u64,
+    pub latency_micros: u64,
+    /// seconds into the run when it finished
+    pub at_sec: f64,
+    /// HTTP status, or the network error kind
+    pub status: Option<u16>,
+    pub error: Option<String>,
+}
+
+/// Orders --top-slow candidates by latency (then seq) for a bounded min-heap.
+struct BySlowness(TopSlowRequest);
+
+impl BySlowness {
+    fn key(&self) -> (u64, u64) {
+        (self.0.latency_micros, self.0.seq)
+    }
+}
+
+impl PartialEq for BySlowness {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for BySlowness {}
+
+impl PartialOrd for BySlowness {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BySlowness {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// Keeps `req` if it is among the `n` slowest seen so far; the heap top is the fastest kept.
+fn keep_slowest(heap: &mut BinaryHeap<Reverse<BySlowness>>, n: usize, req: TopSlowRequest) {
+    if heap.len() < n {
+        heap.push(Reverse(BySlowness(req)));
+    } else if heap
+        .peek()
+        .is_some_and(|Reverse(min)| min.0.latency_micros < req.latency_micros)
+    {
+        heap.pop();
+        heap.push(Reverse(BySlowness(req)));
+    }
+}
+
+/// Adds `more` to a --top-slow list and keeps the `n` slowest, slowest first.
+pub fn merge_top_slow(
+    top: &mut Vec<TopSlowRequest>,
+    more: impl IntoIterator<Item = TopSlowRequest>,
+    n: usize,
+) {
+    top.extend(more);
+    top.sort_by_key(|t| (Reverse(t.latency_micros), t.seq));
+    top.truncate(n);
+}
+
+/// Detail captured for one request slower than --capture-slow-over.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlowSample {
+    pub seq: u64,
+    pub latency_micros: u64,
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub body_snippet: String,
+}
+
+impl SlowSample {
+    fn from_response(seq: u64, latency_micros: u64, r: &reqwest::Response) -> Self {
+        Self {
+            seq,
+            latency_micros,
+            status: Some(r.status().as_u16()),
+            error: None,
+            headers: r
+                .headers()
+                .iter()
+                .map(|(k, v)| (k.to_string(), String::from_utf8_lossy(v.as_bytes()).into()))
+                .collect(),
+            body_snippet: String::new(),
+        }
+    }
+
+    fn from_error(seq: u64, latency_micros: u64, e: &SendError) -> Self {
+        Self {
+            seq,
+            latency_micros,
+            status: None,
+            error: Some(e.to_string()),
+            headers: Vec::new(),
+            body_snippet: String::new(),
+        }
+    }
+}
+
+/// Reads at most `limit` bytes of the body (lossy UTF-8); the rest is dropped.
+async fn read_body_snippet(mut r: reqwest::Response, limit: usize) -> String {
+    let mut buf = Vec::new();
+    while buf.len() < limit {
+        match r.chunk().await {
+            Ok(Some(chunk)) => buf.extend_from_slice(&chunk),
+            _ => break,
+        }
+    }
+    buf.truncate(limit);
+    String::from_utf8_lossy(&buf).into_owned()
+}
+
+/// At least this share of responses with Connection: close gets a warning.
+const CONNECTION_CLOSE_WARN: f64 = 0.5;
+
+/// Achieving less than this share of the target rate gets a warning.
+const RATE_ATTAINMENT_WARN: f64 = 0.9;
+
+/// Distinct messages kept per error kind by --dedupe-errors.
+pub const ERROR_EXAMPLE_CAP: usize = 20;
+
+/// Where --dedupe-errors counts messages past `ERROR_EXAMPLE_CAP`.
+pub const ERROR_EXAMPLE_OVERFLOW: &str = "(other messages)";
+
+/// Longer --dedupe-errors messages are cut to this many characters.
+const ERROR_EXAMPLE_MAX_CHARS: usize = 300;
+
+/// How many of each kind's messages the report shows.
+const ERROR_EXAMPLES_SHOWN: usize = 3;
+
+fn add_error_example(messages: &mut BTreeMap<String, u64>, message: &str, n: u64) {
+    let full = messages.len()
+        >= ERROR_EXAMPLE_CAP + usize::from(messages.contains_key(ERROR_EXAMPLE_OVERFLOW));
+    let key = if messages.contains_key(message) || !full {
+        message
+    } else {
+        ERROR_EXAMPLE_OVERFLOW
+    };
+    *messages.entry(key.to_string()).or_insert(0) += n;
+}
+
+/// Histograms are stored as `serialize_histogram` strings; results saved before that
+/// hold `[value, count]` pairs, which still load (into a 3-significant-figure histogram).
+mod histogram_serde {
+    use hdrhistogram::Histogram;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Stored {
+        V2(String),
+        Pairs(Vec<(u64, u64)>),
+    }
+
+    pub fn serialize<S: Serializer>(h: &Histogram<u64>, s: S) -> Result<S::Ok, S::Error> {
+        let text = super::serialize_histogram(h).map_err(serde::ser::Error::custom)?;
+        s.serialize_str(&text)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Histogram<u64>, D::Error> {
+        match Stored::deserialize(d)? {
+            Stored::V2(text) => {
+                super::deserialize_histogram(&text).map_err(serde::de::Error::custom)
+            }
+            Stored::Pairs(pairs) => {
+                let mut h = Histogram::<u64>::new(3).map_err(serde::de::Error::custom)?;
+                for (value, count) in pairs {
+                    h.record_n(value, count).map_err(serde::de::Error::custom)?;
+                }
+                Ok(h)
+            }
+        }
+    }
+
+    pub fn empty() -> Histogram<u64> {
+        Histogram::<u64>::new(3).expect("3 significant figures is valid")
+    }
+}
+
+/// Server-Timing metric names kept per run; a server that makes up new names per
+/// request can't grow the result without bound.
+pub const SERVER_TIMING_METRIC_CAP: usize = 32;
+
+/// A name -> histogram map stored like `histogram_serde`, one V2 string per name.
+mod histogram_map_serde {
+    use hdrhistogram::Histogram;
+    use serde::{ser::SerializeMap, Deserialize, Deserializer, Serializer};
+    use std::collections::BTreeMap;
+
+    pub fn serialize<S: Serializer>(
+        map: &BTreeMap<String, Histogram<u64>>,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut out = s.serialize_map(Some(map.len()))?;
+        for (name, h) in map {
+            let text = super::serialize_histogram(h).map_err(serde::ser::Error::custom)?;
+            out.serialize_entry(name, &text)?;
+        }
+        out.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<BTreeMap<String, Histogram<u64>>, D::Error> {
+        BTreeMap::<String, String>::deserialize(d)?
+            .into_iter()
+            .map(|(name, text)| {
+                super::deserialize_histogram(&text)
+                    .map(|h| (name, h))
+                    .map_err(serde::de::Error::custom)
+            })
+            .collect()
+    }
+}
+
+/// Encodes a histogram losslessly: hdrhistogram's V2 format, base64 encoded.
+pub fn serialize_histogram(h: &Histogram<u64>) -> anyhow::Result<String> {
+    let mut buf = Vec::new();
+    use hdrhistogram::serialization::Serializer as _;
+    hdrhistogram::serialization::V2Serializer::new()
+        .serialize(h, &mut buf)
+        .map_err(|e| anyhow::anyhow!("Failed to serialize histogram: {e:?}"))?;
+    Ok(BASE64_STANDARD.encode(buf))
+}
+
+/// Decodes a `serialize_histogram` string. The result auto-resizes, so `Histogram::add`
+/// of a histogram with a wider range grows it instead of failing.
+pub fn deserialize_histogram(text: &str) -> anyhow::Result<Histogram<u64>> {
+    let bytes = BASE64_STANDARD
+        .decode(text)
+        .map_err(|e| anyhow::anyhow!("Invalid histogram: {e}"))?;
+    let mut h: Histogram<u64> = hdrhistogram::serialization::Deserializer::new()
+        .deserialize(&mut &bytes[..])
+        .map_err(|e| anyhow::anyhow!("Invalid histogram: {e:?}"))?;
+    h.auto(true);
+    Ok(h)
+}
+
+/// Shape of the latency histogram: precision and optional fixed upper bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistConfig {
+    pub sigfigs: u8,
+    /// `None` means auto-resize
+    pub max_micros: Option<u64>,
+}
+
+impl Default for HistConfig {
+    fn default() -> Self {
+        Self {
+            sigfigs: 3,
+            max_micros: None,
+        }
+    }
+}
+
+impl HistConfig {
+    pub fn build(&self) -> anyhow::Result<Histogram<u64>> {
+        let h = match self.max_micros {
+            Some(max) => Histogram::<u64>::new_with_bounds(1, max, self.sigfigs)?,
+            None => Histogram::<u64>::new(self.sigfigs)?,
+        };
+        Ok(h)
+    }
+}
+
+/// Auto-resizing histograms grow to fit; bounded ones (--hist-max) clamp to their max.
+/// (`saturating_record` alone would clamp auto-resizing ones too.)
+fn record_micros(h: &mut Histogram<u64>, micros: u64) {
+    let v = micros.max(1);
+    if !h.is_auto_resize() || h.record(v).is_err() {
+        h.saturating_record(v);
+    }
+}
+
+impl Aggregates {
+    pub fn new() -> anyhow::Result<Self> {
+        Self::with_hist(HistConfig::default())
+    }
+
+    pub fn with_hist(hist: HistConfig) -> anyhow::Result<Self> {
+        Ok(Self {
+            status_exact: BTreeMap::new(),
+            status_class: StatusClassCounts::default(),
+            net_errors: NetErrCounts::default(),
+            error_examples: BTreeMap::new(),
+            bytes_sent: 0,
+            cache_hits: 0,
+            cache_misses: 0,
+            backoffs: 0,
+            backoff_micros: 0,
+            retried: BTreeMap::new(),
+            retries_exhausted: 0,
+            grpc_status: BTreeMap::new(),
+            grpc_status_missing: 0,
+            remote_ipv4: 0,
+            remote_ipv6: 0,
+            connection_close_responses: 0,
+            latency_micros: hist.build()?,
+            connection_setup_micros: hist.build()?,
+            ttfb_micros: hist.build()?,
+            corrected_micros: hist.build()?,
+            latency_per_kb_micros: hist.build()?,
+            server_timing_micros: BTreeMap::new(),
+            header_checks: BTreeMap::new(),
+            status_checks: CheckCounts::default(),
+            body_checks: CheckCounts::default(),
+            body_size_checks: CheckCounts::default(),
+            schema_checks: CheckCounts::default(),
+            schema_skipped: 0,
+            validated_responses: 0,
+            validation_sampled_out: 0,
+            schema_violations: Vec::new(),
+            capture_failures: BTreeMap::new(),
+            slow_samples: VecDeque::new(),
+            preflight: None,
+        })
+    }
+
+    pub fn record_status(&mut self, code: u16) {
+        *self.status_exact.entry(code).or_insert(0) += 1;
+        self.status_class.record(code);
+    }
+
+    pub fn record_error(&mut self, kind: NetErrKind) {
+        self.net_errors.record(kind);
+    }
+
+    /// Counts one occurrence of `message` under `kind` (see `error_examples`).
+    pub fn record_error_example(&mut self, kind: NetErrKind, message: &str) {
+        let messages = self
+            .error_examples
+            .entry(kind.as_str().to_string())
+            .or_default();
+        let message = match message.char_indices().nth(ERROR_EXAMPLE_MAX_CHARS) {
+            Some((i, _)) => format!("{}...", &message[..i]),
+            None => message.to_string(),
+        };
+        add_error_example(messages, &message, 1);
+    }
+
+    pub fn record_latency(&mut self, micros: u64) {
+        record_micros(&mut self.latency_micros, micros);
+    }
+
+    /// Records a worker's first request, which is assumed to open a new connection.
+    pub fn record_connection_setup(&mut self, micros: u64) {
+        self.record_latency(micros);
+        record_micros(&mut self.connection_setup_micros, micros);
+    }
+
+    pub fn record_ttfb(&mut self, micros: u64) {
+        record_micros(&mut self.ttfb_micros, micros);
+    }
+
+    /// Records a Server-Timing dur= (in ms) under its metric name.
+    pub fn record_server_timing(&mut self, name: &str, dur_ms: f64) {
+        let micros = (dur_ms * 1000.0).round() as u64;
+        if let Some(h) = self.server_timing_micros.get_mut(name) {
+            record_micros(h, micros);
+        } else if self.server_timing_micros.len() < SERVER_TIMING_METRIC_CAP {
+            if let Ok(mut h) = Histogram::new(self.latency_micros.sigfig()) {
+                record_micros(&mut h, micros);
+                self.server_timing_micros.insert(name.to_string(), h);
+            }
+        }
+    }
+
+    /// Records `micros` scaled to one KiB of a `bytes`-long body; empty bodies are skipped.
+    pub fn record_latency_per_kb(&mut self, micros: u64, bytes: u64) {
+        if bytes > 0 {
+            let per_kb = u128::from(micros) * 1024 / u128::from(bytes);
+            record_micros(
+                &mut self.latency_per_kb_micros,
+                per_kb.min(u128::from(u64::MAX)) as u64,
+            );
+        }
+    }
+
+    pub fn record_corrected(&mut self, micros: u64) {
+        record_micros(&mut self.corrected_micros, micros);
+    }
+
+    /// Records a paced request into `corrected_micros` together with the requests that
+    /// should have started while it was outstanding (one per `interval_micros`).
+    pub fn record_corrected_interval(&mut self, micros: u64, interval_micros: u64) {
+        let h = &mut self.corrected_micros;
+        if h.record_correct(micros.max(1), interval_micros).is_err() {
+            // bounded histogram: no backfill beyond the bound
+            h.saturating_record(micros.max(1));
+        }
+    }
+
+    /// Latencies without each worker's first request, i.e. on (presumably) reused connections.
+    pub fn steady_state_latency(&self) -> Histogram<u64> {
+        let mut h = self.latency_micros.clone();
+        if h.subtract(&self.connection_setup_micros).is_err() {
+            return self.latency_micros.clone();
+        }
+        h
+    }
+
+    pub fn record_header_check(&mut self, name: &str, ok: bool) {
+        self.header_checks
+            .entry(name.to_string())
+            .or_default()
+            .record(ok);
+    }
+
+    /// Records one preflight into the `preflight` aggregates (created on first use
+    /// with the same histogram shape as the main one).
+    pub fn record_preflight(&mut self, micros: u64, outcome: Result<u16, NetErrKind>) {
+        let hist = HistConfig {
+            sigfigs: self.latency_micros.sigfig(),
+            max_micros: (!self.latency_micros.is_auto_resize()).then(|| self.latency_micros.high()),
+        };
+        let p = match &mut self.preflight {
+            Some(p) => p,
+            None => match Aggregates::with_hist(hist) {
+                Ok(a) => self.preflight.insert(Box::new(a)),
+                Err(_) => return,
+            },
+        };
+        p.record_latency(micros);
+        match outcome {
+            Ok(code) => p.record_status(code),
+            Err(kind) => p.record_error(kind),
+        }
+    }
+
+    /// Keeps a violation as an example unless it is a repeat or the list is full.
+    pub fn push_schema_violation(&mut self, violation: &str) {
+        if self.schema_violations.len() < SCHEMA_VIOLATION_CAP
+            && !self.schema_violations.iter().any(|v| v == violation)
+        {
+            self.schema_violations.push(violation.to_string());
+        }
+    }
+
+    pub fn push_slow_sample(&mut self, sample: SlowSample) {
+        if self.slow_samples.len() >= SLOW_SAMPLE_CAP {
+            self.slow_samples.pop_front();
+        }
+        self.slow_samples.push_back(sample);
+    }
+
+    /// Total failed response validations of any kind.
+    pub fn validation_failures(&self) -> u64 {
+        self.header_checks.values().map(|c| c.fail).sum::<u64>()
+            + self.status_checks.fail
+            + self.body_checks.fail
+            + self.body_size_checks.fail
+            + self.schema_checks.fail
+            + self.capture_failures.values().sum::<u64>()
+    }
+
+    /// Folds another set of aggregates into this one (counts add, histograms combine).
+    pub fn merge(&mut self, other: &Aggregates) -> anyhow::Result<()> {
+        for (code, count) in &other.status_exact {
+            *self.status_exact.entry(*code).or_insert(0) += count;
+        }
+        self.status_class.merge(&other.status_class);
+        self.net_errors.merge(&other.net_errors);
+        for (kind, messages) in &other.error_examples {
+            let mine = self.error_examples.entry(kind.clone()).or_default();
+            for (msg, n) in messages {
+                add_error_example(mine, msg, *n);
+            }
+        }
+        self.bytes_sent += other.bytes_sent;
+        self.cache_hits += other.cache_hits;
+        self.cache_misses += other.cache_misses;
+        self.backoffs += other.backoffs;
+        self.backoff_micros += other.backoff_micros;
+        for (k, n) in &other.retried {
+            *self.retried.entry(*k).or_insert(0) += n;
+        }
+        self.retries_exhausted += other.retries_exhausted;
+        for (code, n) in &other.grpc_status {
+            *self.grpc_status.entry(*code).or_insert(0) += n;
+        }
+        self.grpc_status_missing += other.grpc_status_missing;
+        self.remote_ipv4 += other.remote_ipv4;
+        self.remote_ipv6 += other.remote_ipv6;
+        self.connection_close_responses += other.connection_close_responses;
+        self.latency_micros
+            .add(&other.latency_micros)
+            .map_err(|e| anyhow::anyhow!("Failed to merge latency histograms: {e:?}"))?;
+        self.connection_setup_micros
+            .add(&other.connection_setup_micros)
+            .map_err(|e| anyhow::anyhow!("Failed to merge latency histograms: {e:?}"))?;
+        self.ttfb_micros
+            .add(&other.ttfb_micros)
+            .map_err(|e| anyhow::anyhow!("Failed to merge latency histograms: {e:?}"))?;
+        self.corrected_micros
+            .add(&other.corrected_micros)
+            .map_err(|e| anyhow::anyhow!("Failed to merge latency histograms: {e:?}"))?;
+        self.latency_per_kb_micros
+            .add(&other.latency_per_kb_micros)
+            .map_err(|e| anyhow::anyhow!("Failed to merge latency histograms: {e:?}"))?;
+        for (name, h) in &other.server_timing_micros {
+            let room = self.server_timing_micros.len() < SERVER_TIMING_METRIC_CAP;
+            match self.server_timing_micros.get_mut(name) {
+                Some(mine) => mine
+                    .add(h)
+                    .map_err(|e| anyhow::anyhow!("Failed to merge latency histograms: {e:?}"))?,
+                None if room => {
+                    self.server_timing_micros.insert(name.clone(), h.clone());
+                }
+                None => {}
+            }
+        }
+        for (name, c) in &other.header_checks {
+            self.header_checks.entry(name.clone()).or_default().merge(c);
+        }
+        self.status_checks.merge(&other.status_checks);
+        self.body_checks.merge(&other.body_checks);
+        self.body_size_checks.merge(&other.body_size_checks);
+        self.schema_checks.merge(&other.schema_checks);
+        self.schema_skipped += other.schema_skipped;
+        self.validated_responses += other.validated_responses;
+        self.validation_sampled_out += other.validation_sampled_out;
+        for v in &other.schema_violations {
+            self.push_schema_violation(v);
+        }
+        for (var, n) in &other.capture_failures {
+            *self.capture_failures.entry(var.clone()).or_insert(0) += n;
+        }
+        for sample in &other.slow_samples {
+            self.push_slow_sample(sample.clone());
+        }
+        match (&mut self.preflight, &other.preflight) {
+            (Some(mine), Some(theirs)) => mine.merge(theirs)?,
+            (None, Some(theirs)) => self.preflight = Some(theirs.clone()),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Why a request got no response.
+#[derive(Debug)]
+enum SendError {
+    Reqwest(reqwest::Error),
+    /// reading the body failed after the headers came in
+    Body(reqwest::Error),
+    /// --hard-timeout expired before reqwest gave up
+    HardTimeout(Duration),
+}
+
+impl SendError {
+    fn kind(&self) -> NetErrKind {
+        match self {
+            SendError::Reqwest(e) => classify_reqwest_error(e),
+            // reqwest reports a cut-off body as a decode error
+            SendError::Body(e) if e.is_timeout() => NetErrKind::Timeout,
+            SendError::Body(_) => NetErrKind::Body,
+            SendError::HardTimeout(_) => NetErrKind::Timeout,
+        }
+    }
+}
+
+impl SendError {
+    /// The error with its causes, without the URL reqwest puts in (it would make every
+    /// request's message distinct).
+    fn message(&self) -> String {
+        match self {
+            SendError::Reqwest(e) | SendError::Body(e) => {
+                let msg = error_chain(e);
+                match e.url() {
+                    Some(url) => msg.replace(&format!(" for url ({url})"), ""),
+                    None => msg,
+                }
+            }
+            SendError::HardTimeout(_) => self.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for SendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SendError::Reqwest(e) | SendError::Body(e) => e.fmt(f),
+            SendError::HardTimeout(d) => {
+                write!(f, "no complete response within --hard-timeout {d:?}")
+            }
+        }
+    }
+}
+
+/// The next body chunk; a read error or the --hard-timeout deadline fails the request like
+/// a send error.
+async fn next_chunk(
+    r: &mut reqwest::Response,
+    hard: Option<HardDeadline>,
+) -> Result<Option<Bytes>, SendError> {
+    match until_deadline(hard, r.chunk()).await {
+        Some(chunk) => chunk.map_err(SendError::Body),
+        None => Err(HardDeadline::error(hard)),
+    }
+}
+
+/// When a request attempt runs out of --hard-timeout: sending it and reading the body
+/// count against the same cap.
+#[derive(Debug, Clone, Copy)]
+struct HardDeadline {
+    at: tokio::time::Instant,
+    cap: Duration,
+}
+
+impl HardDeadline {
+    fn after(cap: Duration) -> Self {
+        HardDeadline {
+            at: tokio::time::Instant::now() + cap,
+            cap,
+        }
+    }
+
+    fn error(hard: Option<HardDeadline>) -> SendError {
+        SendError::HardTimeout(hard.map_or(Duration::ZERO, |h| h.cap))
+    }
+}
+
+/// Runs `fut` until the --hard-timeout deadline, if there is one; `None` once it passes.
+async fn until_deadline<F: Future>(hard: Option<HardDeadline>, fut: F) -> Option<F::Output> {
+    match hard {
+        Some(h) => tokio::time::timeout_at(h.at, fut).await.ok(),
+        None => Some(fut.await),
+    }
+}
+
+pub fn classify_reqwest_error(e: &reqwest::Error) -> NetErrKind {
+    if e.is_timeout() {
+        NetErrKind::Timeout
+    } else if e.is_connect() && is_dns_error(e) {
+        NetErrKind::Dns
+    } else if e.is_connect() {
+        NetErrKind::Connect
+    } else if e.is_request() {
+        NetErrKind::Request
+    } else if e.is_body() {
+        NetErrKind::Body
+    } else if e.is_decode() {
+        NetErrKind::Decode
+    } else {
+        NetErrKind::Other
+    }
+}
+
+/// Resolver failures are not exposed as a type by reqwest; hyper-util wraps them as
+/// "dns error" and the system resolver reports "failed to lookup address", so we
+/// look for those in the source chain.
+fn is_dns_error(e: &reqwest::Error) -> bool {
+    let mut source = std::error::Error::source(e);
+    while let Some(err) = source {
+        let msg = err.to_string().to_ascii_lowercase();
+        if msg.contains("dns error") || msg.contains("failed to lookup address") {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+/* ============================== LOGGING ============================== */
+
+/// Diagnostic output on stderr; the report itself is always printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    /// warnings only
+    Quiet,
+    /// warnings, progress and stage changes
+    Normal,
+    /// plus worker lifecycle and resolved configuration
+    Verbose,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Logger {
+    pub level: LogLevel,
+}
+
+impl Logger {
+    pub fn warn(&self, msg: std::fmt::Arguments) {
+        eprintln!("warning: {msg}");
+    }
+
+    pub fn info(&self, msg: std::fmt::Arguments) {
+        if self.level >= LogLevel::Normal {
+            eprintln!("{msg}");
+        }
+    }
+
+    pub fn debug(&self, msg: std::fmt::Arguments) {
+        if self.level >= LogLevel::Verbose {
+            eprintln!("debug: {msg}");
+        }
+    }
+}
+
+/* ================================ RUN ================================ */
+
+pub async fn run(args: RunArgs) -> anyhow::Result<RunResult> {
+    let log = Logger {
+        level: args.log_level,
+    };
+
+    // validate url; what the parser would quietly encode or rewrite is flagged first
+    let raw_url = if args.auto_encode_url {
+        let encoded = encode_url(&args.url);
+        if encoded != args.url {
+            log.info(format_args!("--url encoded as {encoded}"));
+        }
+        encoded
+    } else {
+        for w in url_warnings(&args.url) {
+            log.warn(format_args!(
+                "--url has {w}; --auto-encode-url would encode it"
+            ));
+        }
+        args.url.clone()
+    };
+    let url = Url::parse(&raw_url).map_err(|e| anyhow::anyhow!("Invalid --url: {e}"))?;
+
+    // validate method (explicit allow-list; reqwest accepts extension methods)
+    let method = parse_http_method(&args.method)
+        .ok_or_else(|| anyhow::anyhow!("Invalid --method: {}", args.method))?;
+
+    let stage_plan = match &args.stages {
+        Some(st) => {
+            if args.requests.is_some() || args.duration.is_some() {
+                return Err(anyhow::anyhow!(
+                    "--stages cannot be combined with --requests or --duration"
+                ));
+            }
+            parse_stages(st).ok_or_else(|| {
+                anyhow::anyhow!("Invalid --stages: {st} (expected \"10:30s,50:30s\")")
+            })?
+        }
+        None => Vec::new(),
+    };
+
+    if let Some(n) = args.requests_per_worker {
+        if args.requests.is_some()
+            || !stage_plan.is_empty()
+            || args.open_loop
+            || args.trace_file.is_some()
+        {
+            return Err(anyhow::anyhow!(
+                "--requests-per-worker cannot be combined with --requests, --stages, --open-loop or --trace-file"
+            ));
+        }
+        if n == 0 {
+            return Err(anyhow::anyhow!("Invalid --requests-per-worker: 0"));
+        }
+    }
+    // concurrency x --requests-per-worker stands in for --requests from here on
+    let requests_total = args.requests.or(args
+        .requests_per_worker
+        .map(|n| n.saturating_mul(args.concurrency.max(1) as u64)));
+
+    if args.once
+        && (args.warmup > 0 || args.warm_compare.is_some() || args.measure_handshake.is_some())
+    {
+        return Err(anyhow::anyhow!(
+            "--once sends a single request; it cannot be combined with --warmup, --warm-compare or --measure-handshake"
+        ));
+    }
+    if args.latency_budget {
+        if !args.once {
+            return Err(anyhow::anyhow!(
+                "--latency-budget times a single request; it needs --once"
+            ));
+        }
+        if args.proxy.is_some() || args.unix_socket.is_some() {
+            return Err(anyhow::anyhow!(
+                "--latency-budget connects directly; it cannot be combined with --proxy or --unix-socket"
+            ));
+        }
+    }
+    if let Some(k) = args.cycles {
+        if !stage_plan.is_empty() || args.open_loop || args.trace_file.is_some() || args.once {
+            return Err(anyhow::anyhow!(
+                "--cycles cannot be combined with --stages, --open-loop, --trace-file or --once"
+            ));
+        }
+        if k == 0 {
+            return Err(anyhow::anyhow!("Invalid --cycles: 0"));
+        }
+    }
+    if requests_total.is_none()
+        && !args.once
+        && args.duration.is_none()
+        && stage_plan.is_empty()
+        && !args.until_stable
+        && args.cycles.is_none()
+        && args.trace_file.is_none()
+    {
+        return Err(anyhow::anyhow!(
+            "You must provide either --requests or --duration"
+        ));
+    }
+
+    // validate timeout and optional duration
+    let timeout_dur = parse_duration(&args.timeout)
+        .ok_or_else(|| anyhow::anyhow!("Invalid --timeout: {}", args.timeout))?;
+
+    let duration_target = if let Some(d) = &args.duration {
+        Some(parse_duration(d).ok_or_else(|| anyhow::anyhow!("Invalid --duration: {d}"))?)
+    } else {
+        None
+    };
+
+    let hard_timeout = match &args.hard_timeout {
+        Some(d) => Some(
+            parse_duration(d)
+                .filter(|d| !d.is_zero())
+                .ok_or_else(|| anyhow::anyhow!("Invalid --hard-timeout: {d}"))?,
+        ),
+        None => None,
+    };
+    let shutdown_grace = match &args.shutdown_grace {
+        Some(g) => {
+            parse_duration(g).ok_or_else(|| anyhow::anyhow!("Invalid --shutdown-grace: {g}"))?
+        }
+        None => timeout_dur + Duration::from_secs(1),
+    };
+
+    if !(1..=5).contains(&args.hist_sigfigs) {
+        return Err(anyhow::anyhow!(
+            "Invalid --hist-sigfigs: {} (expected 1..5)",
+            args.hist_sigfigs
+        ));
+    }
+    let retry = if args.retries > 0 {
+        let base = parse_duration(&args.retry_backoff)
+            .ok_or_else(|| anyhow::anyhow!("Invalid --retry-backoff: {}", args.retry_backoff))?;
+        let max = parse_duration(&args.retry_backoff_max).ok_or_else(|| {
+            anyhow::anyhow!("Invalid --retry-backoff-max: {}", args.retry_backoff_max)
+        })?;
+        Some(RetryPolicy {
+            retries: args.retries,
+            base,
+            max,
+            mode: args.retry_backoff_mode,
+        })
+    } else {
+        None
+    };
+    let apdex_threshold = match &args.apdex_threshold {
+        Some(d) => Some(
+            parse_duration(d)
+                .filter(|t| !t.is_zero())
+                .ok_or_else(|| anyhow::anyhow!("Invalid --apdex-threshold: {d}"))?,
+        ),
+        None => None,
+    };
+    let hist_max = if let Some(d) = &args.hist_max {
+        let max = parse_duration(d).ok_or_else(|| anyhow::anyhow!("Invalid --hist-max: {d}"))?;
+        if max <= timeout_dur {
+            return Err(anyhow::anyhow!(
+                "--hist-max {d} must be greater than --timeout {}",
+                args.timeout
+            ));
+        }
+        Some(max.as_micros().min(u128::from(u64::MAX)) as u64)
+    } else {
+        None
+    };
+    let hist = HistConfig {
+        sigfigs: args.hist_sigfigs,
+        max_micros: hist_max,
+    };
+
+    let min_interval = match args.max_rps_per_worker {
+        Some(n) if n.is_finite() && n > 0.0 => Some(Duration::from_secs_f64(1.0 / n)),
+        Some(n) => return Err(anyhow::anyhow!("Invalid --max-rps-per-worker: {n}")),
+        None => None,
+    };
+
+    let arrival_rate = match (args.open_loop, args.arrival_rate) {
+        (true, Some(r)) if r.is_finite() && r > 0.0 => Some(r),
+        (true, Some(r)) => return Err(anyhow::anyhow!("Invalid --arrival-rate: {r}")),
+        (true, None) => return Err(anyhow::anyhow!("--open-loop requires --arrival-rate")),
+        (false, Some(_)) => return Err(anyhow::anyhow!("--arrival-rate requires --open-loop")),
+        (false, None) => None,
+    };
+    match args.max_inflight {
+        Some(0) => return Err(anyhow::anyhow!("Invalid --max-inflight: 0")),
+        Some(_) if arrival_rate.is_none() => {
+            return Err(anyhow::anyhow!("--max-inflight requires --open-loop"))
+        }
+        _ => {}
+    }
+    if arrival_rate.is_some() && (args.stages.is_some() || min_interval.is_some()) {
+        return Err(anyhow::anyhow!(
+            "--open-loop cannot be combined with --stages or --max-rps-per-worker"
+        ));
+    }
+
+    let sample_interval = if let Some(d) = &args.sample_interval {
+        let iv =
+            parse_duration(d).ok_or_else(|| anyhow::anyhow!("Invalid --sample-interval: {d}"))?;
+        if iv.is_zero() {
+            return Err(anyhow::anyhow!("Invalid --sample-interval: {d}"));
+        }
+        Some(iv)
+    } else {
+        None
+    };
+    // --until-stable: the sampler compares consecutive windows
+    let stable = if args.until_stable {
+        if sample_interval.is_some() || !stage_plan.is_empty() {
+            return Err(anyhow::anyhow!(
+                "--until-stable cannot be combined with --sample-interval or --stages"
+            ));
+        }
+        let tol = args.stable_tolerance;
+        if !(tol.is_finite() && tol > 0.0) {
+            return Err(anyhow::anyhow!("Invalid --stable-tolerance: {tol}"));
+        }
+        let w = &args.stable_window;
+        let window = parse_duration(w)
+            .filter(|d| !d.is_zero())
+            .ok_or_else(|| anyhow::anyhow!("Invalid --stable-window: {w}"))?;
+        Some((window, tol / 100.0))
+    } else {
+        None
+    };
+    let sample_interval = sample_interval.or(stable.map(|(w, _)| w));
+    // --until-stable with nothing else to stop it: give up after STABLE_MAX_WINDOWS windows
+    let stable_cap = match stable {
+        Some((window, _))
+            if duration_target.is_none()
+                && requests_total.is_none()
+                && args.cycles.is_none()
+                && args.trace_file.is_none() =>
+        {
+            Some(window.saturating_mul(STABLE_MAX_WINDOWS))
+        }
+        _ => None,
+    };
+    let duration_target = duration_target.or(stable_cap);
+    let heatmap_bucket = match &args.heatmap_bucket {
+        Some(_) if sample_interval.is_none() => {
+            return Err(anyhow::anyhow!("--heatmap-csv requires --sample-interval"))
+        }
+        Some(b) => Some(
+            parse_duration(b)
+                .map(|d| d.as_micros() as u64)
+                .filter(|&us| us > 0)
+                .ok_or_else(|| anyhow::anyhow!("Invalid --heatmap-bucket: {b}"))?,
+        ),
+        None => None,
+    };
+    let burst = match &args.burst {
+        Some(b) => {
+            if args.duration.is_none()
+                || args.requests.is_some()
+                || arrival_rate.is_some()
+                || args.trace_file.is_some()
+                || stable.is_some()
+            {
+                return Err(anyhow::anyhow!(
+                    "--burst requires --duration and cannot be combined with --requests, --open-loop, --trace-file or --until-stable"
+                ));
+            }
+            Some(parse_burst(b).ok_or_else(|| {
+                anyhow::anyhow!("Invalid --burst: {b} (expected \"on=10s,off=20s\")")
+            })?)
+        }
+        None => None,
+    };
+    let profile = match &args.concurrency_profile {
+        Some(p) => {
+            if args.duration.is_none()
+                || !stage_plan.is_empty()
+                || arrival_rate.is_some()
+                || args.trace_file.is_some()
+                || burst.is_some()
+                || args.cycles.is_some()
+                || args.once
+            {
+                return Err(anyhow::anyhow!(
+                    "--concurrency-profile requires --duration and cannot be combined with --stages, --open-loop, --trace-file, --burst, --cycles or --once"
+                ));
+            }
+            Some(parse_concurrency_profile(p).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Invalid --concurrency-profile: {p} (expected \"sine:min=10,max=100,period=60s\" or \"linear:10..100\")"
+                )
+            })?)
+        }
+        None => None,
+    };
+    if args.soak && sample_interval.is_none() {
+        return Err(anyhow::anyhow!("--soak requires --sample-interval"));
+    }
+
+    let capture_slow_over = if let Some(d) = &args.capture_slow_over {
+        Some(parse_duration(d).ok_or_else(|| anyhow::anyhow!("Invalid --capture-slow-over: {d}"))?)
+    } else {
+        None
+    };
+
+    // parse headers
+    // repeated names are kept; each value is sent
+    let mut header_map: Vec<(String, String)> = Vec::new();
+    for h in &args.headers {
+        let (k, v) = parse_header(h).ok_or_else(|| {
+            anyhow::anyhow!("Invalid --header format: {h} (expected \"Key: Value\")")
+        })?;
+        let v = expand_env_for("--header", &v)?;
+        check_header(&k, &v).map_err(|e| anyhow::anyhow!("Invalid --header {h:?}: {e}"))?;
+        header_map.push((k, v));
+    }
+    if args.api_key.is_some() && args.basic_user.is_some() {
+        return Err(anyhow::anyhow!(
+            "Provide only one of --api-key or --basic-user."
+        ));
+    }
+    let has_authorization = header_map
+        .iter()
+        .any(|(k, _)| k.eq_ignore_ascii_case("authorization"));
+    if has_authorization && (args.api_key.is_some() || args.basic_user.is_some()) {
+        return Err(anyhow::anyhow!(
+            "An Authorization --header cannot be combined with --api-key or --basic-user."
+        ));
+    }
+    if let Some(token) = &args.api_key {
+        header_map.push(("Authorization".to_string(), format!("Bearer {token}")));
+    }
+    match (&args.basic_user, &args.basic_pass) {
+        (Some(user), pass) => {
+            let raw = format!("{user}:{}", pass.as_deref().unwrap_or(""));
+            header_map.push((
+                "Authorization".to_string(),
+                format!("Basic {}", BASE64_STANDARD.encode(raw)),
+            ));
+        }
+        (None, Some(_)) => return Err(anyhow::anyhow!("--basic-pass requires --basic-user")),
+        (None, None) => {}
+    }
+
+    // proxy
+    let proxy = match (&args.proxy, &args.proxy_auth) {
+        (Some(p), auth) => {
+            let mut proxy =
+                reqwest::Proxy::all(p).map_err(|e| anyhow::anyhow!("Invalid --proxy: {e}"))?;
+            if let Some(auth) = auth {
+                let (user, pass) = auth.split_once(':').ok_or_else(|| {
+                    anyhow::anyhow!("Invalid --proxy-auth format (expected \"user:pass\")")
+                })?;
+                proxy = proxy.basic_auth(user, pass);
+            }
+            Some(proxy)
+        }
+        (None, Some(_)) => return Err(anyhow::anyhow!("--proxy-auth requires --proxy")),
+        (None, None) => None,
+    };
+
+    let mut credential_sources = args.credential_sources.clone();
+    for (id, flag, present) in [
+        ("api_key", "--api-key", args.api_key.is_some()),
+        ("basic_pass", "--basic-pass", args.basic_pass.is_some()),
+        ("proxy_auth", "--proxy-auth", args.proxy_auth.is_some()),
+        ("hmac_sign", "--hmac-sign", args.hmac_sign.is_some()),
+    ] {
+        if present {
+            credential_sources
+                .entry(id.to_string())
+                .or_insert_with(|| flag.to_string());
+        } else {
+            credential_sources.remove(id);
+        }
+    }
+
+    // parse query params (values are expanded per request)
+    let mut query_params: Vec<(String, String)> = Vec::with_capacity(args.query_params.len());
+    for q in &args.query_params {
+        let (k, v) = parse_query_param(q).ok_or_else(|| {
+            anyhow::anyhow!("Invalid --query-param format: {q} (expected \"key=value\")")
+        })?;
+        query_params.push((k, expand_env_for("--query-param", &v)?));
+    }
+
+    let mut tags = BTreeMap::new();
+    for t in &args.tags {
+        let (k, v) = parse_query_param(t)
+            .ok_or_else(|| anyhow::anyhow!("Invalid --tag format: {t} (expected \"key=value\")"))?;
+        tags.insert(k, v);
+    }
+
+    if let (Some(min), Some(n)) = (args.min_samples, requests_total) {
+        if n < min {
+            log.warn(format_args!(
+                "--requests {n} is below --min-samples {min}; percentiles will be noisy"
+            ));
+        }
+    }
+
+    // parse response header expectations
+    let mut expect_headers = Vec::with_capacity(args.expect_headers.len());
+    for h in &args.expect_headers {
+        expect_headers.push(parse_expect_header(h).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Invalid --expect-header format: {h} (expected \"Name: Value\" or \"Name\")"
+            )
+        })?);
+    }
+    if args.grpc
+        && (args.success_body_contains.is_some()
+            || args.success_body_regex.is_some()
+            || args.response_schema.is_some())
+    {
+        return Err(anyhow::anyhow!(
+            "--success-body-contains/--success-body-regex/--response-schema cannot be combined with --grpc"
+        ));
+    }
+    let m = &args.max_validate_bytes;
+    let max_body_bytes = parse_byte_size(m)
+        .and_then(|n| usize::try_from(n).ok())
+        .filter(|&n| n > 0)
+        .ok_or_else(|| anyhow::anyhow!("Invalid --max-validate-bytes: {m}"))?;
+    let body_check = if args.success_body_contains.is_some() || args.success_body_regex.is_some() {
+        let regex = match &args.success_body_regex {
+            Some(p) => Some(
+                regex::Regex::new(p)
+                    .map_err(|e| anyhow::anyhow!("Invalid --success-body-regex: {p} ({e})"))?,
+            ),
+            None => None,
+        };
+        Some(BodyCheck {
+            contains: args.success_body_contains.clone(),
+            regex,
+        })
+    } else {
+        None
+    };
+    let schema = match &args.response_schema {
+        Some(path) => Some(load_response_schema(path)?),
+        None => None,
+    };
+    let body_size = match &args.expect_body_size_range {
+        Some(r) => Some(parse_size_range(r).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Invalid --expect-body-size-range: {r} (expected \"min:max\" like 1k:64k)"
+            )
+        })?),
+        None => None,
+    };
+    let sample_rate = args.validate_sample_rate;
+    if !(0.0..=1.0).contains(&sample_rate) {
+        return Err(anyhow::anyhow!(
+            "Invalid --validate-sample-rate: {sample_rate} (expected a fraction in [0, 1])"
+        ));
+    }
+    let checks = Arc::new(ResponseChecks {
+        expect_headers,
+        body: body_check,
+        schema,
+        max_body_bytes,
+        body_size,
+        sample_rate,
+    });
+
+    let scenario = match &args.scenario {
+        Some(path) => {
+            if args.json.is_some()
+                || args.json_file.is_some()
+                || args.stream_body_file.is_some()
+                || args.body_file.is_some()
+                || args.body_size.is_some()
+                || args.grpc
+            {
+                return Err(anyhow::anyhow!(
+                    "--scenario cannot be combined with --json, --json-file, --stream-body-file, --body-file, --body-size or --grpc"
+                ));
+            }
+            Some(load_scenario(path)?)
+        }
+        None => None,
+    };
+    let paths = match &args.paths_file {
+        Some(_) if scenario.is_some() => {
+            return Err(anyhow::anyhow!(
+                "--paths-file cannot be combined with --scenario"
+            ))
+        }
+        Some(p) => Some(load_paths(p)?),
+        None => None,
+    };
+    let hosts = match &args.hosts_file {
+        Some(_) if scenario.is_some() || paths.is_some() || !args.method_bodies.is_empty() => {
+            return Err(anyhow::anyhow!(
+                "--hosts-file cannot be combined with --scenario, --paths-file or --method-body"
+            ))
+        }
+        Some(p) => Some(load_hosts(p)?),
+        None => None,
+    };
+
+    let signer = match &args.hmac_sign {
+        Some(_) if args.stream_body_file.is_some() => {
+            return Err(anyhow::anyhow!(
+                "--hmac-sign cannot be combined with --stream-body-file"
+            ))
+        }
+        // the value holds the secret, so it is not echoed back
+        Some(h) => Some(Arc::new(parse_hmac_sign(h).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Invalid --hmac-sign (expected \"header=X-Signature,secret=...,algo=sha256\")"
+            )
+        })?)),
+        None => None,
+    };
+
+    // request body
+    let json_payload = load_json_payload(&args)?;
+    let body = match (json_payload, &args.stream_body_file) {
+        (Some(_), Some(_)) => {
+            return Err(anyhow::anyhow!(
+                "--stream-body-file cannot be combined with --json or --json-file"
+            ))
+        }
+        (Some(v), None) => BodySource::Json(serde_json::to_vec(&v)?),
+        (None, Some(path)) => {
+            std::fs::metadata(path)
+                .map_err(|e| anyhow::anyhow!("Failed to read --stream-body-file {path}: {e}"))?;
+            BodySource::StreamFile(path.clone())
+        }
+        (None, None) => BodySource::None,
+    };
+    let body = match (&args.body_size, body) {
+        (None, body) => body,
+        (Some(size), BodySource::None) => {
+            let n = parse_byte_size(size)
+                .ok_or_else(|| anyhow::anyhow!("Invalid --body-size: {size}"))?;
+            let n = usize::try_from(n)
+                .map_err(|_| anyhow::anyhow!("Invalid --body-size: {size} (too large)"))?;
+            // generated once; every request shares the buffer
+            let buf = match args.body_fill {
+                BodyFill::Zero => vec![0u8; n],
+                BodyFill::Random => {
+                    let mut buf = vec![0u8; n];
+                    worker_rng(args.seed, BODY_SEED_STREAM).fill(&mut buf[..]);
+                    buf
+                }
+            };
+            BodySource::Bytes(Bytes::from(buf))
+        }
+        (Some(_), _) => {
+            return Err(anyhow::anyhow!(
+                "--body-size cannot be combined with --json, --json-file or --stream-body-file"
+            ))
+        }
+    };
+    let body = match (&args.body_file, body) {
+        (None, body) => body,
+        (Some(path), BodySource::None) => BodySource::Bytes(Bytes::from(
+            std::fs::read(path)
+                .map_err(|e| anyhow::anyhow!("Failed to read --body-file {path}: {e}"))?,
+        )),
+        (Some(_), _) => {
+            return Err(anyhow::anyhow!(
+                "--body-file cannot be combined with --json, --json-file, --stream-body-file or --body-size"
+            ))
+        }
+    };
+    let body = match &args.payload_command {
+        None => body,
+        Some(_) if !matches!(body, BodySource::None) || scenario.is_some() || args.grpc => {
+            return Err(anyhow::anyhow!(
+                "--payload-command cannot be combined with --json, --json-file, --stream-body-file, --body-file, --body-size, --scenario or --grpc"
+            ))
+        }
+        Some(_) if args.hmac_sign.is_some() => {
+            return Err(anyhow::anyhow!(
+                "--hmac-sign cannot be combined with --payload-command"
+            ))
+        }
+        Some(cmd) => {
+            let cmd = PayloadCommand {
+                command: cmd.clone(),
+                cache: args.payload_command_cache,
+                slots: tokio::sync::Semaphore::new(
+                    std::thread::available_parallelism().map_or(4, |n| n.get()),
+                ),
+            };
+            // a broken command should stop the run here, not fail every request
+            cmd.output()
+                .await
+                .map_err(|e| anyhow::anyhow!("--payload-command failed: {e}"))?;
+            BodySource::Command(Arc::new(cmd))
+        }
+    };
+    let body = if args.form_files.is_empty() && args.form_fields.is_empty() {
+        body
+    } else {
+        if !matches!(body, BodySource::None) || scenario.is_some() || args.grpc {
+            return Err(anyhow::anyhow!(
+                "--form-file/--form-field cannot be combined with --json, --json-file, --stream-body-file, --body-file, --body-size, --scenario or --grpc"
+            ));
+        }
+        if signer.is_some() {
+            return Err(anyhow::anyhow!(
+                "--hmac-sign cannot be combined with --form-file/--form-field"
+            ));
+        }
+        let mut parts = Vec::new();
+        for f in &args.form_fields {
+            let (name, value) = parse_query_param(f).ok_or_else(|| {
+                anyhow::anyhow!("Invalid --form-field: {f} (expected \"name=value\")")
+            })?;
+            parts.push(FormPart::Field { name, value });
+        }
+        for f in &args.form_files {
+            let (name, path) = parse_form_file(f).ok_or_else(|| {
+                anyhow::anyhow!("Invalid --form-file: {f} (expected \"field=@path\")")
+            })?;
+            std::fs::metadata(&path)
+                .map_err(|e| anyhow::anyhow!("Failed to read --form-file {path}: {e}"))?;
+            parts.push(FormPart::File { name, path });
+        }
+        BodySource::Multipart(parts)
+    };
+    if args.no_body_download && args.measure_ttfb {
+        return Err(anyhow::anyhow!(
+            "--no-body-download cannot be combined with --measure-ttfb"
+        ));
+    }
+    // --grpc: one length-prefixed message (empty without --body-file), always POSTed
+    let (method, body) = if args.grpc {
+        if args.measure_ttfb || args.no_body_download {
+            return Err(anyhow::anyhow!(
+                "--grpc cannot be combined with --measure-ttfb or --no-body-download"
+            ));
+        }
+        let msg: &[u8] = match &body {
+            BodySource::None => &[],
+            BodySource::Bytes(b) => b,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "--grpc takes its message from --body-file (or --body-size), not --json, --json-file or --stream-body-file"
+                ))
+            }
+        };
+        let body = BodySource::Bytes(Bytes::from(grpc_frame(msg)));
+        header_map.retain(|(k, _)| !k.eq_ignore_ascii_case("content-type"));
+        header_map.push((
+            "content-type".to_string(),
+            "application/grpc+proto".to_string(),
+        ));
+        header_map.push(("te".to_string(), "trailers".to_string()));
+        (Method::POST, body)
+    } else {
+        (method, body)
+    };
+    // --compress-body: the payload is fixed, so it is compressed once and shared
+    let (body, body_compression) = match args.compress_body {
+        None => (body, None),
+        Some(BodyCompression::Gzip) => {
+            if args.grpc || scenario.is_some() {
+                return Err(anyhow::anyhow!(
+                    "--compress-body cannot be combined with --grpc or --scenario"
+                ));
+            }
+            let (payload, content_type) = match &body {
+                BodySource::Json(b) => (&b[..], "application/json"),
+                BodySource::Bytes(b) => (&b[..], "application/octet-stream"),
+                BodySource::None => {
+                    return Err(anyhow::anyhow!(
+                        "--compress-body needs a body (--json, --json-file, --body-file or --body-size)"
+                    ))
+                }
+                BodySource::StreamFile(_) | BodySource::Multipart(_) | BodySource::Command(_) => {
+                    return Err(anyhow::anyhow!(
+                        "--compress-body cannot be combined with --stream-body-file, --form-file/--form-field or --payload-command"
+                    ))
+                }
+            };
+            if header_map
+                .iter()
+                .any(|(k, _)| k.eq_ignore_ascii_case("content-encoding"))
+            {
+                return Err(anyhow::anyhow!(
+                    "A Content-Encoding --header cannot be combined with --compress-body"
+                ));
+            }
+            let compressed = gzip_bytes(payload)?;
+            let stats = BodyCompressionStats {
+                encoding: "gzip".to_string(),
+                original_bytes: payload.len() as u64,
+                compressed_bytes: compressed.len() as u64,
+            };
+            // keep the type of the uncompressed payload
+            if !header_map
+                .iter()
+                .any(|(k, _)| k.eq_ignore_ascii_case("content-type"))
+            {
+                header_map.push(("content-type".to_string(), content_type.to_string()));
+            }
+            header_map.push(("content-encoding".to_string(), "gzip".to_string()));
+            (BodySource::Bytes(Bytes::from(compressed)), Some(stats))
+        }
+    };
+
+    let method_bodies = if args.method_bodies.is_empty() {
+        None
+    } else {
+        if !matches!(body, BodySource::None) || scenario.is_some() || paths.is_some() || args.grpc {
+            return Err(anyhow::anyhow!(
+                "--method-body cannot be combined with --paths-file, --scenario, --grpc or another body flag"
+            ));
+        }
+        let mut entries = Vec::new();
+        for entry in &args.method_bodies {
+            let (m, file) = parse_method_body(entry).ok_or_else(|| {
+                anyhow::anyhow!("Invalid --method-body: {entry} (expected \"METHOD=body.json\")")
+            })?;
+            let (name, body) = match file {
+                Some(path) => {
+                    let bytes = std::fs::read(&path).map_err(|e| {
+                        anyhow::anyhow!("Failed to read --method-body file {path}: {e}")
+                    })?;
+                    let v: Value = serde_json::from_slice(&bytes).map_err(|e| {
+                        anyhow::anyhow!("Invalid JSON in --method-body file {path}: {e}")
+                    })?;
+                    (
+                        format!("{m} {path}"),
+                        BodySource::Json(serde_json::to_vec(&v)?),
+                    )
+                }
+                None => (m.to_string(), BodySource::None),
+            };
+            entries.push((name, m, body));
+        }
+        Some(entries)
+    };
+
+    let trace = match &args.trace_file {
+        Some(path) => {
+            if scenario.is_some()
+                || paths.is_some()
+                || hosts.is_some()
+                || method_bodies.is_some()
+                || args.grpc
+                || arrival_rate.is_some()
+                || !stage_plan.is_empty()
+                || min_interval.is_some()
+                || args.requests.is_some()
+            {
+                return Err(anyhow::anyhow!(
+                    "--trace-file cannot be combined with --scenario, --paths-file, --hosts-file, --method-body, --grpc, --open-loop, --stages, --max-rps-per-worker or --requests"
+                ));
+            }
+            Some(load_trace(path)?)
+        }
+        None => None,
+    };
+
+    if args.expect_100_continue {
+        if header_map
+            .iter()
+            .any(|(k, _)| k.eq_ignore_ascii_case("expect"))
+        {
+            return Err(anyhow::anyhow!(
+                "An Expect --header cannot be combined with --expect-100-continue"
+            ));
+        }
+        if matches!(body, BodySource::None) && scenario.is_none() {
+            log.warn(format_args!(
+                "--expect-100-continue without a request body; servers may ignore it"
+            ));
+        }
+        header_map.push(("Expect".to_string(), "100-continue".to_string()));
+    }
+
+    if args.inject_trace_header
+        && header_map
+            .iter()
+            .any(|(k, _)| k.eq_ignore_ascii_case("traceparent"))
+    {
+        return Err(anyhow::anyhow!(
+            "--inject-trace-header cannot be combined with a traceparent --header"
+        ));
+    }
+
+    let idempotency_header = if args.with_idempotency_key {
+        let name = args.idempotency_key_header.trim();
+        HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| anyhow::anyhow!("Invalid --idempotency-key-header: {name}"))?;
+        Some(name.to_string())
+    } else {
+        None
+    };
+
+    if let Some(path) = &args.unix_socket {
+        if proxy.is_some() {
+            return Err(anyhow::anyhow!(
+                "--unix-socket cannot be combined with --proxy"
+            ));
+        }
+        check_unix_socket(path)?;
+    }
+
+    let tcp_keepalive = match &args.tcp_keepalive {
+        Some(d) => Some(
+            parse_duration(d)
+                .filter(|d| !d.is_zero())
+                .ok_or_else(|| anyhow::anyhow!("Invalid --tcp-keepalive: {d}"))?,
+        ),
+        None => None,
+    };
+    if args.top_slow == Some(0) {
+        return Err(anyhow::anyhow!("Invalid --top-slow: 0"));
+    }
+    if let Some(rate) = args.har_sample_rate.filter(|r| !(*r > 0.0 && *r <= 1.0)) {
+        return Err(anyhow::anyhow!(
+            "Invalid --har-sample-rate: {rate} (expected a fraction in (0, 1])"
+        ));
+    }
+    if args.recycle_client_every == Some(0) {
+        return Err(anyhow::anyhow!("Invalid --recycle-client-every: 0"));
+    }
+    // binding the unspecified address of one family makes the connector skip the other
+    let local_address = match args.address_family {
+        AddressFamily::Auto => None,
+        family => {
+            if args.proxy.is_some() || args.unix_socket.is_some() {
+                return Err(anyhow::anyhow!(
+                    "--address-family cannot be combined with --proxy or --unix-socket"
+                ));
+            }
+            Some(check_address_family(&url, family).await?)
+        }
+    };
+    let socket = format!(
+        "tcp_nodelay={} tcp_keepalive={}",
+        if args.tcp_nodelay { "on" } else { "off" },
+        args.tcp_keepalive.as_deref().unwrap_or("off"),
+    );
+
+    // cleartext gRPC needs HTTP/2 without an upgrade; over TLS ALPN negotiates it
+    let h2_prior_knowledge = args.grpc && url.scheme() == "http";
+
+    // build client (per-request headers override the client-level User-Agent); the
+    // recipe is owned so --recycle-client-every workers can rebuild it
+    let user_agent = args.user_agent.clone();
+    let tcp_nodelay = args.tcp_nodelay;
+    let unix_socket = args.unix_socket.clone();
+    let client_builder = Arc::new(move || {
+        let mut builder = reqwest::Client::builder()
+            .timeout(timeout_dur)
+            .user_agent(user_agent.as_str())
+            .tcp_nodelay(tcp_nodelay)
+            .tcp_keepalive(tcp_keepalive)
+            .local_address(local_address);
+        if let Some(p) = &proxy {
+            builder = builder.proxy(p.clone());
+        }
+        if h2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        }
+        #[cfg(unix)]
+        if let Some(path) = &unix_socket {
+            builder = builder.unix_socket(std::path::Path::new(path));
+        }
+        builder
+    });
+    let recycle = args.recycle_client_every.map(|n| {
+        let builder = client_builder.clone();
+        let fresh: ClientFactory = Arc::new(move || builder().build());
+        (n, fresh)
+    });
+    let client = client_builder()
+        .build()
+        .context("Failed to build reqwest client")?;
+    log.debug(format_args!(
+        "client: method={method} url={url} timeout={timeout_dur:?} user_agent={:?} proxy={} concurrency={} {socket}",
+        args.user_agent,
+        args.proxy.is_some(),
+        args.concurrency.max(1),
+    ));
+
+    let mut specs = match scenario {
+        None => vec![RequestSpec {
+            name: String::new(),
+            method,
+            url,
+            headers: header_map,
+            query_params,
+            body,
+            preflight_headers: None,
+            idempotency_header,
+            expect_status: None,
+            captures: Vec::new(),
+            etag: None,
+            signer: signer.clone(),
+        }],
+        Some(sc) => {
+            let mut specs = Vec::with_capacity(sc.steps.len());
+            for (i, step) in sc.steps.into_iter().enumerate() {
+                let n = i + 1;
+                let method = parse_http_method(&step.method).ok_or_else(|| {
+                    anyhow::anyhow!("Invalid method in --scenario step {n}: {}", step.method)
+                })?;
+                let step_url = url.join(&step.path).map_err(|e| {
+                    anyhow::anyhow!("Invalid path in --scenario step {n}: {}: {e}", step.path)
+                })?;
+                for (k, v) in &step.headers {
+                    check_header(k, v).map_err(|e| {
+                        anyhow::anyhow!("Invalid header in --scenario step {n}: {k}: {e}")
+                    })?;
+                }
+                // a step header replaces every --header value of the same name
+                let mut headers = header_map.clone();
+                headers.retain(|(k, _)| !step.headers.keys().any(|s| s.eq_ignore_ascii_case(k)));
+                headers.extend(step.headers);
+                let body = match step.body {
+                    Some(v) => BodySource::Json(serde_json::to_vec(&v)?),
+                    None => BodySource::None,
+                };
+                let mut captures = Vec::with_capacity(step.capture.len());
+                for (var, path) in step.capture {
+                    let segs = parse_json_path(&path).ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "Invalid capture path in --scenario step {n}: {path} (expected \"$.key[0].key\")"
+                        )
+                    })?;
+                    captures.push(Capture { var, path, segs });
+                }
+                specs.push(RequestSpec {
+                    name: step
+                        .name
+                        .unwrap_or_else(|| format!("{method} {}", step.path)),
+                    method,
+                    url: step_url,
+                    headers,
+                    query_params: query_params.clone(),
+                    body,
+                    preflight_headers: None,
+                    idempotency_header: idempotency_header.clone(),
+                    expect_status: step.expect_status,
+                    captures,
+                    etag: None,
+                    signer: signer.clone(),
+                });
+            }
+            specs
+        }
+    };
+
+    if let Some(paths) = &paths {
+        let base = specs.pop().expect("one spec without --scenario");
+        specs = paths
+            .iter()
+            .map(|p| RequestSpec {
+                name: p.clone(),
+                method: base.method.clone(),
+                url: append_path(&base.url, p),
+                headers: base.headers.clone(),
+                query_params: base.query_params.clone(),
+                body: base.body.clone(),
+                preflight_headers: None,
+                idempotency_header: base.idempotency_header.clone(),
+                expect_status: None,
+                captures: Vec::new(),
+                etag: None,
+                signer: signer.clone(),
+            })
+            .collect();
+    }
+    // --hosts-file: one spec per host; `host_slots` spreads the picks by weight
+    let host_slots = match &hosts {
+        Some(hosts) => {
+            let base = specs.pop().expect("one spec without --scenario");
+            let mut host_specs = Vec::with_capacity(hosts.len());
+            for (i, h) in hosts.iter().enumerate() {
+                let n = i + 1;
+                let target = Url::parse(&h.url)
+                    .ok()
+                    .filter(|u| u.has_host())
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("Invalid url in --hosts-file entry {n}: {}", h.url)
+                    })?;
+                let mut url = base.url.clone();
+                if url.set_scheme(target.scheme()).is_err()
+                    || url.set_host(target.host_str()).is_err()
+                    || url.set_port(target.port()).is_err()
+                {
+                    return Err(anyhow::anyhow!(
+                        "Invalid url in --hosts-file entry {n}: {}",
+                        h.url
+                    ));
+                }
+                for (k, v) in &h.headers {
+                    check_header(k, v).map_err(|e| {
+                        anyhow::anyhow!("Invalid header in --hosts-file entry {n}: {k}: {e}")
+                    })?;
+                }
+                // a host header replaces every --header value of the same name
+                let mut headers = base.headers.clone();
+                headers.retain(|(k, _)| !h.headers.keys().any(|s| s.eq_ignore_ascii_case(k)));
+                headers.extend(h.headers.clone());
+                let name = match target.port() {
+                    Some(port) => format!("{}:{port}", target.host_str().unwrap_or_default()),
+                    None => target.host_str().unwrap_or_default().to_string(),
+                };
+                host_specs.push(RequestSpec {
+                    name,
+                    method: base.method.clone(),
+                    url,
+                    headers,
+                    query_params: base.query_params.clone(),
+                    body: base.body.clone(),
+                    preflight_headers: None,
+                    idempotency_header: base.idempotency_header.clone(),
+                    expect_status: None,
+                    captures: Vec::new(),
+                    etag: None,
+                    signer: signer.clone(),
+                });
+            }
+            specs = host_specs;
+            let weights: Vec<u32> = hosts.iter().map(|h| h.weight).collect();
+            Some(Arc::new(weighted_slots(&weights)))
+        }
+        None => None,
+    };
+    if let Some(entries) = &method_bodies {
+        let base = specs.pop().expect("one spec without --scenario");
+        specs = entries
+            .iter()
+            .map(|(name, method, body)| RequestSpec {
+                name: name.clone(),
+                method: method.clone(),
+                url: base.url.clone(),
+                headers: base.headers.clone(),
+                query_params: base.query_params.clone(),
+                body: body.clone(),
+                preflight_headers: None,
+                idempotency_header: base.idempotency_header.clone(),
+                expect_status: None,
+                captures: Vec::new(),
+                etag: None,
+                signer: signer.clone(),
+            })
+            .collect();
+    }
+
+    // --trace-file: one spec per distinct method and path; the schedule points into them
+    let schedule = trace.map(|entries| {
+        let base = specs.pop().expect("one spec without --scenario");
+        let mut index: BTreeMap<(String, String), usize> = BTreeMap::new();
+        let mut schedule = Vec::with_capacity(entries.len());
+        for e in entries {
+            let key = (e.method.to_string(), e.path.clone());
+            let step = *index.entry(key).or_insert_with(|| {
+                specs.push(RequestSpec {
+                    name: format!("{} {}", e.method, e.path),
+                    method: e.method.clone(),
+                    url: append_path(&base.url, &e.path),
+                    headers: base.headers.clone(),
+                    query_params: base.query_params.clone(),
+                    body: base.body.clone(),
+                    preflight_headers: None,
+                    idempotency_header: base.idempotency_header.clone(),
+                    expect_status: None,
+                    captures: Vec::new(),
+                    etag: None,
+                    signer: signer.clone(),
+                });
+                specs.len() - 1
+            });
+            schedule.push((Duration::from_millis(e.offset_ms), step));
+        }
+        Arc::new(schedule)
+    });
+
+    // CORS preflight (browsers only send one for non-simple requests; we model write methods)
+    if args.measure_preflight {
+        for spec in &mut specs {
+            if is_write_method(&spec.method) {
+                spec.preflight_headers =
+                    Some(preflight_headers(&spec.method, &spec.url, &spec.headers));
+            }
+        }
+        if specs.iter().all(|s| s.preflight_headers.is_none()) {
+            log.warn(format_args!(
+                "--measure-preflight only applies to POST/PUT/PATCH/DELETE; ignored for {}",
+                specs[0].method
+            ));
+        }
+    }
+    if args.capture_etag {
+        for spec in &mut specs {
+            spec.etag = Some(std::sync::RwLock::new(None));
+        }
+    }
+    let specs = Arc::new(specs);
+    let conc = profile.map_or(args.concurrency, |p| p.peak()).max(1);
+
+    let tls_resumption = match args.measure_tls_resumption {
+        Some(n) if n > 0 => {
+            if specs[0].url.scheme() != "https" {
+                return Err(anyhow::anyhow!(
+                    "--measure-tls-resumption needs an https --url"
+                ));
+            }
+            if args.proxy.is_some() || args.unix_socket.is_some() || args.once {
+                return Err(anyhow::anyhow!(
+                    "--measure-tls-resumption cannot be combined with --proxy, --unix-socket or --once"
+                ));
+            }
+            let roots = tokio_rustls::rustls::RootCertStore {
+                roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+            };
+            Some(measure_tls_resumption(&specs[0].url, n, roots, timeout_dur, hist).await?)
+        }
+        _ => None,
+    };
+
+    let handshake = match args.measure_handshake {
+        Some(n) if n > 0 => {
+            // no idle connections are kept, so every request connects from scratch
+            let fresh = client_builder()
+                .pool_max_idle_per_host(0)
+                .build()
+                .context("Failed to build reqwest client")?;
+            Some(measure_handshakes(&fresh, &specs[0], n, hist).await?)
+        }
+        _ => None,
+    };
+
+    let warmup_seed = args.seed.map(|s| derive_seed(s, WARMUP_SEED_STREAM));
+    let warmup_discarded = run_warmup(&client, &specs, conc, args.warmup, warmup_seed).await;
+
+    let initial_failures = match &args.abort_if_initial_failures {
+        Some(s) => Some(parse_initial_failures(s).ok_or_else(|| {
+            anyhow::anyhow!("Invalid --abort-if-initial-failures: {s} (expected \"N/M\" like 3/5)")
+        })?),
+        None => None,
+    };
+
+    let pool = Pool {
+        clock: args.clock.clone(),
+        concurrency: conc,
+        limit: args.requests,
+        per_worker_limit: args.requests_per_worker,
+        cycles: args.cycles,
+        duration: duration_target,
+        seq_offset: 0,
+        progress_every: if args.log_level == LogLevel::Quiet {
+            0
+        } else {
+            args.progress_every
+        },
+        progress_format: args.progress_format,
+        checks,
+        capture_slow_over_micros: capture_slow_over.map(|d| d.as_micros() as u64),
+        top_slow: args.top_slow,
+        har_rate: args.har_sample_rate,
+        hist,
+        log,
+        shutdown_grace,
+        sample_interval,
+        heat_bucket: heatmap_bucket,
+        burst,
+        profile,
+        seed: args.seed,
+        min_interval,
+        live: args.live.clone(),
+        events: args.events.clone(),
+        select: (paths.is_some() || hosts.is_some() || method_bodies.is_some())
+            .then_some(args.select),
+        slots: host_slots,
+        recycle,
+        rps_timeline: args.rps_timeline,
+        respect_retry_after: args.respect_retry_after,
+        retry,
+        measure_ttfb: args.measure_ttfb,
+        per_kb: args.normalize_latency_by_payload_size,
+        server_timing: args.parse_server_timing,
+        dedupe_errors: args.dedupe_errors,
+        inject_trace: args.inject_trace_header,
+        drain_bodies: !args.no_body_download,
+        grpc: args.grpc,
+        hard_timeout,
+        initial_failures,
+        arrival_rate,
+        overload: args.max_inflight.map(|n| (n, args.on_overload)),
+        schedule: schedule.clone(),
+        stable_tolerance: stable.map(|(_, tol)| tol),
+    };
+
+    // --warm-compare: a measured priming pass on the same client, so its connections
+    // and any server-side caches stay warm for the main run
+    let cold = match args.warm_compare {
+        Some(n) if n > 0 => {
+            let primed = run_pool(
+                &client,
+                &specs,
+                Pool {
+                    limit: Some(n),
+                    per_worker_limit: None,
+                    cycles: None,
+                    duration: None,
+                    progress_every: 0,
+                    sample_interval: None,
+                    stable_tolerance: None,
+                    burst: None,
+                    profile: None,
+                    live: None,
+                    events: None,
+                    rps_timeline: false,
+                    arrival_rate: None,
+                    schedule: None,
+                    seed: args.seed.map(|s| derive_seed(s, COLD_SEED_STREAM)),
+                    ..pool.clone()
+                },
+            )
+            .await?;
+            Some(primed.aggregates)
+        }
+        _ => None,
+    };
+
+    let mut once = None;
+    let (outcome, stages) = if args.once {
+        let budget = args.latency_budget.then_some(timeout_dur);
+        let method = &specs[0].method;
+        if budget.is_some() && !method.is_safe() && !args.latency_budget_allow_unsafe {
+            return Err(anyhow::anyhow!(
+                "--latency-budget sends the request twice; pass --latency-budget-allow-unsafe to repeat a {method}"
+            ));
+        }
+        let (o, detail) = send_once(&client, &specs, args.seed, hist, budget).await?;
+        once = Some(detail);
+        (o, Vec::new())
+    } else if stage_plan.is_empty() {
+        (run_pool(&client, &specs, pool).await?, Vec::new())
+    } else {
+        run_stages(&client, &specs, &pool, &stage_plan).await?
+    };
+    if let (Some(failed), Some((n, m))) = (outcome.initial_failures, initial_failures) {
+        let e = &outcome.aggregates.net_errors;
+        return Err(anyhow::anyhow!(
+            "Aborted: {failed} of the first {m} requests failed (allowed {n}; dns={} connect={} timeout={}); is --url {} right?",
+            e.dns,
+            e.connect,
+            e.timeout,
+            args.url
+        ));
+    }
+
+    let apdex = apdex_threshold.map(|t| apdex(&outcome.aggregates.latency_micros, t));
+    let knee = find_knee(&stages);
+
+    let replay = schedule
+        .as_ref()
+        .zip(outcome.dispatch_lag.as_ref())
+        .map(|(sch, lag)| ReplayStats {
+            scheduled: sch.len() as u64,
+            sent: outcome.sent,
+            lag_p50_ms: lag.value_at_quantile(0.50) as f64 / 1000.0,
+            lag_p99_ms: lag.value_at_quantile(0.99) as f64 / 1000.0,
+            lag_max_ms: lag.max() as f64 / 1000.0,
+        });
+
+    let stable = stable.map(|(window, tol)| {
+        if outcome.stabilized.is_none() {
+            log.warn(format_args!(
+                "p99 did not settle within {}% before the run ended",
+                args.stable_tolerance
+            ));
+        }
+        Stability {
+            window_sec: window.as_secs_f64(),
+            tolerance_pct: tol * 100.0,
+            after_requests: outcome.stabilized.map(|(n, _)| n),
+            after_sec: outcome.stabilized.map(|(_, t)| t),
+            default_cap_sec: stable_cap.map(|d| d.as_secs_f64()),
+        }
+    });
+
+    let soak = if args.soak {
+        let stats = soak_stats(&outcome.samples);
+        match &stats {
+            Some(st) if st.p99_drift_ms_per_hour > args.soak_max_drift => {
+                log.warn(format_args!(
+                    "p99 drifts by {:.3} ms/hour (--soak-max-drift {})",
+                    st.p99_drift_ms_per_hour, args.soak_max_drift
+                ));
+            }
+            Some(_) => {}
+            None => log.warn(format_args!(
+                "--soak needs at least two --sample-interval windows"
+            )),
+        }
+        stats
+    } else {
+        None
+    };
+
+    let completed = outcome.completed;
+    // the rate asked for: --open-loop arrivals, or every worker at its --max-rps-per-worker
+    // cap (stages, profiles and bursts change the worker count, so there is none then)
+    let target_rps = arrival_rate.or(args
+        .max_rps_per_worker
+        .filter(|_| stage_plan.is_empty() && profile.is_none() && args.burst.is_none())
+        .map(|n| n * conc as f64));
+    if let Some(target) = target_rps.filter(|_| outcome.elapsed_sec > 0.0) {
+        let achieved = completed as f64 / outcome.elapsed_sec;
+        if achieved < target * RATE_ATTAINMENT_WARN {
+            log.warn(format_args!(
+                "achieved {achieved:.2} rps of the {target} rps target ({:.1}%); the server or network is the likely bottleneck, not the load generator",
+                achieved / target * 100.0
+            ));
+        }
+    }
+    let closes = outcome.aggregates.connection_close_responses;
+    let responses: u64 = outcome.aggregates.status_exact.values().sum();
+    if !args.once && responses > 1 && closes as f64 >= responses as f64 * CONNECTION_CLOSE_WARN {
+        log.warn(format_args!(
+            "{closes} of {responses} responses carried Connection: close; keep-alive is not in effect, so connect time and connect errors weigh on every request"
+        ));
+    }
+    if let Some(min) = args.min_samples {
+        if completed < min {
+            log.warn(format_args!(
+                "only {completed} completions (--min-samples {min}); percentiles will be noisy"
+            ));
+        }
+    }
+
+    let per_spec: Vec<StepResult> = specs
+        .iter()
+        .zip(outcome.steps)
+        .map(|(spec, aggregates)| StepResult {
+            name: spec.name.clone(),
+            method: spec.method.to_string(),
+            url: spec.url.to_string(),
+            aggregates,
+        })
+        .collect();
+    let (steps, paths, methods, hosts) = if args.scenario.is_some() {
+        (per_spec, Vec::new(), Vec::new(), Vec::new())
+    } else if paths.is_some() || schedule.is_some() {
+        (Vec::new(), per_spec, Vec::new(), Vec::new())
+    } else if method_bodies.is_some() {
+        (Vec::new(), Vec::new(), per_spec, Vec::new())
+    } else if hosts.is_some() {
+        (Vec::new(), Vec::new(), Vec::new(), per_spec)
+    } else {
+        (Vec::new(), Vec::new(), Vec::new(), Vec::new())
+    };
+
+    Ok(RunResult {
+        url: args.url,
+        method: args.method,
+        concurrency: stage_plan
+            .iter()
+            .map(|st| st.concurrency)
+            .max()
+            .unwrap_or(conc),
+        requests_target: requests_total,
+        requests_per_worker: args.requests_per_worker,
+        duration_target: args.duration,
+        timeout: args.timeout,
+        seed: args.seed,
+        tags,
+        credential_sources,
+        handshake,
+        tls_resumption,
+        cold,
+        unix_socket: args.unix_socket.clone(),
+        address_family: format!("{:?}", args.address_family).to_lowercase(),
+        socket,
+        runtime_threads: tokio::runtime::Handle::current().metrics().num_workers(),
+        recycle_client_every: args.recycle_client_every,
+        client_recycles: outcome.client_recycles,
+        bodies_drained: !args.no_body_download,
+        expect_100_continue: args.expect_100_continue,
+        elapsed_sec: outcome.elapsed_sec,
+        sent: outcome.sent,
+        completed,
+        warmup_discarded,
+        aborted_workers: outcome.aborted_workers,
+        worker_panics: outcome.worker_panics,
+        aggregates: outcome.aggregates,
+        stages,
+        samples: outcome.samples,
+        bursts: outcome.bursts,
+        concurrency_timeline: outcome.concurrency_timeline,
+        heatmap_bucket_ms: heatmap_bucket.map(|b| b as f64 / 1000.0),
+        soak,
+        knee,
+        stable,
+        apdex,
+        rps_timeline: outcome.per_second,
+        arrival_rate,
+        target_rps,
+        overload: outcome.overload,
+        cycles: args
+            .cycles
+            .map(|k| CycleStats::from_counts(k, &outcome.cycles)),
+        replay,
+        body_compression,
+        top_slow: outcome.top_slow,
+        har: outcome.har,
+        once,
+        max_in_flight: outcome.max_in_flight,
+        steps,
+        paths,
+        methods,
+        hosts,
+    })
+}
+
+/// Builds a fresh client with the run's settings.
+type ClientFactory = Arc<dyn Fn() -> reqwest::Result<reqwest::Client> + Send + Sync>;
+
+/// Where the worker pool reads the time and sleeps: `SystemClock` in real runs, a
+/// `MockClock` in tests of pacing and deadlines.
+pub trait Clock: Send + Sync + std::fmt::Debug {
+    fn now(&self) -> Instant;
+
+    /// Resolves once `now()` has reached `at`.
+    fn sleep_until(&self, at: Instant) -> Pin<Box<dyn Future<Output = ()> + Send + '_>>;
+
+    fn since(&self, earlier: Instant) -> Duration {
+        self.now().saturating_duration_since(earlier)
+    }
+}
+
+/// The real clock: `Instant::now()` and tokio's timer.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep_until(&self, at: Instant) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        Box::pin(tokio::time::sleep_until(at.into()))
+    }
+}
+
+/// A clock that stands still until something sleeps on it or `advance` is called;
+/// sleeping jumps straight to the wake-up time, so paced runs finish at once. Every
+/// sleeper moves it for all, so schedules are exact with one sleeping task (one worker,
+/// no --sample-interval or --burst). Network I/O takes no time on it.
+#[derive(Debug)]
+pub struct MockClock {
+    base: Instant,
+    offset_micros: AtomicU64,
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        Self {
+            base: Instant::now(),
+            offset_micros: AtomicU64::new(0),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        let micros = by.as_micros().min(u128::from(u64::MAX)) as u64;
+        self.offset_micros.fetch_add(micros, Ordering::SeqCst);
+    }
+
+    /// How far the clock has moved since it was made.
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_micros(self.offset_micros.load(Ordering::SeqCst))
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.base + self.elapsed()
+    }
+
+    fn sleep_until(&self, at: Instant) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        let micros = at.saturating_duration_since(self.base).as_micros();
+        self.offset_micros
+            .fetch_max(micros.min(u128::from(u64::MAX)) as u64, Ordering::SeqCst);
+        // still a suspension point, so other workers get to run
+        Box::pin(tokio::task::yield_now())
+    }
+}
+
+/// Parameters for one worker pool: a whole run, or one stage of it.
+#[derive(Clone)]
+struct Pool {
+    clock: Arc<dyn Clock>,
+    concurrency: usize,
+    limit: Option<u64>,
+    /// --requests-per-worker: each worker stops after this many, without a shared count
+    per_worker_limit: Option<u64>,
+    /// --cycles: the pool stops once every worker has made this many passes
+    cycles: Option<u64>,
+    duration: Option<Duration>,
+    /// added to the per-pool sequence so `{{seq}}` keeps counting across stages
+    seq_offset: u64,
+    progress_every: u64,
+    progress_format: ProgressFormat,
+    checks: Arc<ResponseChecks>,
+    capture_slow_over_micros: Option<u64>,
+    /// --top-slow: how many of the slowest requests to keep
+    top_slow: Option<usize>,
+    /// --har-sample-rate
+    har_rate: Option<f64>,
+    hist: HistConfig,
+    log: Logger,
+    /// with a duration: how long past it the pool waits before aborting workers
+    shutdown_grace: Duration,
+    sample_interval: Option<Duration>,
+    /// --heatmap-bucket width in micros
+    heat_bucket: Option<u64>,
+    /// --burst on and off phase lengths
+    burst: Option<(Duration, Duration)>,
+    /// --concurrency-profile; `concurrency` is its peak
+    profile: Option<ConcurrencyProfile>,
+    /// --until-stable: stop once p99 moves by at most this fraction between sample windows
+    stable_tolerance: Option<f64>,
+    /// root of the per-worker RNG seeds; `None` seeds from entropy
+    seed: Option<u64>,
+    /// --max-rps-per-worker pacing: minimum gap between a worker's requests
+    min_interval: Option<Duration>,
+    live: Option<Arc<LiveStats>>,
+    events: Option<Sender<RequestOutcome>>,
+    /// --select order for the --paths-file or --method-body pool; `None` walks the specs in order from the first
+    select: Option<Select>,
+    /// --hosts-file: spec indexes repeated by weight; `select` walks these instead of the specs
+    slots: Option<Arc<Vec<usize>>>,
+    /// --recycle-client-every: requests per worker between rebuilds, and the recipe
+    recycle: Option<(u64, ClientFactory)>,
+    rps_timeline: bool,
+    respect_retry_after: bool,
+    retry: Option<RetryPolicy>,
+    measure_ttfb: bool,
+    /// --normalize-latency-by-payload-size
+    per_kb: bool,
+    /// --parse-server-timing
+    server_timing: bool,
+    /// --dedupe-errors
+    dedupe_errors: bool,
+    /// --inject-trace-header
+    inject_trace: bool,
+    /// read bodies to the end so connections go back to the pool
+    drain_bodies: bool,
+    grpc: bool,
+    /// --hard-timeout per attempt
+    hard_timeout: Option<Duration>,
+    /// --abort-if-initial-failures (N, M)
+    initial_failures: Option<(u64, u64)>,
+    /// --open-loop: mean request starts per second; workers take their start times from a pacer
+    arrival_rate: Option<f64>,
+    /// --max-inflight and --on-overload
+    overload: Option<(u64, OnOverload)>,
+    /// --trace-file: start offsets and spec index of each request, in order
+    schedule: Option<Arc<Vec<(Duration, usize)>>>,
+}
+
+/// --retries settings.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub base: Duration,
+    pub max: Duration,
+    pub mode: BackoffMode,
+}
+
+impl RetryPolicy {
+    /// Pause before retry number `attempt` (1-based). Exponential mode draws uniformly
+    /// from 0..=min(max, base * 2^attempt) ("full jitter").
+    pub fn delay(&self, attempt: u32, rng: &mut impl Rng) -> Duration {
+        match self.mode {
+            BackoffMode::Fixed => self.base,
+            BackoffMode::Exponential => {
+                let factor = 2u32.checked_pow(attempt).unwrap_or(u32::MAX);
+                let ceiling = self.base.saturating_mul(factor).min(self.max);
+                ceiling.mul_f64(rng.gen::<f64>())
+            }
+        }
+    }
+}
+
+/// Response validations applied by the workers.
+#[derive(Debug, Default)]
+struct ResponseChecks {
+    expect_headers: Vec<(String, Option<String>)>,
+    body: Option<BodyCheck>,
+    schema: Option<jsonschema::Validator>,
+    /// --max-validate-bytes: how much of a body the body checks read
+    max_body_bytes: usize,
+    /// --expect-body-size-range, inclusive
+    body_size: Option<(u64, u64)>,
+    /// --validate-sample-rate
+    sample_rate: f64,
+}
+
+/// --success-body-contains/--success-body-regex; both must hold when both are given.
+#[derive(Debug)]
+struct BodyCheck {
+    contains: Option<String>,
+    regex: Option<regex::Regex>,
+}
+
+impl BodyCheck {
+    fn passes(&self, body: &[u8]) -> bool {
+        let text = String::from_utf8_lossy(body);
+        self.contains
+            .as_ref()
+            .is_none_or(|c| text.contains(c.as_str()))
+            && self.regex.as_ref().is_none_or(|re| re.is_match(&text))
+    }
+}
+
+/// What the body checks made of one response.
+struct BodyVerdict {
+    /// --success-body-*
+    matched: Option<bool>,
+    /// --response-schema
+    schema: Option<SchemaVerdict>,
+}
+
+enum SchemaVerdict {
+    Valid,
+    Invalid(String),
+    Skipped,
+}
+
+impl BodyVerdict {
+    fn record(self, a: &mut Aggregates) {
+        if let Some(ok) = self.matched {
+            a.body_checks.record(ok);
+        }
+        match self.schema {
+            Some(SchemaVerdict::Valid) => a.schema_checks.record(true),
+            Some(SchemaVerdict::Invalid(v)) => {
+                a.schema_checks.record(false);
+                a.push_schema_violation(&v);
+            }
+            Some(SchemaVerdict::Skipped) => a.schema_skipped += 1,
+            None => {}
+        }
+    }
+}
+
+impl ResponseChecks {
+    fn reads_body(&self) -> bool {
+        self.body.is_some() || self.schema.is_some()
+    }
+
+    /// Whether this response is checked; the RNG is only drawn from when sampling, so
+    /// seeded runs without --validate-sample-rate keep their sequence.
+    fn sampled(&self, rng: &mut StdRng) -> bool {
+        self.sample_rate >= 1.0 || rng.gen::<f64>() < self.sample_rate
+    }
+
+    /// Judges a body read up to one byte past `max_body_bytes`, so a schema check can
+    /// tell a complete body from a cut-off one.
+    fn judge_body(&self, content_type: Option<&str>, body: &[u8]) -> BodyVerdict {
+        let head = &body[..body.len().min(self.max_body_bytes)];
+        let schema = self.schema.as_ref().map(|validator| {
+            // without a Content-Type, a body that does not parse is not taken for JSON
+            let declared = content_type.map(|ct| ct.to_ascii_lowercase().contains("json"));
+            if declared == Some(false) || body.is_empty() || body.len() > self.max_body_bytes {
+                return SchemaVerdict::Skipped;
+            }
+            match serde_json::from_slice::<Value>(body) {
+                Err(_) if declared.is_none() => SchemaVerdict::Skipped,
+                Err(e) => SchemaVerdict::Invalid(format!("not JSON: {e}")),
+                Ok(v) => match validator.validate(&v) {
+                    Ok(()) => SchemaVerdict::Valid,
+                    Err(e) => SchemaVerdict::Invalid(format!("{}: {e}", e.instance_path())),
+                },
+            }
+        });
+        BodyVerdict {
+            matched: self.body.as_ref().map(|check| check.passes(head)),
+            schema,
+        }
+    }
+
+    fn apply(&self, resp: &reqwest::Response, a: &mut Aggregates) {
+        for (name, expected) in &self.expect_headers {
+            let mut values = resp.headers().get_all(name.as_str()).iter();
+            let ok = match expected {
+                None => values.next().is_some(),
+                Some(want) => values.any(|v| v.to_str().is_ok_and(|v| v.trim() == want)),
+            };
+            a.record_header_check(name, ok);
+        }
+    }
+}
+
+struct PoolOutcome {
+    elapsed_sec: f64,
+    sent: u64,
+    completed: u64,
+    aggregates: Aggregates,
+    aborted_workers: u64,
+    worker_panics: u64,
+    samples: Vec<IntervalSample>,
+    bursts: Vec<BurstPhase>,
+    concurrency_timeline: Vec<ConcurrencyPoint>,
+    /// one per request spec (scenario step); `aggregates` is their sum
+    steps: Vec<Aggregates>,
+    /// completions per elapsed second (--rps-timeline)
+    per_second: Vec<u64>,
+    /// most requests in flight at once (tracked for --open-loop)
+    max_in_flight: u64,
+    /// --max-inflight
+    overload: Option<OverloadStats>,
+    /// --until-stable: completed requests and seconds when p99 settled
+    stabilized: Option<(u64, f64)>,
+    /// --abort-if-initial-failures tripped: early connection failures seen
+    initial_failures: Option<u64>,
+    /// --trace-file: start lateness against the schedule
+    dispatch_lag: Option<Histogram<u64>>,
+    /// --recycle-client-every rebuilds
+    client_recycles: u64,
+    /// --top-slow, slowest first
+    top_slow: Vec<TopSlowRequest>,
+    /// --har-file entries
+    har: Vec<HarEntry>,
+    /// --cycles: full passes per worker
+    cycles: Vec<u64>,
+}
+
+/// Turns a --burst phase's histogram into its summary and resets it for the next phase.
+fn close_burst(w: &mut Histogram<u64>, start: Instant, from: Instant, on: Duration) -> BurstPhase {
+    let phase = BurstPhase {
+        start_sec: from.duration_since(start).as_secs_f64(),
+        on_sec: on.as_secs_f64(),
+        requests: w.len(),
+        p50_ms: w.value_at_quantile(0.50) as f64 / 1000.0,
+        p99_ms: w.value_at_quantile(0.99) as f64 / 1000.0,
+    };
+    w.reset();
+    phase
+}
+
+/// Turns the window histogram into a sample and resets it for the next window.
+/// With `heat_bucket` (micros) the sample also carries its linear bucket counts.
+fn close_window(
+    w: &mut Histogram<u64>,
+    start: Instant,
+    from: Instant,
+    to: Instant,
+    heat_bucket: Option<u64>,
+) -> IntervalSample {
+    let buckets = match heat_bucket {
+        Some(step) if !w.is_empty() => w
+            .iter_linear(step)
+            .map(|v| v.count_since_last_iteration())
+            .collect(),
+        _ => Vec::new(),
+    };
+    let sample = IntervalSample {
+        t_sec: to.duration_since(start).as_secs_f64(),
+        width_sec: to.duration_since(from).as_secs_f64(),
+        requests: w.len(),
+        p50_ms: w.value_at_quantile(0.50) as f64 / 1000.0,
+        p99_ms: w.value_at_quantile(0.99) as f64 / 1000.0,
+        buckets,
+    };
+    w.reset();
+    sample
+}
+
+/// Awaits the workers in order, collecting "worker N panicked: ..." for each one that
+/// panicked instead of letting the panic vanish with its task.
+pub async fn join_workers(handles: Vec<tokio::task::JoinHandle<()>>, panics: &mut Vec<String>) {
+    for (worker, h) in handles.into_iter().enumerate() {
+        let Err(e) = h.await else { continue };
+        if !e.is_panic() {
+            continue;
+        }
+        let payload = e.into_panic();
+        let msg = match payload.downcast_ref::<&str>() {
+            Some(m) => m.to_string(),
+            None => payload
+                .downcast_ref::<String>()
+                .cloned()
+                .unwrap_or_else(|| "non-string payload".to_string()),
+        };
+        panics.push(format!("worker {worker} panicked: {msg}"));
+    }
+}
+
+async fn run_pool(
+    client: &reqwest::Client,
+    specs: &Arc<Vec<RequestSpec>>,
+    pool: Pool,
+) -> anyhow::Result<PoolOutcome> {
+    // shared state: one set of aggregates per spec
+    let mut per_spec = Vec::with_capacity(specs.len());
+    for _ in specs.iter() {
+        per_spec.push(Aggregates::with_hist(pool.hist)?);
+    }
+    let agg = Arc::new(Mutex::new(per_spec));
+    let sent = Arc::new(AtomicU64::new(0));
+    // `sent` runs past --requests when workers race for the last slots
+    let sent_cap = pool.limit.unwrap_or(u64::MAX);
+    let completed = Arc::new(AtomicU64::new(0));
+    let stop = Arc::new(AtomicBool::new(false));
+
+    let clock = pool.clock.clone();
+    let start = clock.now();
+    let deadline = pool.duration.map(|d| start + d);
+
+    // --sample-interval: workers also record into the current window, a ticker closes it
+    let window = Arc::new(Mutex::new(pool.hist.build()?));
+    let samples = Arc::new(Mutex::new(Vec::new()));
+    let stabilized = Arc::new(Mutex::new(None));
+    let sampler = pool.sample_interval.map(|iv| {
+        let window = window.clone();
+        let samples = samples.clone();
+        let stabilized = stabilized.clone();
+        let completed = completed.clone();
+        let stop = stop.clone();
+        let tolerance = pool.stable_tolerance;
+        let heat_bucket = pool.heat_bucket;
+        let clock = clock.clone();
+        tokio::spawn(async move {
+            let mut tick = start;
+            let mut last = start;
+            let mut prev_p99: Option<f64> = None;
+            loop {
+                // late ticks catch up at once, like a tokio interval
+                tick += iv;
+                clock.sleep_until(tick).await;
+                let now = tick;
+                let sample = close_window(&mut *window.lock().await, start, last, now, heat_bucket);
+                if let Some(tol) = tolerance {
+                    // an empty window says nothing about p99, so it restarts the comparison
+                    let p99 = (sample.requests > 0).then_some(sample.p99_ms);
+                    if let (Some(prev), Some(cur)) = (prev_p99, p99) {
+                        if prev > 0.0 && (cur - prev).abs() / prev <= tol {
+                            let mut st = stabilized.lock().await;
+                            if st.is_none() {
+                                *st = Some((completed.load(Ordering::Relaxed), sample.t_sec));
+                                stop.store(true, Ordering::Relaxed);
+                            }
+                        }
+                    }
+                    prev_p99 = p99;
+                }
+                samples.lock().await.push(sample);
+                last = now;
+            }
+        })
+    });
+
+    // --burst: a coordinator flips `active` at each phase boundary and wakes the idle
+    // workers when an on-phase begins; each on-phase is summarized once its off-phase ends
+    let burst_window = Arc::new(Mutex::new(pool.hist.build()?));
+    let bursts = Arc::new(Mutex::new(Vec::new()));
+    let burst_gate = pool.burst.map(|_| {
+        (
+            Arc::new(AtomicBool::new(true)),
+            Arc::new(tokio::sync::Notify::new()),
+        )
+    });
+    let coordinator = pool
+        .burst
+        .zip(burst_gate.clone())
+        .map(|((on, off), (active, resume))| {
+            let window = burst_window.clone();
+            let bursts = bursts.clone();
+            let clock = clock.clone();
+            tokio::spawn(async move {
+                let mut from = start;
+                loop {
+                    clock.sleep_until(from + on).await;
+                    active.store(false, Ordering::Relaxed);
+                    let next = from + on + off;
+                    clock.sleep_until(next).await;
+                    let phase = close_burst(&mut *window.lock().await, start, from, on);
+                    bursts.lock().await.push(phase);
+                    active.store(true, Ordering::Relaxed);
+                    resume.notify_waiters();
+                    from = next;
+                }
+            })
+        });
+
+    // --concurrency-profile: a shaper moves `target` along the profile; workers numbered at
+    // or above it park until it rises past them again
+    let profile_gate = pool.profile.map(|p| {
+        let first = p.target(Duration::ZERO, pool.duration.unwrap_or_default());
+        (
+            Arc::new(AtomicU64::new(first as u64)),
+            Arc::new(tokio::sync::Notify::new()),
+            Arc::new(AtomicU64::new(0)),
+        )
+    });
+    let timeline = Arc::new(Mutex::new(Vec::new()));
+    let shaper =
+        pool.profile
+            .zip(profile_gate.clone())
+            .map(|(profile, (target, resume, parked))| {
+                let timeline = timeline.clone();
+                let clock = clock.clone();
+                let total = pool.duration.unwrap_or_default();
+                let workers = pool.concurrency as u64;
+                tokio::spawn(async move {
+                    let mut tick = start;
+                    let mut next_point = start + Duration::from_secs(1);
+                    loop {
+                        tick += PROFILE_TICK;
+                        clock.sleep_until(tick).await;
+                        let now = clock.now();
+                        if now >= next_point {
+                            // what the last second settled at, before this tick moves it
+                            timeline.lock().await.push(ConcurrencyPoint {
+                                t_sec: next_point.duration_since(start).as_secs_f64(),
+                                target: target.load(Ordering::Relaxed),
+                                active: workers.saturating_sub(parked.load(Ordering::Relaxed)),
+                            });
+                            next_point += Duration::from_secs(1);
+                        }
+                        let want = profile.target(now.duration_since(start), total) as u64;
+                        if target.swap(want, Ordering::Relaxed) < want {
+                            resume.notify_waiters();
+                        }
+                    }
+                })
+            });
+
+    // --max-inflight: arrivals the pacer skipped or held back, and a wakeup per completion
+    // for --on-overload block
+    let (dropped, blocked) = (Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)));
+    let freed = Arc::new(tokio::sync::Notify::new());
+    let notify_freed = pool.overload.is_some();
+
+    // --open-loop: a pacer hands out intended start times on a Poisson schedule; idle workers
+    // take the next one, so a slow server makes tickets queue instead of slowing the schedule.
+    // A --trace-file replay hands out the recorded start times, each with its request.
+    let tickets = if let Some(schedule) = pool.schedule.clone() {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<(Instant, Option<usize>)>();
+        let clock = clock.clone();
+        let pacer = tokio::spawn(async move {
+            for &(offset, step) in schedule.iter() {
+                let at = start + offset;
+                if deadline.is_some_and(|dl| at >= dl) {
+                    break;
+                }
+                clock.sleep_until(at).await;
+                if tx.send((at, Some(step))).is_err() {
+                    break;
+                }
+            }
+        });
+        Some((Arc::new(Mutex::new(rx)), pacer))
+    } else {
+        pool.arrival_rate.map(|rate| {
+            let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<(Instant, Option<usize>)>();
+            let mut rng = worker_rng(pool.seed, ARRIVAL_SEED_STREAM);
+            let limit = pool.limit;
+            let clock = clock.clone();
+            let overload = pool.overload;
+            let (completed, freed) = (completed.clone(), freed.clone());
+            let (dropped, blocked) = (dropped.clone(), blocked.clone());
+            let pacer = tokio::spawn(async move {
+                let mut at = start;
+                let mut issued = 0;
+                // handed to the workers; those not completed yet are in flight or queued
+                let mut dispatched = 0u64;
+                'arrivals: loop {
+                    // exponential gaps between arrivals
+                    let u: f64 = rng.gen();
+                    at += Duration::from_secs_f64(-(1.0 - u).ln() / rate);
+                    if deadline.is_some_and(|dl| at >= dl) || limit.is_some_and(|n| issued >= n) {
+                        break;
+                    }
+                    clock.sleep_until(at).await;
+                    issued += 1;
+                    // --max-inflight
+                    if let Some((cap, mode)) = overload {
+                        let busy = || dispatched.saturating_sub(completed.load(Ordering::Relaxed));
+                        if busy() >= cap {
+                            if mode == OnOverload::Drop {
+                                dropped.fetch_add(1, Ordering::Relaxed);
+                                continue;
+                            }
+                            blocked.fetch_add(1, Ordering::Relaxed);
+                            // workers leave a permit per completion, so none is missed
+                            while busy() >= cap {
+                                match deadline {
+                                    Some(dl) => tokio::select! {
+                                        _ = freed.notified() => {}
+                                        _ = clock.sleep_until(dl) => break 'arrivals,
+                                    },
+                                    None => freed.notified().await,
+                                }
+                            }
+                        }
+                    }
+                    if tx.send((at, None)).is_err() {
+                        break;
+                    }
+                    dispatched += 1;
+                }
+            });
+            (Arc::new(Mutex::new(rx)), pacer)
+        })
+    };
+    // --trace-file: how late each request started against its recorded offset
+    let dispatch_lag = match pool.schedule {
+        Some(_) => Some(Arc::new(std::sync::Mutex::new(pool.hist.build()?))),
+        None => None,
+    };
+    let in_flight = Arc::new(AtomicU64::new(0));
+    let max_in_flight = Arc::new(AtomicU64::new(0));
+    let recycles = Arc::new(AtomicU64::new(0));
+    // --cycles: full passes of each worker
+    let cycle_counts: Arc<Vec<AtomicU64>> = Arc::new(match pool.cycles {
+        Some(_) => (0..pool.concurrency).map(|_| AtomicU64::new(0)).collect(),
+        None => Vec::new(),
+    });
+    // --top-slow: each worker's own slowest, handed in as it stops
+    let top_slow = Arc::new(std::sync::Mutex::new(Vec::new()));
+    // --har-file: sampled requests in full, up to HAR_ENTRY_CAP (counted as they are sampled)
+    let har = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let har_claimed = Arc::new(AtomicUsize::new(0));
+    // --abort-if-initial-failures: failures among the first M completions
+    let early_failures = Arc::new(AtomicU64::new(0));
+
+    // micros since start of the last progress line, for its rps
+    let last_progress = Arc::new(AtomicU64::new(0));
+
+    // --rps-timeline: completions bucketed by the elapsed second they finished in
+    let per_second = Arc::new(std::sync::Mutex::new(Vec::<u64>::new()));
+
+    let mut handles = Vec::with_capacity(pool.concurrency);
+
+    let log = pool.log;
+    for worker in 0..pool.concurrency {
+        // --recycle-client-every: a worker's own client, so rebuilding it resets only its pools
+        let mut client = match &pool.recycle {
+            Some((_, fresh)) => fresh().context("Failed to build reqwest client")?,
+            None => client.clone(),
+        };
+        let recycle = pool.recycle.clone();
+        let recycles = recycles.clone();
+        let top_slow = top_slow.clone();
+        let top_slow_n = pool.top_slow;
+        let clock = clock.clone();
+        let har = pool
+            .har_rate
+            .map(|rate| (rate, har.clone(), har_claimed.clone()));
+        let cycles = pool.cycles.map(|k| (k, cycle_counts.clone()));
+        let specs = specs.clone();
+        let agg = agg.clone();
+        let sent = sent.clone();
+        let completed = completed.clone();
+        let freed = freed.clone();
+        let stop = stop.clone();
+        let limit = pool.limit;
+        let per_worker_limit = pool.per_worker_limit;
+        let seq_offset = pool.seq_offset;
+        let progress_every = pool.progress_every;
+        let progress_format = pool.progress_format;
+        let last_progress = last_progress.clone();
+        let checks = pool.checks.clone();
+        let capture_slow_over = pool.capture_slow_over_micros;
+        let window = pool.sample_interval.map(|_| window.clone());
+        let burst_window = pool.burst.map(|_| burst_window.clone());
+        let burst_gate = burst_gate.clone();
+        let profile_gate = profile_gate.clone();
+        let seed = pool.seed;
+        let min_interval = pool.min_interval;
+        let live = pool.live.clone();
+        let events = pool.events.clone();
+        let per_second = pool.rps_timeline.then(|| per_second.clone());
+        let respect_retry_after = pool.respect_retry_after;
+        let retry = pool.retry;
+        let measure_ttfb = pool.measure_ttfb;
+        let per_kb = pool.per_kb;
+        let server_timing = pool.server_timing;
+        let dedupe_errors = pool.dedupe_errors;
+        let drain_bodies = pool.drain_bodies;
+        let inject_trace = pool.inject_trace;
+        let grpc = pool.grpc;
+        let hard_timeout = pool.hard_timeout;
+        let initial_failures = pool.initial_failures;
+        let early_failures = early_failures.clone();
+        let tickets = tickets.as_ref().map(|(rx, _)| rx.clone());
+        let dispatch_lag = dispatch_lag.clone();
+        let in_flight = in_flight.clone();
+        let max_in_flight = max_in_flight.clone();
+        let select = pool.select;
+        let slots = pool.slots.clone();
+
+        handles.push(tokio::spawn(async move {
+            log.debug(format_args!("worker {worker} start"));
+            let mut rng = worker_rng(seed, worker as u64);
+            let mut first_request = true;
+            // round-robin workers start at different paths so short runs still cover them
+            let picks = slots.as_ref().map_or(specs.len(), |s| s.len());
+            let mut next_step = match select {
+                Some(Select::RoundRobin) => worker % picks,
+                _ => 0,
+            };
+            let mut next_send: Option<Instant> = None;
+            let mut own_sent = 0u64;
+            let mut own_done = 0u64;
+            // --scenario captures of this virtual user
+            let mut vars: BTreeMap<String, String> = BTreeMap::new();
+            let mut command_output = None;
+            let mut slowest = BinaryHeap::new();
+
+            loop {
+                if stop.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                if let Some(at) = next_send {
+                    if deadline.is_some_and(|dl| at >= dl) {
+                        break;
+                    }
+                    clock.sleep_until(at).await;
+                }
+
+                if let Some(dl) = deadline {
+                    if clock.now() >= dl {
+                        stop.store(true, Ordering::Relaxed);
+                        break;
+                    }
+                }
+
+                // --burst: sit out the off-phase (the wakeup is registered before the
+                // second look, so a flip in between is not missed)
+                if let Some((active, resume)) = &burst_gate {
+                    if !active.load(Ordering::Relaxed) {
+                        let resumed = resume.notified();
+                        if !active.load(Ordering::Relaxed) {
+                            match deadline {
+                                Some(dl) => tokio::select! {
+                                    _ = resumed => {}
+                                    _ = clock.sleep_until(dl) => {}
+                                },
+                                None => resumed.await,
+                            }
+                        }
+                        continue;
+                    }
+                }
+
+                // --concurrency-profile: park while above the target (registered before the
+                // second look, like --burst)
+                if let Some((target, resume, parked)) = &profile_gate {
+                    if worker as u64 >= target.load(Ordering::Relaxed) {
+                        let resumed = resume.notified();
+                        if worker as u64 >= target.load(Ordering::Relaxed) {
+                            parked.fetch_add(1, Ordering::Relaxed);
+                            match deadline {
+                                Some(dl) => tokio::select! {
+                                    _ = resumed => {}
+                                    _ = clock.sleep_until(dl) => {}
+                                },
+                                None => resumed.await,
+                            }
+                            parked.fetch_sub(1, Ordering::Relaxed);
+                        }
+                        continue;
+                    }
+                }
+
+                // --open-loop: wait for the next scheduled start; the pacer enforces the limit
+                let (intended, scheduled_step) = match &tickets {
+                    Some(rx) => match rx.lock().await.recv().await {
+                        Some((at, step)) => (Some(at), step),
+                        None => break,
+                    },
+                    None => (None, None),
+                };
+                if let (Some(at), Some(lag)) = (intended, &dispatch_lag) {
+                    let micros = clock.since(at).as_micros().min(u128::from(u64::MAX)) as u64;
+                    if let Ok(mut h) = lag.lock() {
+                        record_micros(&mut h, micros);
+                    }
+                }
+
+                if per_worker_limit.is_some_and(|n| own_sent >= n) {
+                    break;
+                }
+                own_sent += 1;
+
+                // exact limit: workers that draw a number past it stop (the counter
+                // overshoots by at most one per worker, the requests never do)
+                let seq = if intended.is_some() {
+                    sent.fetch_add(1, Ordering::Relaxed) + 1
+                } else if let Some(n) = limit {
+                    let cur = sent.fetch_add(1, Ordering::Relaxed);
+                    if cur >= n {
+                        stop.store(true, Ordering::Relaxed);
+                        break;
+                    }
+                    cur + 1
+                } else {
+                    sent.fetch_add(1, Ordering::Relaxed) + 1
+                };
+
+                let seq = seq_offset + seq;
+                if let Some((every, fresh)) = &recycle {
+                    if own_sent > 1 && (own_sent - 1).is_multiple_of(*every) {
+                        match fresh() {
+                            Ok(c) => {
+                                client = c;
+                                recycles.fetch_add(1, Ordering::Relaxed);
+                            }
+                            Err(e) => log.warn(format_args!(
+                                "worker {worker}: keeping the old client, rebuild failed: {e}"
+                            )),
+                        }
+                    }
+                }
+                next_send = min_interval.map(|iv| clock.now() + iv);
+                // each worker walks the steps in order, looping
+                let pick = match select {
+                    Some(Select::Random) => rng.gen_range(0..picks),
+                    // the shared request counter doubles as the pool index
+                    Some(Select::Sequential) => ((seq - 1) % picks as u64) as usize,
+                    _ => next_step,
+                };
+                next_step = (next_step + 1) % picks;
+                let step = match (scheduled_step, &slots) {
+                    (Some(step), _) => step,
+                    (None, Some(slots)) => slots[pick],
+                    (None, None) => pick,
+                };
+                let spec = &specs[step];
+                let req_url = spec.url_for(seq, &mut rng, &vars);
+
+                if let Some(pre) = spec.preflight(&client, &req_url) {
+                    let t = clock.now();
+                    let outcome = match pre.send().await {
+                        Ok(r) => {
+                            let code = r.status().as_u16();
+                            let _ = r.bytes().await;
+                            Ok(code)
+                        }
+                        Err(e) => Err(classify_reqwest_error(&e)),
+                    };
+                    let micros = clock.since(t).as_micros().min(u128::from(u64::MAX)) as u64;
+                    agg.lock().await[step].record_preflight(micros, outcome);
+                }
+
+                let etag = spec.current_etag();
+                // --har-sample-rate: every 1/rate-th request, so the sample is spread evenly;
+                // sampling stops once HAR_ENTRY_CAP entries are claimed
+                let mut har_entry = har
+                    .as_ref()
+                    .filter(|(rate, _, _)| {
+                        (seq as f64 * rate).floor() != ((seq - 1) as f64 * rate).floor()
+                    })
+                    .filter(|(_, _, claimed)| {
+                        claimed
+                            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+                                (n < HAR_ENTRY_CAP).then_some(n + 1)
+                            })
+                            .is_ok()
+                    })
+                    .map(|_| HarEntry {
+                        started_unix_ms: SystemTime::now()
+                            .duration_since(std::time::UNIX_EPOCH)
+                            .map_or(0, |d| d.as_millis() as u64),
+                        ..HarEntry::default()
+                    });
+                // W3C ids must not be all zeros
+                let trace_id = inject_trace.then(|| rng.gen_range(1..=u128::MAX));
+                let now_in_flight = in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+                max_in_flight.fetch_max(now_in_flight, Ordering::Relaxed);
+                let t0 = clock.now();
+                // --retries: the request is rebuilt for each attempt (bodies may be streams)
+                let mut retries = 0;
+                // --hard-timeout: the last attempt's deadline, which the body reads share
+                let mut hard = None;
+                let sent_once = loop {
+                    let built = spec
+                        .build(
+                            &client,
+                            req_url.clone(),
+                            seq,
+                            &mut rng,
+                            &vars,
+                            &mut command_output,
+                        )
+                        .await;
+                    let Ok((mut req, body_len)) = built else {
+                        break None;
+                    };
+                    if let Some(tag) = &etag {
+                        req = req.header(reqwest::header::IF_NONE_MATCH, tag);
+                    }
+                    if let Some(id) = trace_id {
+                        // a new span per attempt, so retries show up as siblings in the trace
+                        req =
+                            req.header("traceparent", traceparent(id, rng.gen_range(1..=u64::MAX)));
+                    }
+                    if let Some(entry) = &mut har_entry {
+                        let (c, built) = req.build_split();
+                        let built = match built {
+                            Ok(r) => r,
+                            Err(e) => break Some((Err(SendError::Reqwest(e)), body_len)),
+                        };
+                        entry.record_request(&built, body_len);
+                        req = RequestBuilder::from_parts(c, built);
+                    }
+                    hard = hard_timeout.map(HardDeadline::after);
+                    let resp = match until_deadline(hard, req.send()).await {
+                        Some(r) => r.map_err(SendError::Reqwest),
+                        None => Err(HardDeadline::error(hard)),
+                    };
+                    let failed = resp.as_ref().map_or(true, |r| r.status().is_server_error());
+                    let Some(policy) = retry.filter(|p| failed && retries < p.retries) else {
+                        break Some((resp, body_len));
+                    };
+                    let until = clock.now() + policy.delay(retries + 1, &mut rng);
+                    if deadline.is_some_and(|dl| until >= dl) {
+                        break Some((resp, body_len));
+                    }
+                    retries += 1;
+                    drop(resp);
+                    clock.sleep_until(until).await;
+                };
+                let Some((resp, body_len)) = sent_once else {
+                    // the body source could not be opened; nothing (more) went on the wire
+                    in_flight.fetch_sub(1, Ordering::Relaxed);
+                    agg.lock().await[step].record_error(NetErrKind::Other);
+                    if let Some(tx) = &events {
+                        let _ = tx
+                            .send(RequestOutcome {
+                                seq,
+                                step,
+                                timestamp: SystemTime::now(),
+                                latency_micros: 0,
+                                status: Err(NetErrKind::Other),
+                                trace_id: trace_id.map(|id| format!("{id:032x}")),
+                            })
+                            .await;
+                    }
+                    completed.fetch_add(1, Ordering::Relaxed);
+                    if notify_freed {
+                        freed.notify_one();
+                    }
+                    continue;
+                };
+                let mut micros = clock.since(t0).as_micros().min(u128::from(u64::MAX)) as u64;
+                let headers_micros = micros;
+                // --expect-body-size-range: the declared length, else what is read below
+                let declared_len = resp.as_ref().ok().and_then(|r| r.content_length());
+                let (mut read_len, mut read_whole) = (0u64, false);
+                // --validate-sample-rate: responses left out are neither read nor judged
+                let validate = resp.is_ok() && checks.sampled(&mut rng);
+
+                // --measure-ttfb: `micros` becomes the full download; the body is kept
+                // only when slow samples or captures will want it
+                let mut ttfb = None;
+                let mut prefetched = None;
+                let resp = match resp {
+                    Ok(mut r) if measure_ttfb => {
+                        ttfb = Some(micros);
+                        let keep = capture_slow_over.is_some()
+                            || !spec.captures.is_empty()
+                            || (validate && checks.reads_body())
+                            || har_entry.is_some();
+                        let mut buf = Vec::new();
+                        let read = async {
+                            while let Some(chunk) = next_chunk(&mut r, hard).await? {
+                                read_len += chunk.len() as u64;
+                                if keep {
+                                    buf.extend_from_slice(&chunk);
+                                }
+                            }
+                            Ok(())
+                        }
+                        .await;
+                        micros = clock.since(t0).as_micros().min(u128::from(u64::MAX)) as u64;
+                        read.map(|()| {
+                            read_whole = true;
+                            if keep {
+                                prefetched = Some(Bytes::from(buf));
+                            }
+                            r
+                        })
+                    }
+                    other => other,
+                };
+
+                // --grpc: the verdict is in the trailers, so the body is read up front and
+                // handed on like a --measure-ttfb prefetch
+                let mut grpc_status = None;
+                let resp = match resp {
+                    Ok(r) if grpc => 'grpc: {
+                        let read = match until_deadline(hard, read_grpc_response(r)).await {
+                            Some(read) => read.map_err(SendError::Body),
+                            None => Err(HardDeadline::error(hard)),
+                        };
+                        let (r, body, status) = match read {
+                            Ok(read) => read,
+                            Err(e) => {
+                                micros =
+                                    clock.since(t0).as_micros().min(u128::from(u64::MAX)) as u64;
+                                break 'grpc Err(e);
+                            }
+                        };
+                        grpc_status = status;
+                        prefetched = Some(body);
+                        micros = clock.since(t0).as_micros().min(u128::from(u64::MAX)) as u64;
+                        Ok(r)
+                    }
+                    other => other,
+                };
+
+                // --har-file: sampled responses are read to the end, for the size and the
+                // receive time; only the preview is kept unless a later stage wants the body
+                let resp = match resp {
+                    Ok(mut r) if har_entry.is_some() => 'har: {
+                        let mut preview = Vec::new();
+                        if prefetched.is_none() {
+                            let keep = capture_slow_over.is_some()
+                                || !spec.captures.is_empty()
+                                || (validate && checks.reads_body());
+                            let read = async {
+                                while let Some(chunk) = next_chunk(&mut r, hard).await? {
+                                    read_len += chunk.len() as u64;
+                                    let room = if keep {
+                                        chunk.len()
+                                    } else {
+                                        HAR_BODY_PREVIEW_BYTES.saturating_sub(preview.len())
+                                    };
+                                    preview.extend_from_slice(&chunk[..chunk.len().min(room)]);
+                                }
+                                Ok(())
+                            }
+                            .await;
+                            if let Err(e) = read {
+                                micros =
+                                    clock.since(t0).as_micros().min(u128::from(u64::MAX)) as u64;
+                                break 'har Err(e);
+                            }
+                            read_whole = true;
+                            if keep {
+                                prefetched = Some(Bytes::from(std::mem::take(&mut preview)));
+                            }
+                        }
+                        let (body, body_bytes) = match &prefetched {
+                            Some(b) => (&b[..], b.len() as u64),
+                            None => (&preview[..], read_len),
+                        };
+                        let done = clock.since(t0).as_micros().min(u128::from(u64::MAX)) as u64;
+                        if let Some(entry) = &mut har_entry {
+                            entry.record_response(&r, body, body_bytes);
+                            entry.wait_ms = headers_micros as f64 / 1000.0;
+                            entry.receive_ms = done.saturating_sub(headers_micros) as f64 / 1000.0;
+                        }
+                        Ok(r)
+                    }
+                    other => other,
+                };
+
+                let is_slow = capture_slow_over.is_some_and(|t| micros >= t);
+
+                // --success-body-* and --response-schema: judge the start of the body; it
+                // is kept whole (as a prefetch) only when a slow sample or capture reads it
+                let mut verdict = None;
+                let resp = match resp {
+                    Ok(mut r) if validate && checks.reads_body() => 'check: {
+                        let content_type = r
+                            .headers()
+                            .get(reqwest::header::CONTENT_TYPE)
+                            .and_then(|v| v.to_str().ok())
+                            .map(str::to_string);
+                        let body = match &prefetched {
+                            Some(b) => b.clone(),
+                            None => {
+                                let keep = is_slow || !spec.captures.is_empty();
+                                let mut buf = Vec::new();
+                                let read = async {
+                                    while keep || buf.len() <= checks.max_body_bytes {
+                                        match next_chunk(&mut r, hard).await? {
+                                            Some(chunk) => buf.extend_from_slice(&chunk),
+                                            None => break,
+                                        }
+                                    }
+                                    Ok(())
+                                }
+                                .await;
+                                if let Err(e) = read {
+                                    micros = clock.since(t0).as_micros().min(u128::from(u64::MAX))
+                                        as u64;
+                                    break 'check Err(e);
+                                }
+                                read_len = buf.len() as u64;
+                                let body = Bytes::from(buf);
+                                if keep {
+                                    prefetched = Some(body.clone());
+                                }
+                                body
+                            }
+                        };
+                        verdict = Some(checks.judge_body(content_type.as_deref(), &body));
+                        Ok(r)
+                    }
+                    other => other,
+                };
+
+                // drain bodies no slow sample or capture will read, so the connection can be
+                // reused; this happens after `micros`, so latency stays time to headers
+                let resp = match resp {
+                    Ok(mut r)
+                        if drain_bodies
+                            && prefetched.is_none()
+                            && !is_slow
+                            && spec.captures.is_empty() =>
+                    {
+                        let read = async {
+                            while let Some(chunk) = next_chunk(&mut r, hard).await? {
+                                read_len += chunk.len() as u64;
+                            }
+                            Ok(())
+                        }
+                        .await;
+                        read.map(|()| {
+                            read_whole = true;
+                            r
+                        })
+                    }
+                    other => other,
+                };
+
+                let mut guard = agg.lock().await;
+                let a = &mut guard[step];
+                if retries > 0 {
+                    *a.retried.entry(retries).or_insert(0) += 1;
+                }
+                if retry.is_some_and(|p| retries == p.retries)
+                    && resp.as_ref().map_or(true, |r| r.status().is_server_error())
+                {
+                    a.retries_exhausted += 1;
+                }
+                if std::mem::take(&mut first_request) {
+                    a.record_connection_setup(micros);
+                } else {
+                    a.record_latency(micros);
+                }
+                if let Some(t) = ttfb {
+                    a.record_ttfb(t);
+                }
+                if let Some(at) = intended {
+                    // from the scheduled start, so time spent queued for a free worker counts
+                    let late = clock.since(at).as_micros().min(u128::from(u64::MAX)) as u64;
+                    a.record_corrected(late);
+                } else if let Some(iv) = min_interval {
+                    // --max-rps-per-worker: a response slower than the pacing interval delayed
+                    // the requests scheduled behind it
+                    a.record_corrected_interval(micros, iv.as_micros() as u64);
+                }
+
+                // responses whose body is needed: slow ones and those with captures
+                let mut pending = None;
+                let mut backoff = None;
+                let status;
+                let is_error = match resp {
+                    Ok(r) => {
+                        a.bytes_sent += body_len * (u64::from(retries) + 1);
+                        let code = r.status().as_u16();
+                        status = Ok(code);
+                        a.record_status(code);
+                        match r.remote_addr() {
+                            Some(addr) if addr.is_ipv4() => a.remote_ipv4 += 1,
+                            Some(_) => a.remote_ipv6 += 1,
+                            None => {}
+                        }
+                        if closes_connection(r.headers()) {
+                            a.connection_close_responses += 1;
+                        }
+                        if validate {
+                            a.validated_responses += 1;
+                            if let Some(want) = spec.expect_status {
+                                a.status_checks.record(code == want);
+                            }
+                            checks.apply(&r, a);
+                        } else {
+                            a.validation_sampled_out += 1;
+                        }
+                        // bodies of unknown length that nobody read are not judged
+                        let size = match &prefetched {
+                            Some(b) => Some(b.len() as u64),
+                            None => declared_len.or(read_whole.then_some(read_len)),
+                        };
+                        if let (true, Some((min, max)), Some(n)) =
+                            (validate, checks.body_size, size)
+                        {
+                            a.body_size_checks.record((min..=max).contains(&n));
+                        }
+                        if let (true, Some(n)) = (per_kb, size) {
+                            a.record_latency_per_kb(micros, n);
+                        }
+                        if server_timing {
+                            for v in r.headers().get_all("server-timing") {
+                                let v = String::from_utf8_lossy(v.as_bytes());
+                                for (name, dur_ms) in parse_server_timing(&v) {
+                                    a.record_server_timing(&name, dur_ms);
+                                }
+                            }
+                        }
+                        if let Some(v) = verdict {
+                            v.record(a);
+                        }
+                        if respect_retry_after && (code == 429 || code == 503) {
+                            let wait = r
+                                .headers()
+                                .get(reqwest::header::RETRY_AFTER)
+                                .and_then(|v| v.to_str().ok())
+                                .and_then(parse_retry_after)
+                                .unwrap_or(DEFAULT_RETRY_AFTER);
+                            backoff = Some(wait);
+                        }
+                        if let Some(store) = &spec.etag {
+                            if code == 304 {
+                                a.cache_hits += 1;
+                            } else if etag.is_some() {
+                                a.cache_misses += 1;
+                            }
+                            let latest = r
+                                .headers()
+                                .get(reqwest::header::ETAG)
+                                .and_then(|v| v.to_str().ok());
+                            if latest.is_some() && latest != etag.as_deref() {
+                                if let Ok(mut e) = store.write() {
+                                    *e = latest.map(str::to_string);
+                                }
+                            }
+                        }
+                        if is_slow || !spec.captures.is_empty() {
+                            let sample =
+                                is_slow.then(|| SlowSample::from_response(seq, micros, &r));
+                            pending = Some((sample, r));
+                        }
+                        if grpc {
+                            match grpc_status {
+                                Some(c) => *a.grpc_status.entry(c).or_insert(0) += 1,
+                                None => a.grpc_status_missing += 1,
+                            }
+                            grpc_status != Some(0)
+                        } else {
+                            code >= 500
+                        }
+                    }
+                    Err(e) => {
+                        let kind = e.kind();
+                        status = Err(kind);
+                        a.record_error(kind);
+                        if dedupe_errors {
+                            a.record_error_example(kind, &e.message());
+                        }
+                        if is_slow {
+                            a.push_slow_sample(SlowSample::from_error(seq, micros, &e));
+                        }
+                        true
+                    }
+                };
+
+                drop(guard);
+                in_flight.fetch_sub(1, Ordering::Relaxed);
+
+                if let (Some(mut entry), Some((_, entries, _))) = (har_entry, &har) {
+                    if let Err(kind) = status {
+                        entry.error = Some(kind.as_str().to_string());
+                        entry.wait_ms = micros as f64 / 1000.0;
+                    }
+                    // the slot was claimed when the request was sampled
+                    entries
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .push(entry);
+                }
+
+                if let Some(n) = top_slow_n {
+                    let (code, error) = match status {
+                        Ok(code) => (Some(code), None),
+                        Err(kind) => (None, Some(kind.as_str().to_string())),
+                    };
+                    let req = TopSlowRequest {
+                        seq,
+                        latency_micros: micros,
+                        at_sec: clock.since(start).as_secs_f64(),
+                        status: code,
+                        error,
+                    };
+                    keep_slowest(&mut slowest, n, req);
+                }
+
+                if let Some(w) = &window {
+                    record_micros(&mut *w.lock().await, micros);
+                }
+                if let Some(w) = &burst_window {
+                    record_micros(&mut *w.lock().await, micros);
+                }
+                if let Some(l) = &live {
+                    l.record(micros, is_error);
+                }
+                if let Some(tx) = &events {
+                    // a dropped receiver just means nobody is listening any more
+                    let _ = tx
+                        .send(RequestOutcome {
+                            seq,
+                            step,
+                            timestamp: SystemTime::now(),
+                            latency_micros: micros,
+                            status,
+                            trace_id: trace_id.map(|id| format!("{id:032x}")),
+                        })
+                        .await;
+                }
+
+                // read the body outside the lock
+                match pending {
+                    Some((Some(mut sample), r))
+                        if spec.captures.is_empty() && prefetched.is_none() =>
+                    {
+                        let snippet = read_body_snippet(r, SLOW_BODY_SNIPPET_BYTES);
+                        sample.body_snippet =
+                            until_deadline(hard, snippet).await.unwrap_or_default();
+                        agg.lock().await[step].push_slow_sample(sample);
+                    }
+                    Some((sample, r)) => {
+                        let body = match prefetched {
+                            Some(b) => b,
+                            None => until_deadline(hard, r.bytes())
+                                .await
+                                .and_then(Result::ok)
+                                .unwrap_or_default(),
+                        };
+                        let failed = apply_captures(&spec.captures, &body, &mut vars);
+                        let mut guard = agg.lock().await;
+                        let a = &mut guard[step];
+                        if let Some(mut sample) = sample {
+                            let n = body.len().min(SLOW_BODY_SNIPPET_BYTES);
+                            sample.body_snippet = String::from_utf8_lossy(&body[..n]).into_owned();
+                            a.push_slow_sample(sample);
+                        }
+                        for c in failed {
+                            let n = a.capture_failures.entry(c.var.clone()).or_insert(0);
+                            *n += 1;
+                            if *n == 1 {
+                                log.warn(format_args!(
+                                    "step {:?}: capture {} path {} did not match the response body",
+                                    spec.name, c.var, c.path
+                                ));
+                            }
+                        }
+                    }
+                    None => {}
+                }
+
+                if let Some(buckets) = &per_second {
+                    let sec = clock.since(start).as_secs() as usize;
+                    if let Ok(mut b) = buckets.lock() {
+                        if b.len() <= sec {
+                            b.resize(sec + 1, 0);
+                        }
+                        b[sec] += 1;
+                    }
+                }
+
+                if let Some(wait) = backoff {
+                    // the pause never runs past the deadline and ends early on a stop
+                    let t = clock.now();
+                    let until = match (t.checked_add(wait), deadline) {
+                        (Some(at), Some(dl)) => at.min(dl),
+                        (Some(at), None) => at,
+                        (None, dl) => dl.unwrap_or(t),
+                    };
+                    while !stop.load(Ordering::Relaxed) && clock.now() < until {
+                        let nap = clock
+                            .now()
+                            .checked_add(RETRY_AFTER_STOP_POLL)
+                            .map_or(until, |at| at.min(until));
+                        clock.sleep_until(nap).await;
+                    }
+                    let mut guard = agg.lock().await;
+                    guard[step].backoffs += 1;
+                    guard[step].backoff_micros +=
+                        clock.since(t).as_micros().min(u128::from(u64::MAX)) as u64;
+                }
 
                 let done = completed.fetch_add(1, Ordering::Relaxed) + 1;
-                if progress_every > 0 && done % progress_every == 0 {
-                    eprintln!("progress: completed={done}");
+                if notify_freed {
+                    freed.notify_one();
+                }
+                own_done += 1;
+                if let Some((k, counts)) = &cycles {
+                    if own_done.is_multiple_of(picks as u64) {
+                        counts[worker].fetch_add(1, Ordering::Relaxed);
+                        if counts.iter().all(|c| c.load(Ordering::Relaxed) >= *k) {
+                            stop.store(true, Ordering::Relaxed);
+                        }
+                    }
+                }
+                if let Some((n, m)) = initial_failures {
+                    let unreachable = matches!(
+                        status,
+                        Err(NetErrKind::Dns | NetErrKind::Connect | NetErrKind::Timeout)
+                    );
+                    if done <= m
+                        && unreachable
+                        && early_failures.fetch_add(1, Ordering::Relaxed) + 1 > n
+                    {
+                        stop.store(true, Ordering::Relaxed);
+                    }
+                }
+                if progress_every > 0 && done.is_multiple_of(progress_every) {
+                    match progress_format {
+                        ProgressFormat::Text => {
+                            log.info(format_args!("progress: completed={done}"))
+                        }
+                        ProgressFormat::Json => {
+                            let now = clock.since(start);
+                            let now_micros = now.as_micros().min(u128::from(u64::MAX)) as u64;
+                            let prev = last_progress.swap(now_micros, Ordering::Relaxed);
+                            let window = now_micros.saturating_sub(prev).max(1) as f64 / 1e6;
+                            let line = serde_json::json!({
+                                "completed": done,
+                                "sent": sent.load(Ordering::Relaxed).min(sent_cap),
+                                "elapsed_sec": now.as_secs_f64(),
+                                "rps": progress_every as f64 / window,
+                            });
+                            log.info(format_args!("{line}"));
+                        }
+                    }
+                }
+            }
+            if !slowest.is_empty() {
+                let mut top = top_slow.lock().unwrap_or_else(|e| e.into_inner());
+                top.extend(slowest.into_iter().map(|Reverse(BySlowness(req))| req));
+            }
+            log.debug(format_args!("worker {worker} stop"));
+        }));
+    }
+
+    // With a duration the run must end even if a request never returns:
+    // past deadline + grace, outstanding workers are aborted and we report what we have.
+    let aborters: Vec<_> = handles.iter().map(|h| h.abort_handle()).collect();
+    let mut panics = Vec::new();
+    let join_all = join_workers(handles, &mut panics);
+    let mut aborted_workers = 0;
+    match pool.duration {
+        Some(d) => {
+            if tokio::time::timeout(d + pool.shutdown_grace, join_all)
+                .await
+                .is_err()
+            {
+                for a in &aborters {
+                    if !a.is_finished() {
+                        a.abort();
+                        aborted_workers += 1;
+                    }
+                }
+                stop.store(true, Ordering::Relaxed);
+                log.warn(format_args!(
+                    "aborted {aborted_workers} worker(s) stuck past the deadline"
+                ));
+            }
+        }
+        None => join_all.await,
+    }
+    for msg in &panics {
+        log.warn(format_args!("{msg}"));
+    }
+
+    if let Some((_, pacer)) = tickets {
+        pacer.abort();
+    }
+
+    let steps = agg.lock().await.clone();
+    let mut aggregates = Aggregates::with_hist(pool.hist)?;
+    for a in &steps {
+        aggregates.merge(a)?;
+    }
+
+    let samples = match sampler {
+        Some(task) => {
+            task.abort();
+            let _ = task.await;
+            let mut samples = std::mem::take(&mut *samples.lock().await);
+            // the last, partial window
+            let mut w = window.lock().await;
+            if !w.is_empty() {
+                let last = samples.last().map_or(start, |s: &IntervalSample| {
+                    start + Duration::from_secs_f64(s.t_sec)
+                });
+                samples.push(close_window(
+                    &mut w,
+                    start,
+                    last,
+                    clock.now(),
+                    pool.heat_bucket,
+                ));
+            }
+            samples
+        }
+        None => Vec::new(),
+    };
+
+    let bursts = match (coordinator, pool.burst) {
+        (Some(task), Some((on, off))) => {
+            task.abort();
+            let _ = task.await;
+            let mut bursts = std::mem::take(&mut *bursts.lock().await);
+            // the phase the run ended in (phases start on a fixed schedule)
+            let from = start + (on + off) * bursts.len() as u32;
+            let mut w = burst_window.lock().await;
+            if !w.is_empty() {
+                let on = on.min(clock.now().saturating_duration_since(from));
+                bursts.push(close_burst(&mut w, start, from, on));
+            }
+            bursts
+        }
+        _ => Vec::new(),
+    };
+
+    let concurrency_timeline = match shaper {
+        Some(task) => {
+            task.abort();
+            let _ = task.await;
+            std::mem::take(&mut *timeline.lock().await)
+        }
+        None => Vec::new(),
+    };
+
+    let per_second = std::mem::take(&mut *per_second.lock().unwrap_or_else(|e| e.into_inner()));
+    let stabilized = *stabilized.lock().await;
+    let early_failures = early_failures.load(Ordering::Relaxed);
+    let initial_failures = pool
+        .initial_failures
+        .and_then(|(n, _)| (early_failures > n).then_some(early_failures));
+
+    Ok(PoolOutcome {
+        elapsed_sec: clock.since(start).as_secs_f64(),
+        sent: sent.load(Ordering::Relaxed).min(sent_cap),
+        completed: completed.load(Ordering::Relaxed),
+        aggregates,
+        aborted_workers,
+        worker_panics: panics.len() as u64,
+        samples,
+        bursts,
+        concurrency_timeline,
+        steps,
+        per_second,
+        max_in_flight: max_in_flight.load(Ordering::Relaxed),
+        overload: pool.overload.map(|(cap, _)| OverloadStats {
+            max_inflight: cap,
+            dropped: dropped.load(Ordering::Relaxed),
+            blocked: blocked.load(Ordering::Relaxed),
+        }),
+        stabilized,
+        initial_failures,
+        dispatch_lag: dispatch_lag.map(|h| h.lock().unwrap_or_else(|e| e.into_inner()).clone()),
+        client_recycles: recycles.load(Ordering::Relaxed),
+        top_slow: {
+            let mut top = std::mem::take(&mut *top_slow.lock().unwrap_or_else(|e| e.into_inner()));
+            merge_top_slow(&mut top, [], pool.top_slow.unwrap_or(0));
+            top
+        },
+        har: {
+            let mut har = har.lock().unwrap_or_else(|e| e.into_inner());
+            std::mem::take(&mut *har)
+        },
+        cycles: cycle_counts
+            .iter()
+            .map(|c| c.load(Ordering::Relaxed))
+            .collect(),
+    })
+}
+
+/// Runs each stage back to back; returns the combined outcome plus per-stage results.
+async fn run_stages(
+    client: &reqwest::Client,
+    specs: &Arc<Vec<RequestSpec>>,
+    base: &Pool,
+    plan: &[Stage],
+) -> anyhow::Result<(PoolOutcome, Vec<StageResult>)> {
+    let mut total = PoolOutcome {
+        elapsed_sec: 0.0,
+        sent: 0,
+        completed: 0,
+        aggregates: Aggregates::with_hist(base.hist)?,
+        aborted_workers: 0,
+        worker_panics: 0,
+        samples: Vec::new(),
+        bursts: Vec::new(),
+        concurrency_timeline: Vec::new(),
+        steps: Vec::new(),
+        per_second: Vec::new(),
+        max_in_flight: 0,
+        overload: None,
+        stabilized: None,
+        initial_failures: None,
+        dispatch_lag: None,
+        client_recycles: 0,
+        top_slow: Vec::new(),
+        har: Vec::new(),
+        cycles: Vec::new(),
+    };
+    for _ in specs.iter() {
+        total.steps.push(Aggregates::with_hist(base.hist)?);
+    }
+    let mut stages = Vec::with_capacity(plan.len());
+
+    for (i, stage) in plan.iter().enumerate() {
+        base.log.info(format_args!(
+            "stage {}/{}: concurrency={} duration={}",
+            i + 1,
+            plan.len(),
+            stage.concurrency,
+            stage.duration_target
+        ));
+        let o = run_pool(
+            client,
+            specs,
+            Pool {
+                concurrency: stage.concurrency,
+                limit: None,
+                duration: Some(stage.duration),
+                seq_offset: total.sent,
+                // a different stream per stage, so stages don't repeat each other
+                seed: base.seed.map(|s| derive_seed(s, i as u64 + 1)),
+                // only the start of the run says anything about the endpoint
+                initial_failures: base.initial_failures.filter(|_| i == 0),
+                ..base.clone()
+            },
+        )
+        .await?;
+
+        let offset = total.elapsed_sec;
+        total
+            .samples
+            .extend(o.samples.iter().cloned().map(|mut sm| {
+                sm.t_sec += offset;
+                sm
+            }));
+        total.per_second.extend_from_slice(&o.per_second);
+        total.elapsed_sec += o.elapsed_sec;
+        total.sent += o.sent;
+        total.completed += o.completed;
+        total.aborted_workers += o.aborted_workers;
+        total.worker_panics += o.worker_panics;
+        total.max_in_flight = total.max_in_flight.max(o.max_in_flight);
+        total.client_recycles += o.client_recycles;
+        merge_top_slow(
+            &mut total.top_slow,
+            o.top_slow.iter().cloned().map(|mut t| {
+                t.at_sec += offset;
+                t
+            }),
+            base.top_slow.unwrap_or(0),
+        );
+        let room = HAR_ENTRY_CAP.saturating_sub(total.har.len());
+        total.har.extend(o.har.iter().take(room).cloned());
+        total.aggregates.merge(&o.aggregates)?;
+        for (mine, theirs) in total.steps.iter_mut().zip(&o.steps) {
+            mine.merge(theirs)?;
+        }
+
+        stages.push(StageResult {
+            concurrency: stage.concurrency,
+            duration_target: stage.duration_target.clone(),
+            elapsed_sec: o.elapsed_sec,
+            sent: o.sent,
+            completed: o.completed,
+            aggregates: o.aggregates,
+        });
+        if o.initial_failures.is_some() {
+            total.initial_failures = o.initial_failures;
+            break;
+        }
+    }
+
+    Ok((total, stages))
+}
+
+/// Everything needed to build one request; shared by all workers.
+#[derive(Debug)]
+struct RequestSpec {
+    /// scenario step label (empty for a plain run)
+    name: String,
+    method: Method,
+    url: Url,
+    /// in command-line order; a name may repeat
+    headers: Vec<(String, String)>,
+    query_params: Vec<(String, String)>,
+    body: BodySource,
+    /// headers of the OPTIONS preflight; `None` when not measuring preflights
+    preflight_headers: Option<Vec<(String, String)>>,
+    /// header that gets a fresh UUID per request
+    idempotency_header: Option<String>,
+    expect_status: Option<u16>,
+    captures: Vec<Capture>,
+    /// latest ETag seen, when --capture-etag is on
+    etag: Option<std::sync::RwLock<Option<String>>>,
+    signer: Option<Arc<HmacSign>>,
+}
+
+/// A --scenario capture: `var` is set from `path` in the JSON response body.
+#[derive(Debug)]
+struct Capture {
+    var: String,
+    path: String,
+    segs: Vec<JsonPathSeg>,
+}
+
+/// Stores every capture found in `body` into `vars`; returns the ones that did not match.
+fn apply_captures<'a>(
+    captures: &'a [Capture],
+    body: &[u8],
+    vars: &mut BTreeMap<String, String>,
+) -> Vec<&'a Capture> {
+    let json: Option<Value> = serde_json::from_slice(body).ok();
+    let mut failed = Vec::new();
+    for c in captures {
+        match json.as_ref().and_then(|j| lookup_json_path(j, &c.segs)) {
+            Some(Value::String(v)) => {
+                vars.insert(c.var.clone(), v.clone());
+            }
+            Some(v) => {
+                vars.insert(c.var.clone(), v.to_string());
+            }
+            None => failed.push(c),
+        }
+    }
+    failed
+}
+
+#[derive(Debug, Clone)]
+enum BodySource {
+    None,
+    /// serialized payload
+    Json(Vec<u8>),
+    /// generated --body-size payload, shared by all requests
+    Bytes(Bytes),
+    StreamFile(String),
+    /// --form-file/--form-field parts; a fresh form (files re-read) per request
+    Multipart(Vec<FormPart>),
+    /// --payload-command output
+    Command(Arc<PayloadCommand>),
+}
+
+#[derive(Debug)]
+struct PayloadCommand {
+    command: String,
+    /// reuse the first output for the rest of the worker's requests
+    cache: bool,
+    /// bounds how many copies run at once
+    slots: tokio::sync::Semaphore,
+}
+
+impl PayloadCommand {
+    /// Runs the command through the shell; a nonzero exit is an error carrying stderr.
+    async fn output(&self) -> std::io::Result<Bytes> {
+        let _slot = self.slots.acquire().await.map_err(std::io::Error::other)?;
+        #[cfg(unix)]
+        let mut cmd = tokio::process::Command::new("sh");
+        #[cfg(unix)]
+        cmd.arg("-c");
+        #[cfg(windows)]
+        let mut cmd = tokio::process::Command::new("cmd");
+        #[cfg(windows)]
+        cmd.arg("/C");
+        let out = cmd
+            .arg(&self.command)
+            .stdin(std::process::Stdio::null())
+            .kill_on_drop(true)
+            .output()
+            .await?;
+        if !out.status.success() {
+            return Err(std::io::Error::other(format!(
+                "{:?} exited with {}: {}",
+                self.command,
+                out.status,
+                String::from_utf8_lossy(&out.stderr).trim()
+            )));
+        }
+        Ok(Bytes::from(out.stdout))
+    }
+}
+
+#[derive(Debug, Clone)]
+enum FormPart {
+    Field { name: String, value: String },
+    File { name: String, path: String },
+}
+
+impl RequestSpec {
+    /// Target URL for request `seq`, with query params expanded.
+    fn url_for(&self, seq: u64, rng: &mut StdRng, captured: &BTreeMap<String, String>) -> Url {
+        if self.query_params.is_empty() {
+            self.url.clone()
+        } else {
+            let vars =
+                TemplateVars::draw(seq, rng, self.query_params.iter().map(|(_, v)| v.as_str()));
+            let mut u = self.url.clone();
+            {
+                let mut pairs = u.query_pairs_mut();
+                for (k, v) in &self.query_params {
+                    pairs.append_pair(k, &expand_vars(&expand_template(v, &vars), captured));
+                }
+            }
+            u
+        }
+    }
+
+    /// CORS preflight for `url` when --measure-preflight applies to this method.
+    fn preflight(&self, client: &reqwest::Client, url: &Url) -> Option<RequestBuilder> {
+        let headers = self.preflight_headers.as_ref()?;
+        let mut req = client.request(Method::OPTIONS, url.clone());
+        for (k, v) in headers {
+            req = req.header(k, v);
+        }
+        Some(req)
+    }
+
+    /// ETag to send as If-None-Match, if one was captured.
+    fn current_etag(&self) -> Option<String> {
+        self.etag.as_ref()?.read().ok()?.clone()
+    }
+
+    /// Sets Content-Type unless the user supplied one (charset, vendor type), which wins.
+    fn default_content_type(&self, req: RequestBuilder, content_type: &str) -> RequestBuilder {
+        if self
+            .headers
+            .iter()
+            .any(|(k, _)| k.eq_ignore_ascii_case("content-type"))
+        {
+            req
+        } else {
+            req.header(reqwest::header::CONTENT_TYPE, content_type)
+        }
+    }
+
+    /// Returns the request and the number of body bytes it will send.
+    /// `command_output` is the worker's --payload-command-cache slot.
+    async fn build(
+        &self,
+        client: &reqwest::Client,
+        url: Url,
+        seq: u64,
+        rng: &mut StdRng,
+        captured: &BTreeMap<String, String>,
+        command_output: &mut Option<Bytes>,
+    ) -> std::io::Result<(RequestBuilder, u64)> {
+        let signature = self.signer.as_ref().map(|signer| {
+            let body: &[u8] = match &self.body {
+                BodySource::Json(b) => b,
+                BodySource::Bytes(b) => b,
+                BodySource::None
+                | BodySource::StreamFile(_)
+                | BodySource::Multipart(_)
+                | BodySource::Command(_) => &[],
+            };
+            (
+                signer.header.as_str(),
+                sign_request(signer, &self.method, &url, body),
+            )
+        });
+        let mut req = client.request(self.method.clone(), url);
+        // header values are templated per request, after the captures (so a capture named
+        // like a placeholder keeps its value); nothing is drawn unless a placeholder is used
+        let values: Vec<String> = self
+            .headers
+            .iter()
+            .map(|(_, v)| expand_vars(v, captured))
+            .collect();
+        let vars = values
+            .iter()
+            .any(|v| uses_template(v))
+            .then(|| TemplateVars::draw(seq, rng, values.iter().map(String::as_str)));
+        for ((k, _), v) in self.headers.iter().zip(values) {
+            let v = match &vars {
+                Some(vars) => expand_template(&v, vars),
+                None => v,
+            };
+            req = req.header(k, v);
+        }
+        if let Some(name) = &self.idempotency_header {
+            req = req.header(name, new_uuid(rng).to_string());
+        }
+        if let Some((name, signature)) = signature {
+            req = req.header(name, signature);
+        }
+        let body_len = match &self.body {
+            BodySource::None => 0,
+            BodySource::Json(bytes) => {
+                req = self.default_content_type(req, "application/json");
+                req = req.body(bytes.clone());
+                bytes.len() as u64
+            }
+            BodySource::Bytes(bytes) => {
+                req = self.default_content_type(req, "application/octet-stream");
+                req = req.body(bytes.clone());
+                bytes.len() as u64
+            }
+            BodySource::StreamFile(path) => {
+                let file = tokio::fs::File::open(path).await?;
+                let len = file.metadata().await?.len();
+                req = req.body(reqwest::Body::wrap_stream(ReaderStream::new(file)));
+                len
+            }
+            // counts the part contents, not the multipart framing
+            BodySource::Multipart(parts) => {
+                let mut form = reqwest::multipart::Form::new();
+                let mut len = 0u64;
+                for part in parts {
+                    form = match part {
+                        FormPart::Field { name, value } => {
+                            len += value.len() as u64;
+                            form.text(name.clone(), value.clone())
+                        }
+                        FormPart::File { name, path } => {
+                            let data = tokio::fs::read(path).await?;
+                            len += data.len() as u64;
+                            let file_name = std::path::Path::new(path)
+                                .file_name()
+                                .map(|n| n.to_string_lossy().into_owned())
+                                .unwrap_or_default();
+                            form.part(
+                                name.clone(),
+                                reqwest::multipart::Part::bytes(data).file_name(file_name),
+                            )
+                        }
+                    };
+                }
+                req = req.multipart(form);
+                len
+            }
+            BodySource::Command(cmd) => {
+                let bytes = match command_output {
+                    Some(b) => b.clone(),
+                    None => {
+                        let b = cmd.output().await?;
+                        if cmd.cache {
+                            *command_output = Some(b.clone());
+                        }
+                        b
+                    }
+                };
+                req = self.default_content_type(req, "application/octet-stream");
+                req = req.body(bytes.clone());
+                bytes.len() as u64
+            }
+        };
+        Ok((req, body_len))
+    }
+}
+
+/// --once: sends the first spec's request by itself, outside the worker pool.
+async fn send_once(
+    client: &reqwest::Client,
+    specs: &[RequestSpec],
+    seed: Option<u64>,
+    hist: HistConfig,
+    latency_budget: Option<Duration>,
+) -> anyhow::Result<(PoolOutcome, OnceDetail)> {
+    let spec = &specs[0];
+    let mut rng = worker_rng(seed, 0);
+    let none = BTreeMap::new();
+    let url = spec.url_for(1, &mut rng, &none);
+    let (req, _) = spec
+        .build(client, url, 1, &mut rng, &none, &mut None)
+        .await
+        .context("Failed to build the request")?;
+    let req = req.build().context("Failed to build the request")?;
+    let mut detail = OnceDetail {
+        request_headers: req
+            .headers()
+            .iter()
+            .map(|(k, v)| {
+                let v = String::from_utf8_lossy(v.as_bytes());
+                (k.to_string(), redact_credential(k.as_str(), &v))
+            })
+            .collect(),
+        ..OnceDetail::default()
+    };
+
+    // --latency-budget: the same request again, after this one
+    let probe = latency_budget.map(|timeout| (req.try_clone(), timeout));
+
+    let mut agg = Aggregates::with_hist(hist)?;
+    let start = Instant::now();
+    match client.execute(req).await {
+        Ok(r) => {
+            detail.headers_ms = start.elapsed().as_secs_f64() * 1000.0;
+            detail.status = Some(r.status().as_u16());
+            detail.version = Some(format!("{:?}", r.version()));
+            detail.remote_addr = r.remote_addr().map(|a| a.to_string());
+            detail.response_headers = r
+                .headers()
+                .iter()
+                .map(|(k, v)| (k.to_string(), String::from_utf8_lossy(v.as_bytes()).into()))
+                .collect();
+            agg.record_status(r.status().as_u16());
+            if closes_connection(r.headers()) {
+                agg.connection_close_responses += 1;
+            }
+            let mut r = r;
+            let mut preview = Vec::new();
+            loop {
+                match r.chunk().await {
+                    Ok(Some(chunk)) => {
+                        detail.body_bytes += chunk.len() as u64;
+                        let room = ONCE_BODY_PREVIEW_BYTES.saturating_sub(preview.len());
+                        preview.extend_from_slice(&chunk[..chunk.len().min(room)]);
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        detail.error = Some(error_chain(&e));
+                        break;
+                    }
+                }
+            }
+            detail.body_preview = String::from_utf8_lossy(&preview).into_owned();
+            detail.body_ms = start.elapsed().as_secs_f64() * 1000.0 - detail.headers_ms;
+        }
+        Err(e) => {
+            agg.record_error(classify_reqwest_error(&e));
+            detail.error = Some(error_chain(&e));
+            detail.headers_ms = start.elapsed().as_secs_f64() * 1000.0;
+        }
+    }
+    let elapsed = start.elapsed();
+    agg.record_latency(elapsed.as_micros().min(u128::from(u64::MAX)) as u64);
+    detail.waterfall = match probe {
+        Some((Some(req), timeout)) => Some(measure_waterfall(&req, timeout).await),
+        Some((None, _)) => Some(Waterfall {
+            error: Some("a streamed request body cannot be sent twice".to_string()),
+            ..Waterfall::default()
+        }),
+        None => None,
+    };
+
+    let mut steps = Vec::with_capacity(specs.len());
+    for _ in specs {
+        steps.push(Aggregates::with_hist(hist)?);
+    }
+    steps[0].merge(&agg)?;
+    let outcome = PoolOutcome {
+        elapsed_sec: elapsed.as_secs_f64(),
+        sent: 1,
+        completed: 1,
+        aggregates: agg,
+        aborted_workers: 0,
+        worker_panics: 0,
+        samples: Vec::new(),
+        bursts: Vec::new(),
+        concurrency_timeline: Vec::new(),
+        steps,
+        per_second: Vec::new(),
+        max_in_flight: 1,
+        overload: None,
+        stabilized: None,
+        initial_failures: None,
+        dispatch_lag: None,
+        client_recycles: 0,
+        top_slow: Vec::new(),
+        har: Vec::new(),
+        cycles: Vec::new(),
+    };
+    Ok((outcome, detail))
+}
+
+/// An error and its causes, outermost first, joined with ": ".
+fn error_chain(e: &dyn std::error::Error) -> String {
+    let mut s = e.to_string();
+    let mut cause = e.source();
+    while let Some(c) = cause {
+        s.push_str(&format!(": {c}"));
+        cause = c.source();
+    }
+    s
+}
+
+/// Keeps the scheme of an Authorization-style value and hides the credential.
+fn redact_credential(name: &str, value: &str) -> String {
+    let secret = name.eq_ignore_ascii_case("authorization")
+        || name.eq_ignore_ascii_case("proxy-authorization");
+    match value.split_once(' ') {
+        Some((scheme, _)) if secret => format!("{scheme} <redacted>"),
+        _ if secret => "<redacted>".to_string(),
+        _ => value.to_string(),
+    }
+}
+
+/// Times `n` sequential HEAD requests to the first spec's URL on a client without
+/// connection pooling, so each sample includes DNS, TCP and TLS setup.
+async fn measure_handshakes(
+    client: &reqwest::Client,
+    spec: &RequestSpec,
+    n: u64,
+    hist: HistConfig,
+) -> anyhow::Result<Aggregates> {
+    let mut agg = Aggregates::with_hist(hist)?;
+    for _ in 0..n {
+        let mut req = client.head(spec.url.clone());
+        for (k, v) in &spec.headers {
+            req = req.header(k, v);
+        }
+        let t = Instant::now();
+        let resp = req.send().await;
+        agg.record_latency(t.elapsed().as_micros().min(u128::from(u64::MAX)) as u64);
+        match resp {
+            Ok(r) => agg.record_status(r.status().as_u16()),
+            Err(e) => agg.record_error(classify_reqwest_error(&e)),
+        }
+    }
+    Ok(agg)
+}
+
+/// Sends `req` as HTTP/1.1 on a connection of its own, timing each phase (--latency-budget).
+/// Only the request's own headers go out, not the client's defaults.
+pub async fn measure_waterfall(req: &reqwest::Request, timeout: Duration) -> Waterfall {
+    let mut w = Waterfall::default();
+    let phases = tokio::time::timeout(timeout, waterfall_phases(req, &mut w)).await;
+    if let Err(e) = phases.unwrap_or_else(|_| Err(format!("timed out after {timeout:?}"))) {
+        w.error = Some(e);
+    }
+    w
+}
+
+async fn waterfall_phases(req: &reqwest::Request, w: &mut Waterfall) -> Result<(), String> {
+    use tokio_rustls::rustls::{self, pki_types::ServerName};
+
+    let ms = |t: Instant| t.elapsed().as_secs_f64() * 1000.0;
+    let url = req.url();
+    let host = url.host_str().ok_or("the URL has no host")?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let port = url.port_or_known_default().unwrap_or(80);
+
+    let t = Instant::now();
+    let addr = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| format!("dns: {e}"))?
+        .next()
+        .ok_or_else(|| format!("dns: no address for {host}"))?;
+    w.dns_ms = Some(ms(t));
+
+    let t = Instant::now();
+    let tcp = tokio::net::TcpStream::connect(addr)
+        .await
+        .map_err(|e| format!("connect: {e}"))?;
+    w.connect_ms = Some(ms(t));
+
+    let mut head = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\n",
+        req.method(),
+        &url[url::Position::BeforePath..url::Position::AfterQuery],
+        &url[url::Position::BeforeHost..url::Position::AfterPort]
+    );
+    for (k, v) in req.headers() {
+        if k != reqwest::header::HOST && k != reqwest::header::CONNECTION {
+            head.push_str(&format!(
+                "{k}: {}\r\n",
+                String::from_utf8_lossy(v.as_bytes())
+            ));
+        }
+    }
+    let body = req.body().and_then(|b| b.as_bytes()).unwrap_or_default();
+    if !body.is_empty() && !req.headers().contains_key(reqwest::header::CONTENT_LENGTH) {
+        head.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    }
+    head.push_str("Connection: close\r\n\r\n");
+    let mut request = head.into_bytes();
+    request.extend_from_slice(body);
+
+    if url.scheme() != "https" {
+        return waterfall_exchange(tcp, &request, w).await;
+    }
+    let name = ServerName::try_from(host.to_string()).map_err(|e| format!("tls: {e}"))?;
+    let roots = rustls::RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let connector = tls_connector(roots, true).map_err(|e| format!("tls: {e}"))?;
+    let t = Instant::now();
+    let tls = connector
+        .connect(name, tcp)
+        .await
+        .map_err(|e| format!("tls: {e}"))?;
+    w.tls_ms = Some(ms(t));
+    waterfall_exchange(tls, &request, w).await
+}
+
+/// A rustls connector (ring, safe protocol versions) trusting `roots`, for the probes that
+/// open TLS connections of their own; `resume: false` turns session resumption off.
+fn tls_connector(
+    roots: tokio_rustls::rustls::RootCertStore,
+    resume: bool,
+) -> Result<tokio_rustls::TlsConnector, tokio_rustls::rustls::Error> {
+    use tokio_rustls::rustls::{self, client::Resumption};
+
+    let mut config = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()?
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    if !resume {
+        config.resumption = Resumption::disabled();
+    }
+    Ok(tokio_rustls::TlsConnector::from(Arc::new(config)))
+}
+
+/// Writes the request and reads the response until the server closes the connection.
+async fn waterfall_exchange<S>(
+    mut stream: S,
+    request: &[u8],
+    w: &mut Waterfall,
+) -> Result<(), String>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let t = Instant::now();
+    stream
+        .write_all(request)
+        .await
+        .map_err(|e| format!("send: {e}"))?;
+    let mut buf = vec![0u8; 16 * 1024];
+    let n = stream
+        .read(&mut buf)
+        .await
+        .map_err(|e| format!("response: {e}"))?;
+    if n == 0 {
+        return Err("response: the connection closed before any byte arrived".to_string());
+    }
+    w.ttfb_ms = Some(t.elapsed().as_secs_f64() * 1000.0);
+    w.response_bytes = n as u64;
+
+    let t = Instant::now();
+    loop {
+        match stream.read(&mut buf).await {
+            Ok(0) => break,
+            Ok(n) => w.response_bytes += n as u64,
+            // a close without TLS close_notify still ends the response
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(format!("transfer: {e}")),
+        }
+    }
+    w.transfer_ms = Some(t.elapsed().as_secs_f64() * 1000.0);
+    Ok(())
+}
+
+/// Handshake times of fresh TLS sessions against resumed ones, from --measure-tls-resumption.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlsResumption {
+    /// handshakes with session resumption turned off
+    pub full: Aggregates,
+    /// handshakes that offered the session of a priming connection
+    pub resumed: Aggregates,
+    /// how many of those the server actually resumed
+    pub resumed_count: u64,
+}
+
+/// Times `n` full TLS handshakes and `n` resumption attempts against `url`'s host, each
+/// on a new TCP connection; only the handshake itself is timed (DNS is resolved once,
+/// TCP connects are left out). Fails if the priming handshake does.
+pub async fn measure_tls_resumption(
+    url: &Url,
+    n: u64,
+    roots: tokio_rustls::rustls::RootCertStore,
+    timeout: Duration,
+    hist: HistConfig,
+) -> anyhow::Result<TlsResumption> {
+    use tokio_rustls::rustls::{self, pki_types::ServerName};
+
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow::anyhow!("--url has no host"))?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let port = url.port_or_known_default().unwrap_or(443);
+    let addr = tokio::net::lookup_host((host, port))
+        .await
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .ok_or_else(|| anyhow::anyhow!("Failed to resolve {host}"))?;
+    let name = ServerName::try_from(host.to_string())
+        .map_err(|e| anyhow::anyhow!("Invalid TLS server name {host}: {e}"))?;
+
+    let (full_tls, resumed_tls) = (
+        tls_connector(roots.clone(), false)?,
+        tls_connector(roots, true)?,
+    );
+
+    // one connection; Ok(resumed) once the handshake is through
+    let handshake = |tls: tokio_rustls::TlsConnector| {
+        let name = name.clone();
+        async move {
+            let tcp = tokio::time::timeout(timeout, tokio::net::TcpStream::connect(addr))
+                .await
+                .map_err(|_| (NetErrKind::Timeout, "connect timed out".to_string()))?
+                .map_err(|e| (NetErrKind::Connect, e.to_string()))?;
+            let t = Instant::now();
+            let done = tokio::time::timeout(timeout, tls.connect(name, tcp)).await;
+            let micros = t.elapsed().as_micros().min(u128::from(u64::MAX)) as u64;
+            let mut stream = done
+                .map_err(|_| (NetErrKind::Timeout, "handshake timed out".to_string()))?
+                .map_err(|e| (NetErrKind::Connect, e.to_string()))?;
+            let resumed =
+                stream.get_ref().1.handshake_kind() == Some(rustls::HandshakeKind::Resumed);
+            // TLS 1.3 session tickets arrive after the handshake: read a short exchange
+            let head = format!("HEAD / HTTP/1.1\r\nHost: {host}\r\nConnection: close\r\n\r\n");
+            let _ = tokio::time::timeout(timeout, async {
+                use tokio::io::{AsyncReadExt, AsyncWriteExt};
+                stream.write_all(head.as_bytes()).await?;
+                let mut sink = Vec::new();
+                stream.read_to_end(&mut sink).await
+            })
+            .await;
+            Ok::<_, (NetErrKind, String)>((micros, resumed))
+        }
+    };
+
+    let mut out = TlsResumption {
+        full: Aggregates::with_hist(hist)?,
+        resumed: Aggregates::with_hist(hist)?,
+        resumed_count: 0,
+    };
+    handshake(resumed_tls.clone())
+        .await
+        .map_err(|(_, e)| anyhow::anyhow!("TLS handshake with {host} failed: {e}"))?;
+    for (tls, agg) in [(&full_tls, &mut out.full), (&resumed_tls, &mut out.resumed)] {
+        for _ in 0..n {
+            match handshake(tls.clone()).await {
+                Ok((micros, resumed)) => {
+                    agg.record_latency(micros);
+                    if resumed {
+                        out.resumed_count += 1;
+                    }
+                }
+                Err((kind, _)) => agg.record_error(kind),
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Sends `total` requests across `conc` workers without recording anything.
+/// Bodies are drained so the warmed connections go back to the pool.
+async fn run_warmup(
+    client: &reqwest::Client,
+    specs: &Arc<Vec<RequestSpec>>,
+    conc: usize,
+    total: u64,
+    seed: Option<u64>,
+) -> u64 {
+    if total == 0 {
+        return 0;
+    }
+
+    let remaining = Arc::new(AtomicU64::new(total));
+    let done = Arc::new(AtomicU64::new(0));
+    let mut handles = Vec::with_capacity(conc);
+
+    for worker in 0..conc {
+        let client = client.clone();
+        let specs = specs.clone();
+        let remaining = remaining.clone();
+        let done = done.clone();
+
+        handles.push(tokio::spawn(async move {
+            let mut rng = worker_rng(seed, worker as u64);
+            let mut command_output = None;
+            while let Ok(left) =
+                remaining.fetch_update(Ordering::SeqCst, Ordering::Relaxed, |n| n.checked_sub(1))
+            {
+                let seq = total - left + 1;
+                let spec = &specs[(seq - 1) as usize % specs.len()];
+                let none = BTreeMap::new();
+                let url = spec.url_for(seq, &mut rng, &none);
+                let built = spec
+                    .build(&client, url, seq, &mut rng, &none, &mut command_output)
+                    .await;
+                if let Ok((req, _)) = built {
+                    if let Ok(r) = req.send().await {
+                        let _ = r.bytes().await;
+                    }
                 }
+                done.fetch_add(1, Ordering::Relaxed);
+            }
+        }));
+    }
+
+    for h in handles {
+        let _ = h.await;
+    }
+
+    done.load(Ordering::Relaxed)
+}
+
+/// Combines runs executed one after another (elapsed times add up).
+/// Returns `None` for an empty slice.
+pub fn combine_runs(runs: &[RunResult]) -> anyhow::Result<Option<RunResult>> {
+    let Some((first, rest)) = runs.split_first() else {
+        return Ok(None);
+    };
+    let mut combined = first.clone();
+    for (runs, r) in (1..).zip(rest) {
+        let offset = combined.elapsed_sec;
+        combined
+            .samples
+            .extend(r.samples.iter().cloned().map(|mut sm| {
+                sm.t_sec += offset;
+                sm
+            }));
+        combined
+            .bursts
+            .extend(r.bursts.iter().cloned().map(|mut b| {
+                b.start_sec += offset;
+                b
+            }));
+        combined
+            .concurrency_timeline
+            .extend(r.concurrency_timeline.iter().cloned().map(|mut p| {
+                p.t_sec += offset;
+                p
+            }));
+        combined.rps_timeline.extend_from_slice(&r.rps_timeline);
+        combined.elapsed_sec += r.elapsed_sec;
+        combined.sent += r.sent;
+        combined.completed += r.completed;
+        combined.warmup_discarded += r.warmup_discarded;
+        combined.aborted_workers += r.aborted_workers;
+        combined.worker_panics += r.worker_panics;
+        combined.max_in_flight = combined.max_in_flight.max(r.max_in_flight);
+        if let (Some(o), Some(ro)) = (&mut combined.overload, &r.overload) {
+            o.add(ro);
+        }
+        combined.client_recycles += r.client_recycles;
+        if let (Some(c), Some(rc)) = (&mut combined.cycles, &r.cycles) {
+            c.merge(rc, runs * first.concurrency, r.concurrency);
+        }
+        // the lists were cut at the same N; the longer one shows it
+        let n = combined.top_slow.len().max(r.top_slow.len());
+        merge_top_slow(
+            &mut combined.top_slow,
+            r.top_slow.iter().cloned().map(|mut t| {
+                t.at_sec += offset;
+                t
+            }),
+            n,
+        );
+        let room = HAR_ENTRY_CAP.saturating_sub(combined.har.len());
+        combined.har.extend(r.har.iter().take(room).cloned());
+        combined.aggregates.merge(&r.aggregates)?;
+        merge_cold(&mut combined.cold, &r.cold)?;
+        merge_steps(&mut combined.steps, &r.steps)?;
+        merge_steps(&mut combined.paths, &r.paths)?;
+        merge_steps(&mut combined.methods, &r.methods)?;
+        merge_steps(&mut combined.hosts, &r.hosts)?;
+    }
+    if combined.soak.is_some() {
+        combined.soak = soak_stats(&combined.samples);
+    }
+    if !rest.is_empty() {
+        // each run settled (or not) on its own
+        combined.stable = None;
+        combined.replay = None;
+    }
+    rescore_apdex(&mut combined);
+    Ok(Some(combined))
+}
+
+/// Combines results of instances that ran in parallel (e.g. on several machines):
+/// counts add up and elapsed is the longest instance, so throughput is over the shared window.
+pub fn merge_results(results: &[RunResult]) -> anyhow::Result<RunResult> {
+    let (first, rest) = results
+        .split_first()
+        .ok_or_else(|| anyhow::anyhow!("No results to merge"))?;
+    let mut merged = first.clone();
+    merged.stages.clear();
+    merged.knee = None;
+    // windows of different machines don't line up
+    merged.samples.clear();
+    merged.bursts.clear();
+    merged.concurrency_timeline.clear();
+    merged.heatmap_bucket_ms = None;
+    merged.rps_timeline.clear();
+    merged.soak = None;
+    merged.stable = None;
+    merged.replay = None;
+    for r in rest {
+        merged.concurrency += r.concurrency;
+        merged.runtime_threads += r.runtime_threads;
+        merged.elapsed_sec = merged.elapsed_sec.max(r.elapsed_sec);
+        merged.sent += r.sent;
+        merged.completed += r.completed;
+        merged.warmup_discarded += r.warmup_discarded;
+        merged.aborted_workers += r.aborted_workers;
+        merged.worker_panics += r.worker_panics;
+        merged.max_in_flight += r.max_in_flight;
+        merged.client_recycles += r.client_recycles;
+        if let (Some(c), Some(rc)) = (&mut merged.cycles, &r.cycles) {
+            c.merge(rc, merged.concurrency - r.concurrency, r.concurrency);
+        }
+        // the lists were cut at the same N; the longer one shows it
+        let n = merged.top_slow.len().max(r.top_slow.len());
+        merge_top_slow(&mut merged.top_slow, r.top_slow.iter().cloned(), n);
+        merged.arrival_rate = merged.arrival_rate.zip(r.arrival_rate).map(|(a, b)| a + b);
+        merged.target_rps = merged.target_rps.zip(r.target_rps).map(|(a, b)| a + b);
+        if let (Some(o), Some(ro)) = (&mut merged.overload, &r.overload) {
+            o.add(ro);
+            o.max_inflight += ro.max_inflight;
+        }
+        merged.aggregates.merge(&r.aggregates)?;
+        merge_cold(&mut merged.cold, &r.cold)?;
+        merge_steps(&mut merged.steps, &r.steps)?;
+        merge_steps(&mut merged.paths, &r.paths)?;
+        merge_steps(&mut merged.methods, &r.methods)?;
+        merge_steps(&mut merged.hosts, &r.hosts)?;
+        // keep only the tags all instances agree on
+        merged.tags.retain(|k, v| r.tags.get(k) == Some(v));
+        if let (Some(a), Some(b)) = (merged.requests_target, r.requests_target) {
+            merged.requests_target = Some(a + b);
+        }
+        if merged.requests_per_worker != r.requests_per_worker {
+            merged.requests_per_worker = None;
+        }
+    }
+    rescore_apdex(&mut merged);
+    Ok(merged)
+}
+
+/// Recomputes the Apdex score over merged latencies.
+fn rescore_apdex(r: &mut RunResult) {
+    if let Some(a) = &r.apdex {
+        let t = Duration::from_secs_f64(a.threshold_ms / 1000.0);
+        r.apdex = Some(apdex(&r.aggregates.latency_micros, t));
+    }
+}
+
+/// Adds --warm-compare priming results; dropped unless every run has them.
+fn merge_cold(mine: &mut Option<Aggregates>, theirs: &Option<Aggregates>) -> anyhow::Result<()> {
+    match (mine.as_mut(), theirs) {
+        (Some(a), Some(b)) => a.merge(b)?,
+        _ => *mine = None,
+    }
+    Ok(())
+}
+
+/// Adds per-step results step by step; results of different scenarios are dropped.
+fn merge_steps(mine: &mut Vec<StepResult>, theirs: &[StepResult]) -> anyhow::Result<()> {
+    let same = mine.len() == theirs.len()
+        && mine
+            .iter()
+            .zip(theirs)
+            .all(|(a, b)| a.name == b.name && a.url == b.url);
+    if !same {
+        mine.clear();
+        return Ok(());
+    }
+    for (a, b) in mine.iter_mut().zip(theirs) {
+        a.aggregates.merge(&b.aggregates)?;
+    }
+    Ok(())
+}
+
+pub fn load_scenario(path: &str) -> anyhow::Result<Scenario> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read --scenario {path}: {e}"))?;
+    // YAML is a superset of JSON, so this reads both
+    let sc: Scenario = serde_yaml::from_str(&text)
+        .map_err(|e| anyhow::anyhow!("Invalid --scenario {path}: {e}"))?;
+    if sc.steps.is_empty() {
+        return Err(anyhow::anyhow!("Invalid --scenario {path}: no steps"));
+    }
+    Ok(sc)
+}
+
+/// Parses a --method-body entry: "POST=create.json", or "GET" for no body.
+pub fn parse_method_body(s: &str) -> Option<(Method, Option<String>)> {
+    let (m, file) = match s.split_once('=') {
+        Some((m, file)) => (m, Some(file.trim()).filter(|f| !f.is_empty())),
+        None => (s, None),
+    };
+    Some((parse_http_method(m)?, file.map(str::to_string)))
+}
+
+/// Reads and compiles the --response-schema JSON Schema.
+fn load_response_schema(path: &str) -> anyhow::Result<jsonschema::Validator> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read --response-schema {path}: {e}"))?;
+    let schema: Value = serde_json::from_str(&text)
+        .map_err(|e| anyhow::anyhow!("Invalid JSON in --response-schema {path}: {e}"))?;
+    jsonschema::validator_for(&schema)
+        .map_err(|e| anyhow::anyhow!("Invalid --response-schema {path}: {e}"))
+}
+
+/// Reads --hosts-file (YAML or JSON): a list of `HostTarget`s with non-zero weights.
+pub fn load_hosts(path: &str) -> anyhow::Result<Vec<HostTarget>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read --hosts-file {path}: {e}"))?;
+    let hosts: Vec<HostTarget> = serde_yaml::from_str(&text)
+        .map_err(|e| anyhow::anyhow!("Invalid --hosts-file {path}: {e}"))?;
+    if hosts.is_empty() {
+        return Err(anyhow::anyhow!("Invalid --hosts-file {path}: no hosts"));
+    }
+    if let Some(i) = hosts.iter().position(|h| h.weight == 0) {
+        return Err(anyhow::anyhow!(
+            "Invalid --hosts-file {path}: entry {} has weight 0",
+            i + 1
+        ));
+    }
+    let weights: Vec<u32> = hosts.iter().map(|h| h.weight).collect();
+    let cycle: u64 = reduce_weights(&weights).iter().map(|&w| u64::from(w)).sum();
+    if cycle > MAX_WEIGHT_CYCLE {
+        return Err(anyhow::anyhow!(
+            "Invalid --hosts-file {path}: the weights add up to {cycle} after dividing by their common divisor; at most {MAX_WEIGHT_CYCLE} are supported"
+        ));
+    }
+    Ok(hosts)
+}
+
+/// Longest cycle `weighted_slots` builds: --hosts-file weights, divided by their gcd, may
+/// add up to at most this.
+pub const MAX_WEIGHT_CYCLE: u64 = 10_000;
+
+/// `weights` divided by their greatest common divisor.
+fn reduce_weights(weights: &[u32]) -> Vec<u32> {
+    fn gcd(a: u32, b: u32) -> u32 {
+        if b == 0 {
+            a
+        } else {
+            gcd(b, a % b)
+        }
+    }
+    let g = weights.iter().copied().fold(0, gcd).max(1);
+    weights.iter().map(|&w| w / g).collect()
+}
+
+/// Spreads entry indexes over one cycle in proportion to `weights` (reduced by their
+/// gcd), interleaved by smooth weighted round-robin: [2, 1] gives [0, 1, 0]. The cycle
+/// is as long as the reduced weights' sum; `load_hosts` caps it at `MAX_WEIGHT_CYCLE`.
+pub fn weighted_slots(weights: &[u32]) -> Vec<usize> {
+    let weights: Vec<i64> = reduce_weights(weights).into_iter().map(i64::from).collect();
+    let total: i64 = weights.iter().sum();
+    let mut current = vec![0i64; weights.len()];
+    let mut slots = Vec::with_capacity(total as usize);
+    for _ in 0..total {
+        for (c, w) in current.iter_mut().zip(&weights) {
+            *c += w;
+        }
+        let (best, _) = current
+            .iter()
+            .enumerate()
+            .max_by_key(|&(i, c)| (*c, Reverse(i)))
+            .expect("at least one weight");
+        current[best] -= total;
+        slots.push(best);
+    }
+    slots
+}
+
+/// Reads --paths-file: one path per line; blank lines and `#` comments are skipped.
+pub fn load_paths(path: &str) -> anyhow::Result<Vec<String>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read --paths-file {path}: {e}"))?;
+    let paths: Vec<String> = text
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(str::to_string)
+        .collect();
+    if paths.is_empty() {
+        return Err(anyhow::anyhow!("Invalid --paths-file {path}: no paths"));
+    }
+    Ok(paths)
+}
+
+/// Parses one --trace-file line: "offset_ms METHOD /path".
+pub fn parse_trace_line(line: &str) -> Option<TraceEntry> {
+    let mut parts = line.split_whitespace();
+    let offset_ms = parts.next()?.parse().ok()?;
+    let method = parse_http_method(parts.next()?)?;
+    let path = parts.next()?.to_string();
+    parts.next().is_none().then_some(TraceEntry {
+        offset_ms,
+        method,
+        path,
+    })
+}
+
+/// Reads --trace-file, skipping blank lines and # comments; entries come back in start order.
+pub fn load_trace(path: &str) -> anyhow::Result<Vec<TraceEntry>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read --trace-file {path}: {e}"))?;
+    let mut entries = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        entries.push(parse_trace_line(line).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Invalid --trace-file {path} line {}: {line} (expected \"offset_ms METHOD /path\")",
+                i + 1
+            )
+        })?);
+    }
+    if entries.is_empty() {
+        return Err(anyhow::anyhow!("Invalid --trace-file {path}: no requests"));
+    }
+    // captures are usually in order already; the sort is stable for equal offsets
+    entries.sort_by_key(|e| e.offset_ms);
+    Ok(entries)
+}
+
+/// Appends `path` to the base URL's path ("/api" + "users/1" -> "/api/users/1").
+pub fn append_path(base: &Url, path: &str) -> Url {
+    let mut url = base.clone();
+    let joined = format!(
+        "{}/{}",
+        base.path().trim_end_matches('/'),
+        path.trim_start_matches('/')
+    );
+    // the path may carry its own query string
+    match joined.split_once('?') {
+        Some((p, q)) => {
+            url.set_path(p);
+            url.set_query(Some(q));
+        }
+        None => url.set_path(&joined),
+    }
+    url
+}
+
+/// Characters the URL parser does not take as written: most get percent-encoded on
+/// the quiet, `\` turns into `/` (a stray `%` is handled apart: it is kept verbatim).
+const URL_UNSAFE: &str = " \t{}\\\"<>^`|";
+
+/// What looks wrong in a raw --url, one note per kind of problem; empty when clean.
+pub fn url_warnings(raw: &str) -> Vec<String> {
+    let trimmed = raw.trim();
+    let mut out = Vec::new();
+    if trimmed.len() != raw.len() {
+        out.push("leading or trailing whitespace".to_string());
+    }
+    if trimmed.contains(char::is_whitespace) {
+        out.push("unencoded whitespace".to_string());
+    }
+    if trimmed.contains(['{', '}']) {
+        out.push(
+            "unencoded braces (placeholders are only expanded in --query-param and --header)"
+                .to_string(),
+        );
+    }
+    if trimmed.contains('\\') {
+        out.push("a backslash (read as '/')".to_string());
+    }
+    let other: String = trimmed.chars().filter(|c| "\"<>^`|".contains(*c)).collect();
+    if !other.is_empty() {
+        out.push(format!("unencoded characters: {other}"));
+    }
+    if stray_percent(trimmed).next().is_some() {
+        out.push("a '%' not followed by two hex digits".to_string());
+    }
+    out
+}
+
+/// Byte offsets of each '%' that does not start a percent-escape.
+fn stray_percent(s: &str) -> impl Iterator<Item = usize> + '_ {
+    let b = s.as_bytes();
+    b.iter().enumerate().filter_map(move |(i, &c)| {
+        let escape =
+            b.len() > i + 2 && b[i + 1].is_ascii_hexdigit() && b[i + 2].is_ascii_hexdigit();
+        (c == b'%' && !escape).then_some(i)
+    })
+}
+
+/// --auto-encode-url: trims the URL and percent-encodes what `url_warnings` flags.
+pub fn encode_url(raw: &str) -> String {
+    let s = raw.trim();
+    let stray: Vec<usize> = stray_percent(s).collect();
+    let mut out = String::with_capacity(s.len());
+    for (i, c) in s.char_indices() {
+        if URL_UNSAFE.contains(c) || c.is_whitespace() || stray.contains(&i) {
+            let mut buf = [0u8; 4];
+            for b in c.encode_utf8(&mut buf).bytes() {
+                out.push_str(&format!("%{b:02X}"));
             }
-        }));
+        } else {
+            out.push(c);
+        }
     }
+    out
+}
 
-    for h in handles {
-        let _ = h.await;
+pub fn load_result(path: &str) -> anyhow::Result<RunResult> {
+    let bytes =
+        std::fs::read(path).map_err(|e| anyhow::anyhow!("Failed to read result {path}: {e}"))?;
+    serde_json::from_slice(&bytes)
+        .map_err(|e| anyhow::anyhow!("Invalid result JSON in {path}: {e}"))
+}
+
+/// Writes a rendered report for --report-file, creating missing parent directories.
+pub fn write_report_file(path: &str, text: &str) -> anyhow::Result<()> {
+    let p = std::path::Path::new(path);
+    if let Some(dir) = p.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(|e| {
+            anyhow::anyhow!("Failed to create directory for --report-file {path}: {e}")
+        })?;
     }
+    std::fs::write(p, text)
+        .map_err(|e| anyhow::anyhow!("Failed to write --report-file {path}: {e}"))
+}
+
+/// One line of an --append-results file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResultRecord {
+    /// seconds since the Unix epoch when the run finished
+    pub timestamp: u64,
+    pub result: RunResult,
+}
 
-    let aggregates = {
-        let guard = agg.lock().await;
-        guard.clone()
+/// Appends `r` as one JSON line to an --append-results file, creating it (and its directory).
+pub fn append_result(path: &str, r: &RunResult) -> anyhow::Result<()> {
+    use std::io::Write;
+    let p = std::path::Path::new(path);
+    if let Some(dir) = p.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(|e| {
+            anyhow::anyhow!("Failed to create directory for --append-results {path}: {e}")
+        })?;
+    }
+    let record = ResultRecord {
+        timestamp: SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        result: r.clone(),
     };
+    let mut line = serde_json::to_string(&record).context("Failed to serialize result")?;
+    line.push('\n');
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(p)
+        .and_then(|mut f| f.write_all(line.as_bytes()))
+        .map_err(|e| anyhow::anyhow!("Failed to write --append-results {path}: {e}"))
+}
 
-    Ok(RunResult {
-        url: args.url,
-        method: args.method,
-        concurrency: conc,
-        requests_target: args.requests,
-        duration_target: args.duration,
-        timeout: args.timeout,
-        elapsed_sec: start.elapsed().as_secs_f64(),
-        sent: sent.load(Ordering::Relaxed),
-        completed: completed.load(Ordering::Relaxed),
-        aggregates,
-    })
+/// Reads an --append-results file, oldest run first; blank lines are skipped.
+pub fn load_trend(path: &str) -> anyhow::Result<Vec<ResultRecord>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read --trend {path}: {e}"))?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(i, l)| {
+            serde_json::from_str(l)
+                .map_err(|e| anyhow::anyhow!("Invalid --trend {path} line {}: {e}", i + 1))
+        })
+        .collect()
 }
 
 /* ============================== REPORT ============================== */
 
+pub fn render_output(r: &RunResult, format: OutputFormat) -> anyhow::Result<String> {
+    match format {
+        OutputFormat::Text => Ok(render_report(r)),
+        OutputFormat::Json => render_json(r),
+        OutputFormat::Summary => Ok(render_report_summary(r)),
+    }
+}
+
+const ANSI_RED: &str = "31";
+const ANSI_GREEN: &str = "32";
+const ANSI_YELLOW: &str = "33";
+
+fn paint_ansi(line: &str, code: &str) -> String {
+    format!("\x1b[{code}m{line}\x1b[0m")
+}
+
+/// Colors a text report for a terminal: 2xx green, 4xx yellow, 5xx and network errors red,
+/// and, given a latency target, percentiles over 80% of it yellow and over it red.
+pub fn colorize_report(text: &str, target_ms: Option<f64>) -> String {
+    let mut out = String::with_capacity(text.len() + 256);
+    let mut section = "";
+    for line in text.split_inclusive('\n') {
+        let body = line.trim_end_matches('\n');
+        if !body.starts_with(' ') && body.ends_with(':') {
+            section = body.trim_end_matches(':');
+        }
+        // "key: value ..." with the value's leading number
+        let (key, num) = match body.split_once(": ") {
+            Some((k, v)) => (
+                k.trim(),
+                v.split([' ', '%'])
+                    .next()
+                    .and_then(|n| n.parse::<f64>().ok()),
+            ),
+            None => (body.trim(), None),
+        };
+        let code = match (section, key, num) {
+            (_, "error_rate", Some(n)) => Some(if n > 0.0 { ANSI_RED } else { ANSI_GREEN }),
+            ("status_class_counts", "2xx", Some(n)) if n > 0.0 => Some(ANSI_GREEN),
+            ("status_class_counts", "4xx", Some(n)) if n > 0.0 => Some(ANSI_YELLOW),
+            ("status_class_counts", "5xx", Some(n)) if n > 0.0 => Some(ANSI_RED),
+            ("status_exact_counts", k, _) => match k.as_bytes().first() {
+                Some(b'2') => Some(ANSI_GREEN),
+                Some(b'4') => Some(ANSI_YELLOW),
+                Some(b'5') => Some(ANSI_RED),
+                _ => None,
+            },
+            ("network_error_counts", _, Some(n)) if n > 0.0 => Some(ANSI_RED),
+            (sec, "p50" | "p90" | "p95" | "p99" | "max", Some(v)) if sec.ends_with("_ms") => {
+                target_ms.and_then(|t| {
+                    if v > t {
+                        Some(ANSI_RED)
+                    } else if v > 0.8 * t {
+                        Some(ANSI_YELLOW)
+                    } else {
+                        None
+                    }
+                })
+            }
+            _ => None,
+        };
+        match code {
+            Some(c) => {
+                out.push_str(&paint_ansi(body, c));
+                out.push_str(&line[body.len()..]);
+            }
+            None => out.push_str(line),
+        }
+    }
+    out
+}
+
+/// The --latency-budget phases, each bar placed where the phase fell in the total.
+pub fn render_waterfall(w: &Waterfall) -> String {
+    let total = w.total_ms();
+    let col = |ms: f64| {
+        if total > 0.0 {
+            ((ms / total * WATERFALL_WIDTH as f64).round() as usize).min(WATERFALL_WIDTH)
+        } else {
+            0
+        }
+    };
+    let mut s = String::from("latency_budget (the request again, on its own connection):\n");
+    let mut start = 0.0;
+    for (name, ms) in w.phases() {
+        let Some(ms) = ms else {
+            continue;
+        };
+        // every phase gets at least one mark, however short
+        let from = col(start).min(WATERFALL_WIDTH - 1);
+        let to = col(start + ms).max(from + 1);
+        s.push_str(&format!(
+            "  {name:<8} {ms:>10.3} ms {:>5.1}% |{}{}{}|\n",
+            if total > 0.0 { ms / total * 100.0 } else { 0.0 },
+            " ".repeat(from),
+            "#".repeat(to - from),
+            " ".repeat(WATERFALL_WIDTH - to)
+        ));
+        start += ms;
+    }
+    s.push_str(&format!(
+        "  {:<8} {total:>10.3} ms ({} response bytes)\n",
+        "total", w.response_bytes
+    ));
+    if let Some(e) = &w.error {
+        s.push_str(&format!("  error: {e}\n"));
+    }
+    s
+}
+
+/// The --once report: the one exchange in full.
+fn render_once(r: &RunResult, o: &OnceDetail) -> String {
+    let mut s = String::new();
+    s.push_str("== Single request ==\n");
+    s.push_str(&format!("{} {}\n", r.method, r.url));
+    for (k, v) in &o.request_headers {
+        s.push_str(&format!("> {k}: {v}\n"));
+    }
+    match o.status {
+        Some(code) => {
+            let reason = reqwest::StatusCode::from_u16(code)
+                .ok()
+                .and_then(|c| c.canonical_reason())
+                .unwrap_or("");
+            s.push_str(&format!(
+                "status: {code} {reason}\nversion: {}\n",
+                o.version.as_deref().unwrap_or("?")
+            ));
+            if let Some(addr) = &o.remote_addr {
+                s.push_str(&format!("remote_addr: {addr}\n"));
+            }
+            for (k, v) in &o.response_headers {
+                s.push_str(&format!("< {k}: {v}\n"));
+            }
+        }
+        None => s.push_str("status: none\n"),
+    }
+    if let Some(e) = &o.error {
+        s.push_str(&format!("error: {e}\n"));
+    }
+    s.push_str(&format!(
+        "timing: headers_ms={:.3} body_ms={:.3} total_ms={:.3}\n",
+        o.headers_ms,
+        o.body_ms,
+        o.headers_ms + o.body_ms
+    ));
+    if o.status.is_some() {
+        s.push_str(&format!("body: {} bytes\n", o.body_bytes));
+        if !o.body_preview.is_empty() {
+            s.push_str(&o.body_preview);
+            if !o.body_preview.ends_with('\n') {
+                s.push('\n');
+            }
+            if o.body_bytes > ONCE_BODY_PREVIEW_BYTES as u64 {
+                s.push_str(&format!(
+                    "... ({} more bytes)\n",
+                    o.body_bytes - ONCE_BODY_PREVIEW_BYTES as u64
+                ));
+            }
+        }
+    }
+    if let Some(w) = &o.waterfall {
+        s.push('\n');
+        s.push_str(&render_waterfall(w));
+    }
+    s
+}
+
+/// One grepable line: `url=... completed=N rps=X p50=.. p99=.. errors=.. 5xx=..` (ms).
+pub fn render_report_summary(r: &RunResult) -> String {
+    let h = &r.aggregates.latency_micros;
+    let rps = if r.elapsed_sec > 0.0 {
+        r.completed as f64 / r.elapsed_sec
+    } else {
+        0.0
+    };
+    format!(
+        "url={} completed={} rps={:.2} p50={:.3} p99={:.3} errors={} 5xx={}\n",
+        r.url,
+        r.completed,
+        rps,
+        h.value_at_quantile(0.50) as f64 / 1000.0,
+        h.value_at_quantile(0.99) as f64 / 1000.0,
+        r.aggregates.net_errors.total(),
+        r.aggregates.status_class.c5xx
+    )
+}
+
+/// p99 and throughput of the given runs (oldest first); `total` is the number in the file.
+pub fn render_trend(records: &[ResultRecord], total: usize) -> String {
+    let mut s = String::new();
+    s.push_str(&format!(
+        "== Trend (last {} of {total} runs) ==\n",
+        records.len()
+    ));
+    if records.is_empty() {
+        s.push_str("no runs recorded\n");
+        return s;
+    }
+    let p99: Vec<u64> = records
+        .iter()
+        .map(|rec| rec.result.aggregates.latency_micros.value_at_quantile(0.99))
+        .collect();
+    let rps: Vec<f64> = records
+        .iter()
+        .map(|rec| {
+            let r = &rec.result;
+            if r.elapsed_sec > 0.0 {
+                r.completed as f64 / r.elapsed_sec
+            } else {
+                0.0
+            }
+        })
+        .collect();
+    let ms = |v: u64| v as f64 / 1000.0;
+    s.push_str(&format!(
+        "p99_ms:         {}  min={:.3} max={:.3}\n",
+        sparkline(&p99),
+        ms(p99.iter().copied().min().unwrap_or(0)),
+        ms(p99.iter().copied().max().unwrap_or(0))
+    ));
+    let rps_bars: Vec<u64> = rps.iter().map(|r| r.round() as u64).collect();
+    s.push_str(&format!(
+        "throughput_rps: {}  min={:.2} max={:.2}\n",
+        sparkline(&rps_bars),
+        rps.iter().copied().fold(f64::INFINITY, f64::min),
+        rps.iter().copied().fold(0.0, f64::max)
+    ));
+    s.push_str("runs:\n");
+    for ((rec, p), r) in records.iter().zip(&p99).zip(&rps) {
+        let tags: Vec<String> = rec
+            .result
+            .tags
+            .iter()
+            .map(|(k, v)| format!(" {k}={v}"))
+            .collect();
+        s.push_str(&format!(
+            "  t={} p99_ms={:.3} throughput_rps={:.2}{}\n",
+            rec.timestamp,
+            ms(*p),
+            r,
+            tags.concat()
+        ));
+    }
+    s
+}
+
+/// Side-by-side table of --compare-methods results.
+pub fn render_method_comparison(results: &[RunResult]) -> String {
+    let mut s = String::new();
+    s.push_str("== Method comparison ==\n");
+    if let Some(r) = results.first() {
+        s.push_str(&format!("url: {}\n", r.url));
+    }
+    s.push_str(&format!(
+        "{:<8} {:>10} {:>15} {:>10} {:>10} {:>8}\n",
+        "method", "completed", "throughput_rps", "p50_ms", "p99_ms", "errors"
+    ));
+    for r in results {
+        let h = &r.aggregates.latency_micros;
+        let rps = if r.elapsed_sec > 0.0 {
+            r.completed as f64 / r.elapsed_sec
+        } else {
+            0.0
+        };
+        s.push_str(&format!(
+            "{:<8} {:>10} {:>15.2} {:>10.3} {:>10.3} {:>8}\n",
+            r.method,
+            r.completed,
+            rps,
+            h.value_at_quantile(0.50) as f64 / 1000.0,
+            h.value_at_quantile(0.99) as f64 / 1000.0,
+            r.aggregates.net_errors.total()
+        ));
+    }
+    s
+}
+
+pub fn render_json(r: &RunResult) -> anyhow::Result<String> {
+    let mut s = serde_json::to_string_pretty(r).context("Failed to serialize result")?;
+    s.push('\n');
+    Ok(s)
+}
+
+/// The --stream-ndjson summary record: the --output json result on one line, with
+/// "type":"summary".
+pub fn render_ndjson_summary(r: &RunResult) -> anyhow::Result<String> {
+    let mut v = serde_json::to_value(r).context("Failed to serialize result")?;
+    if let Some(fields) = v.as_object_mut() {
+        fields.insert("type".to_string(), "summary".into());
+    }
+    Ok(format!("{v}\n"))
+}
+
 pub fn render_report(r: &RunResult) -> String {
+    if let Some(once) = &r.once {
+        return render_once(r, once);
+    }
     let mut s = String::new();
     s.push_str("== Results ==\n");
     s.push_str(&format!("url: {}\n", r.url));
@@ -397,72 +8184,878 @@ pub fn render_report(r: &RunResult) -> String {
     if let Some(n) = r.requests_target {
         s.push_str(&format!("requests_target: {n}\n"));
     }
-    if let Some(d) = &r.duration_target {
-        s.push_str(&format!("duration_target: {d}\n"));
+    if let Some(n) = r.requests_per_worker {
+        s.push_str(&format!(
+            "requests_per_worker: {n} ({} workers)\n",
+            r.concurrency
+        ));
+    }
+    if let Some(d) = &r.duration_target {
+        s.push_str(&format!("duration_target: {d}\n"));
+    }
+    s.push_str(&format!("timeout: {}\n", r.timeout));
+    if let Some(path) = &r.unix_socket {
+        s.push_str(&format!("unix_socket: {path}\n"));
+    }
+    if !r.socket.is_empty() {
+        s.push_str(&format!("socket: {}\n", r.socket));
+    }
+    if r.runtime_threads > 0 {
+        s.push_str(&format!("runtime_threads: {}\n", r.runtime_threads));
+    }
+    if let Some(n) = r.recycle_client_every {
+        s.push_str(&format!(
+            "client_recycles: {} (every {n} requests per worker)\n",
+            r.client_recycles
+        ));
+    }
+    if r.aggregates.remote_ipv4 + r.aggregates.remote_ipv6 > 0 {
+        s.push_str(&format!(
+            "address_family: {} (responses over ipv4={} ipv6={})\n",
+            if r.address_family.is_empty() {
+                "auto"
+            } else {
+                &r.address_family
+            },
+            r.aggregates.remote_ipv4,
+            r.aggregates.remote_ipv6
+        ));
+    }
+    s.push_str(if r.bodies_drained {
+        "response_bodies: drained\n"
+    } else {
+        "response_bodies: dropped unread (--no-body-download)\n"
+    });
+    if r.expect_100_continue {
+        // hyper writes the body right after the headers, so a 100 (or an early
+        // final status) is never seen on its own
+        s.push_str(
+            "expect_100_continue: header sent; the client does not wait for or report the interim 100 response, only the final status\n",
+        );
+    }
+    if let Some(seed) = r.seed {
+        s.push_str(&format!("seed: {seed}\n"));
+    }
+    if !r.tags.is_empty() {
+        s.push_str("tags:\n");
+        for (k, v) in &r.tags {
+            s.push_str(&format!("  {k}: {v}\n"));
+        }
+    }
+    for (name, source) in &r.credential_sources {
+        s.push_str(&format!("{name}: set via {source}\n"));
+    }
+    s.push('\n');
+
+    s.push_str(&format!("elapsed_sec: {:.3}\n", r.elapsed_sec));
+    s.push_str(&format!("sent: {}\n", r.sent));
+    s.push_str(&format!("completed: {}\n", r.completed));
+    if r.aggregates.bytes_sent > 0 {
+        s.push_str(&format!("bytes_sent: {}\n", r.aggregates.bytes_sent));
+    }
+    if let Some(c) = &r.body_compression {
+        s.push_str(&format!(
+            "compress_body: {} original={} bytes compressed={} bytes ({:.1}% of original)\n",
+            c.encoding,
+            c.original_bytes,
+            c.compressed_bytes,
+            if c.original_bytes > 0 {
+                c.compressed_bytes as f64 * 100.0 / c.original_bytes as f64
+            } else {
+                0.0
+            }
+        ));
+    }
+    let closes = r.aggregates.connection_close_responses;
+    if closes > 0 {
+        let responses: u64 = r.aggregates.status_exact.values().sum();
+        s.push_str(&format!(
+            "connection_close_responses: {closes} ({:.1}% of responses; each made the next request on that worker open a new connection)\n",
+            closes as f64 * 100.0 / responses.max(1) as f64
+        ));
+    }
+    let conditional = r.aggregates.cache_hits + r.aggregates.cache_misses;
+    if conditional > 0 {
+        s.push_str(&format!(
+            "etag_cache: hits={} misses={} hit_rate={:.2}%\n",
+            r.aggregates.cache_hits,
+            r.aggregates.cache_misses,
+            r.aggregates.cache_hits as f64 / conditional as f64 * 100.0
+        ));
+    }
+    if r.aggregates.backoffs > 0 {
+        s.push_str(&format!(
+            "retry_after_backoff: count={} total_ms={:.3}\n",
+            r.aggregates.backoffs,
+            r.aggregates.backoff_micros as f64 / 1000.0
+        ));
+    }
+    if !r.aggregates.retried.is_empty() {
+        let by_count: Vec<String> = r
+            .aggregates
+            .retried
+            .iter()
+            .map(|(k, n)| format!("{k}x={n}"))
+            .collect();
+        s.push_str(&format!(
+            "retries: {} exhausted={}\n",
+            by_count.join(" "),
+            r.aggregates.retries_exhausted
+        ));
+    }
+    if r.warmup_discarded > 0 {
+        s.push_str(&format!("warmup_discarded: {}\n", r.warmup_discarded));
+    }
+    if r.aborted_workers > 0 {
+        s.push_str(&format!("aborted_workers: {}\n", r.aborted_workers));
+    }
+    if r.worker_panics > 0 {
+        s.push_str(&format!(
+            "worker_panics: {} (concurrency dropped for the rest of the run)\n",
+            r.worker_panics
+        ));
+    }
+    if let Some(rate) = r.arrival_rate {
+        s.push_str(&format!(
+            "open_loop: arrival_rate={rate} max_in_flight={} (cap {})\n",
+            r.max_in_flight, r.concurrency
+        ));
+    }
+    if let Some(o) = &r.overload {
+        s.push_str(&format!(
+            "overload: max_inflight={} dropped={} blocked={}\n",
+            o.max_inflight, o.dropped, o.blocked
+        ));
+    }
+    if let Some(c) = &r.cycles {
+        s.push_str(&format!(
+            "cycles: target={} min={} max={} avg={:.1} per worker\n",
+            c.target, c.min, c.max, c.avg
+        ));
+    }
+    if let Some(rp) = &r.replay {
+        s.push_str(&format!(
+            "replay: sent={} of {} scheduled, start lag p50_ms={:.3} p99_ms={:.3} max_ms={:.3} max_in_flight={} (cap {})\n",
+            rp.sent, rp.scheduled, rp.lag_p50_ms, rp.lag_p99_ms, rp.lag_max_ms, r.max_in_flight, r.concurrency
+        ));
+    }
+    if r.elapsed_sec > 0.0 {
+        s.push_str(&format!(
+            "throughput_rps: {:.2}\n",
+            (r.completed as f64) / r.elapsed_sec
+        ));
+        if let Some(target) = r.target_rps {
+            let achieved = r.completed as f64 / r.elapsed_sec;
+            s.push_str(&format!(
+                "rate: target_rps={target:.2} achieved_rps={achieved:.2} rate_attainment={:.1}%{}\n",
+                achieved / target * 100.0,
+                if achieved < target * RATE_ATTAINMENT_WARN {
+                    " (below target: likely limited downstream)"
+                } else {
+                    ""
+                }
+            ));
+        }
+    }
+    // shares of `completed`; 0% for an empty run
+    let pct = |n: u64| {
+        if r.completed > 0 {
+            n as f64 / r.completed as f64 * 100.0
+        } else {
+            0.0
+        }
+    };
+    let classes = &r.aggregates.status_class;
+    let errors = &r.aggregates.net_errors;
+    s.push_str(&format!(
+        "success_rate: {:.2}% (2xx and 3xx)\n",
+        pct(classes.c2xx + classes.c3xx)
+    ));
+    s.push_str(&format!(
+        "error_rate: {:.2}% (network errors and 5xx)\n",
+        pct(errors.total() + classes.c5xx)
+    ));
+    s.push('\n');
+
+    s.push_str("status_class_counts:\n");
+    for (label, n) in [
+        ("1xx", classes.c1xx),
+        ("2xx", classes.c2xx),
+        ("3xx", classes.c3xx),
+        ("4xx", classes.c4xx),
+        ("5xx", classes.c5xx),
+        ("other", classes.other),
+    ] {
+        s.push_str(&format!("  {label}: {n} ({:.1}%)\n", pct(n)));
+    }
+    s.push('\n');
+
+    s.push_str("status_exact_counts:\n");
+    for (code, count) in &r.aggregates.status_exact {
+        s.push_str(&format!("  {code}: {count}\n"));
+    }
+    s.push('\n');
+
+    if !r.aggregates.grpc_status.is_empty() || r.aggregates.grpc_status_missing > 0 {
+        s.push_str("grpc_status_counts:\n");
+        for (code, count) in &r.aggregates.grpc_status {
+            s.push_str(&format!("  {code}: {count}\n"));
+        }
+        if r.aggregates.grpc_status_missing > 0 {
+            s.push_str(&format!(
+                "  missing: {}\n",
+                r.aggregates.grpc_status_missing
+            ));
+        }
+        s.push('\n');
+    }
+
+    s.push_str("network_error_counts:\n");
+    for (label, n) in [
+        ("timeout", errors.timeout),
+        ("dns", errors.dns),
+        ("connect", errors.connect),
+        ("request", errors.request),
+        ("body", errors.body),
+        ("decode", errors.decode),
+        ("other", errors.other),
+        ("total", errors.total()),
+    ] {
+        s.push_str(&format!("  {label}: {n} ({:.1}%)\n", pct(n)));
+    }
+    s.push('\n');
+
+    if !r.aggregates.error_examples.is_empty() {
+        s.push_str("error_examples:\n");
+        for (kind, messages) in &r.aggregates.error_examples {
+            let mut top: Vec<_> = messages.iter().collect();
+            top.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+            s.push_str(&format!("  {kind}: {} distinct\n", messages.len()));
+            for (msg, n) in top.into_iter().take(ERROR_EXAMPLES_SHOWN) {
+                s.push_str(&format!("    {n}x {msg}\n"));
+            }
+        }
+        s.push('\n');
+    }
+
+    let (checked, left_out) = (
+        r.aggregates.validated_responses,
+        r.aggregates.validation_sampled_out,
+    );
+    if left_out > 0 {
+        s.push_str(&format!(
+            "validated_responses: {checked} of {} (--validate-sample-rate)\n\n",
+            checked + left_out
+        ));
+    }
+
+    let sc = &r.aggregates.status_checks;
+    if sc.pass + sc.fail > 0 {
+        s.push_str(&format!(
+            "status_checks: pass={} fail={}\n\n",
+            sc.pass, sc.fail
+        ));
+    }
+
+    let bc = &r.aggregates.body_checks;
+    if bc.pass + bc.fail > 0 {
+        s.push_str(&format!(
+            "body_checks: pass={} fail={}\n\n",
+            bc.pass, bc.fail
+        ));
+    }
+
+    let bs = &r.aggregates.body_size_checks;
+    if bs.pass + bs.fail > 0 {
+        s.push_str(&format!(
+            "body_size_checks: pass={} fail={}\n\n",
+            bs.pass, bs.fail
+        ));
+    }
+
+    let sc = &r.aggregates.schema_checks;
+    if sc.pass + sc.fail + r.aggregates.schema_skipped > 0 {
+        s.push_str(&format!(
+            "schema_checks: pass={} fail={} skipped={}\n",
+            sc.pass, sc.fail, r.aggregates.schema_skipped
+        ));
+        for v in &r.aggregates.schema_violations {
+            s.push_str(&format!("  violation: {v}\n"));
+        }
+        s.push('\n');
+    }
+
+    if !r.aggregates.capture_failures.is_empty() {
+        s.push_str("capture_failures:\n");
+        for (var, n) in &r.aggregates.capture_failures {
+            s.push_str(&format!("  {var}: {n}\n"));
+        }
+        s.push('\n');
+    }
+
+    if !r.aggregates.header_checks.is_empty() {
+        s.push_str("header_checks:\n");
+        for (name, c) in &r.aggregates.header_checks {
+            s.push_str(&format!("  {name}: pass={} fail={}\n", c.pass, c.fail));
+        }
+        s.push('\n');
+    }
+
+    push_latency_section(&mut s, "latency_ms", &r.aggregates.latency_micros);
+    let setup = &r.aggregates.connection_setup_micros;
+    if !setup.is_empty() && setup.len() < r.aggregates.latency_micros.len() {
+        push_latency_section(&mut s, "connection_setup_ms", setup);
+        push_latency_section(&mut s, "request_ms", &r.aggregates.steady_state_latency());
+    }
+    // with --measure-ttfb, latency_ms above runs to the end of the body
+    push_latency_section(&mut s, "ttfb_ms", &r.aggregates.ttfb_micros);
+    push_latency_section(
+        &mut s,
+        "corrected_latency_ms",
+        &r.aggregates.corrected_micros,
+    );
+    push_latency_section(
+        &mut s,
+        "latency_per_kb_ms",
+        &r.aggregates.latency_per_kb_micros,
+    );
+    if !r.aggregates.server_timing_micros.is_empty() {
+        // gap: what the client waited beyond the server's own figure (network, queueing)
+        let client_p50 = r.aggregates.latency_micros.value_at_quantile(0.50) as f64 / 1000.0;
+        s.push_str("server_timing_ms:\n");
+        for (name, h) in &r.aggregates.server_timing_micros {
+            let p50 = h.value_at_quantile(0.50) as f64 / 1000.0;
+            s.push_str(&format!(
+                "  {name}: count={} mean={:.3} p50={:.3} p99={:.3} max={:.3} gap_p50={:.3}\n",
+                h.len(),
+                h.mean() / 1000.0,
+                p50,
+                h.value_at_quantile(0.99) as f64 / 1000.0,
+                h.max() as f64 / 1000.0,
+                client_p50 - p50,
+            ));
+        }
+    }
+
+    if let Some(h) = &r.handshake {
+        s.push_str(&format!(
+            "\nhandshake (new connection per request): errors: {}\n",
+            h.net_errors.total()
+        ));
+        push_latency_section(&mut s, "handshake_ms", &h.latency_micros);
+    }
+
+    if let Some(t) = &r.tls_resumption {
+        let ms = |h: &Histogram<u64>, q: f64| h.value_at_quantile(q) as f64 / 1000.0;
+        s.push_str("\ntls_resumption (handshake only, new connection each):\n");
+        for (label, a) in [("full", &t.full), ("resumed", &t.resumed)] {
+            let h = &a.latency_micros;
+            s.push_str(&format!(
+                "  {label}: count={} p50_ms={:.3} p99_ms={:.3} mean_ms={:.3} errors={}\n",
+                h.len(),
+                ms(h, 0.50),
+                ms(h, 0.99),
+                h.mean() / 1000.0,
+                a.net_errors.total()
+            ));
+        }
+        let (f, r) = (&t.full.latency_micros, &t.resumed.latency_micros);
+        s.push_str(&format!(
+            "  resumed_by_server: {} of {}\n",
+            t.resumed_count,
+            r.len() + t.resumed.net_errors.total()
+        ));
+        if !f.is_empty() && !r.is_empty() {
+            let saving = ms(f, 0.50) - ms(r, 0.50);
+            let pct = if ms(f, 0.50) > 0.0 {
+                saving / ms(f, 0.50) * 100.0
+            } else {
+                0.0
+            };
+            s.push_str(&format!(
+                "  saving: p50_ms={saving:.3} ({pct:.1}% of a full handshake)\n"
+            ));
+        }
+    }
+
+    if let Some(cold) = &r.cold {
+        let (c, w) = (&cold.latency_micros, &r.aggregates.latency_micros);
+        let ms = |h: &Histogram<u64>, q: f64| h.value_at_quantile(q) as f64 / 1000.0;
+        // how many times faster the warm side is
+        let ratio = |q: f64| {
+            if w.is_empty() || ms(w, q) == 0.0 {
+                0.0
+            } else {
+                ms(c, q) / ms(w, q)
+            }
+        };
+        s.push_str("\nwarm_compare:\n");
+        for (label, h) in [("cold", c), ("warm", w)] {
+            s.push_str(&format!(
+                "  {label}: requests={} p50_ms={:.3} p99_ms={:.3}\n",
+                h.len(),
+                ms(h, 0.50),
+                ms(h, 0.99)
+            ));
+        }
+        s.push_str(&format!(
+            "  improvement: p50 x{:.2} p99 x{:.2}\n",
+            ratio(0.50),
+            ratio(0.99)
+        ));
     }
-    s.push_str(&format!("timeout: {}\n\n", r.timeout));
 
-    s.push_str(&format!("elapsed_sec: {:.3}\n", r.elapsed_sec));
-    s.push_str(&format!("sent: {}\n", r.sent));
-    s.push_str(&format!("completed: {}\n", r.completed));
-    if r.elapsed_sec > 0.0 {
+    if let Some(p) = &r.aggregates.preflight {
+        s.push_str("\npreflight:\n");
         s.push_str(&format!(
-            "throughput_rps: {:.2}\n",
-            (r.completed as f64) / r.elapsed_sec
+            "  2xx: {}  4xx: {}  5xx: {}  other: {}  errors: {}\n",
+            p.status_class.c2xx,
+            p.status_class.c4xx,
+            p.status_class.c5xx,
+            p.status_class.c1xx + p.status_class.c3xx + p.status_class.other,
+            p.net_errors.total()
         ));
+        push_latency_section(&mut s, "preflight_latency_ms", &p.latency_micros);
     }
-    s.push('\n');
 
-    s.push_str("status_class_counts:\n");
-    s.push_str(&format!("  1xx: {}\n", r.aggregates.status_class.c1xx));
-    s.push_str(&format!("  2xx: {}\n", r.aggregates.status_class.c2xx));
-    s.push_str(&format!("  3xx: {}\n", r.aggregates.status_class.c3xx));
-    s.push_str(&format!("  4xx: {}\n", r.aggregates.status_class.c4xx));
-    s.push_str(&format!("  5xx: {}\n", r.aggregates.status_class.c5xx));
-    s.push_str(&format!("  other: {}\n\n", r.aggregates.status_class.other));
+    if !r.aggregates.slow_samples.is_empty() {
+        s.push_str("\nslow_requests:\n");
+        for sample in &r.aggregates.slow_samples {
+            let outcome = match (sample.status, &sample.error) {
+                (Some(code), _) => format!("status={code}"),
+                (None, Some(e)) => format!("error={e}"),
+                (None, None) => "status=?".to_string(),
+            };
+            s.push_str(&format!(
+                "  - seq={} latency_ms={:.3} {outcome}\n",
+                sample.seq,
+                (sample.latency_micros as f64) / 1000.0
+            ));
+            for (k, v) in &sample.headers {
+                s.push_str(&format!("      {k}: {v}\n"));
+            }
+            if !sample.body_snippet.is_empty() {
+                s.push_str(&format!("      body: {:?}\n", sample.body_snippet));
+            }
+        }
+    }
 
-    s.push_str("status_exact_counts:\n");
-    for (code, count) in &r.aggregates.status_exact {
-        s.push_str(&format!("  {code}: {count}\n"));
+    if !r.top_slow.is_empty() {
+        s.push_str("\ntop_slow:\n");
+        for t in &r.top_slow {
+            let outcome = match (t.status, &t.error) {
+                (Some(code), _) => format!("status={code}"),
+                (None, Some(e)) => format!("error={e}"),
+                (None, None) => "status=?".to_string(),
+            };
+            s.push_str(&format!(
+                "  - seq={} latency_ms={:.3} at_sec={:.3} {outcome}\n",
+                t.seq,
+                t.latency_micros as f64 / 1000.0,
+                t.at_sec
+            ));
+        }
     }
-    s.push('\n');
 
-    s.push_str("network_error_counts:\n");
-    s.push_str(&format!("  timeout: {}\n", r.aggregates.net_errors.timeout));
-    s.push_str(&format!("  connect: {}\n", r.aggregates.net_errors.connect));
-    s.push_str(&format!("  request: {}\n", r.aggregates.net_errors.request));
-    s.push_str(&format!("  body: {}\n", r.aggregates.net_errors.body));
-    s.push_str(&format!("  decode: {}\n", r.aggregates.net_errors.decode));
-    s.push_str(&format!("  other: {}\n", r.aggregates.net_errors.other));
-    s.push_str(&format!("  total: {}\n\n", r.aggregates.net_errors.total()));
+    if !r.concurrency_timeline.is_empty() {
+        s.push_str("\nconcurrency_timeline (target/active each second):\n");
+        for p in &r.concurrency_timeline {
+            s.push_str(&format!(
+                "  t={:.0}s target={} active={}\n",
+                p.t_sec, p.target, p.active
+            ));
+        }
+    }
 
-    let h = &r.aggregates.latency_micros;
-    if h.len() > 0 {
-        s.push_str("latency_ms:\n");
-        s.push_str(&format!("  min: {:.3}\n", (h.min() as f64) / 1000.0));
+    if !r.bursts.is_empty() {
+        s.push_str("\nbursts:\n");
+        for (i, b) in r.bursts.iter().enumerate() {
+            let rps = if b.on_sec > 0.0 {
+                b.requests as f64 / b.on_sec
+            } else {
+                0.0
+            };
+            s.push_str(&format!(
+                "  burst {}: start={:.1}s on={:.1}s requests={} throughput_rps={:.2} p50_ms={:.3} p99_ms={:.3}\n",
+                i + 1,
+                b.start_sec,
+                b.on_sec,
+                b.requests,
+                rps,
+                b.p50_ms,
+                b.p99_ms
+            ));
+        }
+    }
+
+    if !r.samples.is_empty() {
+        s.push_str("\ntimeline:\n");
+        for sm in &r.samples {
+            s.push_str(&format!(
+                "  t={:.1}s requests={} throughput_rps={:.2} p50_ms={:.3} p99_ms={:.3}\n",
+                sm.t_sec,
+                sm.requests,
+                sm.rps(),
+                sm.p50_ms,
+                sm.p99_ms
+            ));
+        }
+    }
+
+    if !r.rps_timeline.is_empty() {
+        let t = &r.rps_timeline;
         s.push_str(&format!(
-            "  p50: {:.3}\n",
-            (h.value_at_quantile(0.50) as f64) / 1000.0
+            "\nrps_timeline: {}s min={} max={} (one bar per second)\n  {}\n",
+            t.len(),
+            t.iter().min().unwrap_or(&0),
+            t.iter().max().unwrap_or(&0),
+            sparkline(t)
         ));
+    }
+
+    if let Some(a) = &r.apdex {
         s.push_str(&format!(
-            "  p90: {:.3}\n",
-            (h.value_at_quantile(0.90) as f64) / 1000.0
+            "\napdex (T={}ms): {:.3} satisfied={} tolerating={} frustrated={}\n",
+            a.threshold_ms, a.score, a.satisfied, a.tolerating, a.frustrated
         ));
+    }
+
+    if let Some(st) = &r.stable {
+        match (st.after_requests, st.after_sec) {
+            (Some(n), Some(t)) => s.push_str(&format!(
+                "\nstable: p99 within {}% over {}s windows after {} requests ({:.1}s)\n",
+                st.tolerance_pct, st.window_sec, n, t
+            )),
+            _ => {
+                s.push_str(&format!(
+                    "\nstable: not stabilized (p99 kept moving more than {}% over {}s windows",
+                    st.tolerance_pct, st.window_sec
+                ));
+                match st.default_cap_sec {
+                    Some(cap) => s.push_str(&format!(
+                        "; stopped at the default cap of {STABLE_MAX_WINDOWS} windows, {cap}s)\n"
+                    )),
+                    None => s.push_str(")\n"),
+                }
+            }
+        }
+    }
+
+    if let Some(st) = &r.soak {
+        s.push_str("\nsoak:\n");
         s.push_str(&format!(
-            "  p95: {:.3}\n",
-            (h.value_at_quantile(0.95) as f64) / 1000.0
+            "  p99_drift_ms_per_hour: {:.3}\n",
+            st.p99_drift_ms_per_hour
         ));
+        s.push_str(&format!("  throughput_cv: {:.3}\n", st.throughput_cv));
+    }
+
+    if !r.steps.is_empty() {
+        s.push_str("\nsteps:\n");
+        for (i, st) in r.steps.iter().enumerate() {
+            let a = &st.aggregates;
+            let h = &a.latency_micros;
+            s.push_str(&format!(
+                "  step {}: {} ({} {}) requests={} 2xx={} errors={} status_check_fail={} p50_ms={:.3} p99_ms={:.3}\n",
+                i + 1,
+                st.name,
+                st.method,
+                st.url,
+                h.len(),
+                a.status_class.c2xx,
+                a.net_errors.total(),
+                a.status_checks.fail,
+                (h.value_at_quantile(0.50) as f64) / 1000.0,
+                (h.value_at_quantile(0.99) as f64) / 1000.0,
+            ));
+        }
+    }
+
+    for (title, pool) in [
+        ("paths", &r.paths),
+        ("methods", &r.methods),
+        ("hosts", &r.hosts),
+    ] {
+        if pool.is_empty() {
+            continue;
+        }
+        s.push_str(&format!("\n{title}:\n"));
+        for p in pool {
+            let a = &p.aggregates;
+            let h = &a.latency_micros;
+            s.push_str(&format!(
+                "  {} requests={} 2xx={} errors={} p50_ms={:.3} p99_ms={:.3}\n",
+                p.name,
+                h.len(),
+                a.status_class.c2xx,
+                a.net_errors.total(),
+                (h.value_at_quantile(0.50) as f64) / 1000.0,
+                (h.value_at_quantile(0.99) as f64) / 1000.0,
+            ));
+        }
+    }
+
+    if !r.stages.is_empty() {
+        s.push_str("\nstages:\n");
+        for (i, st) in r.stages.iter().enumerate() {
+            let rps = if st.elapsed_sec > 0.0 {
+                (st.completed as f64) / st.elapsed_sec
+            } else {
+                0.0
+            };
+            let h = &st.aggregates.latency_micros;
+            s.push_str(&format!(
+                "  stage {}: concurrency={} duration_target={} completed={} throughput_rps={:.2} 2xx={} errors={} p50_ms={:.3} p99_ms={:.3}\n",
+                i + 1,
+                st.concurrency,
+                st.duration_target,
+                st.completed,
+                rps,
+                st.aggregates.status_class.c2xx,
+                st.aggregates.net_errors.total(),
+                (h.value_at_quantile(0.50) as f64) / 1000.0,
+                (h.value_at_quantile(0.99) as f64) / 1000.0,
+            ));
+        }
+    }
+    if let Some(k) = &r.knee {
         s.push_str(&format!(
-            "  p99: {:.3}\n",
-            (h.value_at_quantile(0.99) as f64) / 1000.0
+            "knee: stage {} concurrency={} throughput_rps={:.2} mean_ms={:.3} ({:.2} rps per ms of latency, the best of all stages)\n",
+            k.stage, k.concurrency, k.rps, k.mean_ms, k.rps_per_ms
         ));
-        s.push_str(&format!("  max: {:.3}\n", (h.max() as f64) / 1000.0));
+        match &k.next {
+            Some(n) => s.push_str(&format!(
+                "  recommended concurrency: {}; at {} (stage {}) throughput changed {:+.1}% for {:+.1}% mean latency\n",
+                k.concurrency, n.concurrency, n.stage, n.rps_gain_pct, n.latency_gain_pct
+            )),
+            None => s.push_str(&format!(
+                "  recommended concurrency: {}; it was the highest tried, so the knee may lie further up\n",
+                k.concurrency
+            )),
+        }
     }
     s
 }
 
+fn push_latency_section(s: &mut String, title: &str, h: &Histogram<u64>) {
+    if h.is_empty() {
+        return;
+    }
+    s.push_str(&format!("{title}:\n"));
+    s.push_str(&format!("  count: {}\n", h.len()));
+    s.push_str(&format!("  mean: {:.3}\n", h.mean() / 1000.0));
+    s.push_str(&format!("  stddev: {:.3}\n", h.stdev() / 1000.0));
+    s.push_str(&format!("  min: {:.3}\n", (h.min() as f64) / 1000.0));
+    s.push_str(&format!(
+        "  p50: {:.3}\n",
+        (h.value_at_quantile(0.50) as f64) / 1000.0
+    ));
+    s.push_str(&format!(
+        "  p90: {:.3}\n",
+        (h.value_at_quantile(0.90) as f64) / 1000.0
+    ));
+    s.push_str(&format!(
+        "  p95: {:.3}\n",
+        (h.value_at_quantile(0.95) as f64) / 1000.0
+    ));
+    s.push_str(&format!(
+        "  p99: {:.3}\n",
+        (h.value_at_quantile(0.99) as f64) / 1000.0
+    ));
+    s.push_str(&format!("  max: {:.3}\n", (h.max() as f64) / 1000.0));
+}
+
 /* ============================== HELPERS ============================== */
 
+/// One block character per value, scaled to the largest.
+pub fn sparkline(values: &[u64]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max = values.iter().copied().max().unwrap_or(0).max(1);
+    values
+        .iter()
+        .map(|&v| BARS[(v.saturating_mul(7) / max) as usize])
+        .collect()
+}
+
+/// Apdex for target time `t`: satisfied <= t, tolerating <= 4t, frustrated otherwise.
+/// An empty histogram scores 0.
+pub fn apdex(h: &Histogram<u64>, t: Duration) -> Apdex {
+    let t_micros = t.as_micros().min(u128::from(u64::MAX)) as u64;
+    let (mut satisfied, mut tolerating, mut frustrated) = (0, 0, 0);
+    for v in h.iter_recorded() {
+        let micros = h.lowest_equivalent(v.value_iterated_to());
+        let n = v.count_at_value();
+        if micros <= t_micros {
+            satisfied += n;
+        } else if micros <= t_micros.saturating_mul(4) {
+            tolerating += n;
+        } else {
+            frustrated += n;
+        }
+    }
+    let total = satisfied + tolerating + frustrated;
+    let score = if total > 0 {
+        (satisfied as f64 + tolerating as f64 / 2.0) / total as f64
+    } else {
+        0.0
+    };
+    Apdex {
+        threshold_ms: t.as_secs_f64() * 1000.0,
+        satisfied,
+        tolerating,
+        frustrated,
+        score,
+    }
+}
+
+/// Soak metrics over a time series; `None` with fewer than two windows.
+pub fn soak_stats(samples: &[IntervalSample]) -> Option<SoakStats> {
+    if samples.len() < 2 {
+        return None;
+    }
+    let n = samples.len() as f64;
+
+    // least-squares slope of p99 (ms) against time (hours)
+    let xs: Vec<f64> = samples.iter().map(|s| s.t_sec / 3600.0).collect();
+    let mean_x = xs.iter().sum::<f64>() / n;
+    let mean_y = samples.iter().map(|s| s.p99_ms).sum::<f64>() / n;
+    let (mut cov, mut var) = (0.0, 0.0);
+    for (x, s) in xs.iter().zip(samples) {
+        cov += (x - mean_x) * (s.p99_ms - mean_y);
+        var += (x - mean_x) * (x - mean_x);
+    }
+    let slope = if var > 0.0 { cov / var } else { 0.0 };
+
+    let rates: Vec<f64> = samples.iter().map(IntervalSample::rps).collect();
+    let mean_rps = rates.iter().sum::<f64>() / n;
+    let sd_rps = (rates.iter().map(|r| (r - mean_rps).powi(2)).sum::<f64>() / n).sqrt();
+    let cv = if mean_rps > 0.0 {
+        sd_rps / mean_rps
+    } else {
+        0.0
+    };
+
+    Some(SoakStats {
+        p99_drift_ms_per_hour: slope,
+        throughput_cv: cv,
+    })
+}
+
+/// Throughput knee over --stages results: the stage with the highest rps per ms of
+/// mean latency. `None` unless at least two stages at different concurrencies completed
+/// requests.
+pub fn find_knee(stages: &[StageResult]) -> Option<Knee> {
+    let points: Vec<(usize, &StageResult, f64, f64)> = stages
+        .iter()
+        .enumerate()
+        .filter(|(_, st)| st.completed > 0 && st.elapsed_sec > 0.0)
+        .map(|(i, st)| {
+            let rps = st.completed as f64 / st.elapsed_sec;
+            let mean_ms = st.aggregates.latency_micros.mean() / 1000.0;
+            (i + 1, st, rps, mean_ms)
+        })
+        .filter(|&(_, _, _, mean_ms)| mean_ms > 0.0)
+        .collect();
+    let first = points.first()?;
+    if points
+        .iter()
+        .all(|p| p.1.concurrency == first.1.concurrency)
+    {
+        return None;
+    }
+    let &(stage, st, rps, mean_ms) = points
+        .iter()
+        .max_by(|a, b| (a.2 / a.3).total_cmp(&(b.2 / b.3)))?;
+    let next = points
+        .iter()
+        .skip_while(|p| p.0 != stage)
+        .find(|p| p.1.concurrency > st.concurrency)
+        .map(|&(i, next, next_rps, next_ms)| KneeStep {
+            stage: i,
+            concurrency: next.concurrency,
+            rps_gain_pct: (next_rps / rps - 1.0) * 100.0,
+            latency_gain_pct: (next_ms / mean_ms - 1.0) * 100.0,
+        });
+    Some(Knee {
+        stage,
+        concurrency: st.concurrency,
+        rps,
+        mean_ms,
+        rps_per_ms: rps / mean_ms,
+        next,
+    })
+}
+
+/// Seed stream of the warmup workers (stages use 1, 2, ...).
+const WARMUP_SEED_STREAM: u64 = u64::MAX;
+/// Seed stream of the random --body-size payload.
+const BODY_SEED_STREAM: u64 = u64::MAX - 1;
+/// Seed stream of the --open-loop arrival schedule.
+const ARRIVAL_SEED_STREAM: u64 = u64::MAX - 2;
+/// Seed stream of the --warm-compare priming pass.
+const COLD_SEED_STREAM: u64 = u64::MAX - 3;
+
+/// Derives an independent seed for `stream` from a root seed (splitmix64 finalizer).
+pub fn derive_seed(root: u64, stream: u64) -> u64 {
+    let mut z = root ^ stream.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// RNG of one worker: derived from `seed` when given, else from entropy.
+fn worker_rng(seed: Option<u64>, worker: u64) -> StdRng {
+    match seed {
+        Some(s) => StdRng::seed_from_u64(derive_seed(s, worker)),
+        None => StdRng::from_entropy(),
+    }
+}
+
+/// Random (v4) UUID drawn from `rng`.
+pub fn new_uuid(rng: &mut impl Rng) -> Uuid {
+    uuid::Builder::from_random_bytes(rng.gen()).into_uuid()
+}
+
+pub fn is_write_method(m: &Method) -> bool {
+    matches!(
+        *m,
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+    )
+}
+
+/// Headers a browser would put on the preflight for this request.
+fn preflight_headers(
+    method: &Method,
+    url: &Url,
+    headers: &[(String, String)],
+) -> Vec<(String, String)> {
+    let origin = headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("origin"))
+        .map(|(_, v)| v.clone())
+        .unwrap_or_else(|| url.origin().ascii_serialization());
+    let mut out = vec![
+        ("Origin".to_string(), origin),
+        (
+            "Access-Control-Request-Method".to_string(),
+            method.as_str().to_string(),
+        ),
+    ];
+    let requested: BTreeSet<String> = headers
+        .iter()
+        .map(|(k, _)| k.to_ascii_lowercase())
+        .filter(|k| k != "origin")
+        .collect();
+    if !requested.is_empty() {
+        let requested: Vec<String> = requested.into_iter().collect();
+        out.push((
+            "Access-Control-Request-Headers".to_string(),
+            requested.join(","),
+        ));
+    }
+    out
+}
+
 pub fn parse_http_method(s: &str) -> Option<Method> {
     match s.trim().to_ascii_uppercase().as_str() {
         "GET" => Some(Method::GET),
@@ -491,6 +9084,645 @@ pub fn parse_header(s: &str) -> Option<(String, String)> {
     Some((k.to_string(), v.to_string()))
 }
 
+/// Whether a response's Connection header says the server will close the connection.
+pub fn closes_connection(headers: &reqwest::header::HeaderMap) -> bool {
+    headers
+        .get_all(reqwest::header::CONNECTION)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|token| token.trim().eq_ignore_ascii_case("close"))
+}
+
+/// Checks that a header name and value are valid on the wire.
+pub fn check_header(name: &str, value: &str) -> Result<(), String> {
+    HeaderName::from_bytes(name.as_bytes()).map_err(|_| format!("invalid name {name:?}"))?;
+    reqwest::header::HeaderValue::from_str(value)
+        .map_err(|_| format!("invalid value for {name}"))?;
+    Ok(())
+}
+
+/// "on=10s,off=20s" -> (on, off); both phases must be longer than zero.
+pub fn parse_burst(s: &str) -> Option<(Duration, Duration)> {
+    let (mut on, mut off) = (None, None);
+    for part in s.split(',') {
+        let (k, v) = part.split_once('=')?;
+        let d = parse_duration(v).filter(|d| !d.is_zero())?;
+        match k.trim() {
+            "on" => on = Some(d),
+            "off" => off = Some(d),
+            _ => return None,
+        }
+    }
+    on.zip(off)
+}
+
+/// "sine:min=10,max=100,period=60s" or "linear:10..100" (also downwards); the peak must
+/// be at least 1.
+pub fn parse_concurrency_profile(s: &str) -> Option<ConcurrencyProfile> {
+    let (kind, rest) = s.split_once(':')?;
+    let profile = match kind.trim() {
+        "sine" => {
+            let (mut min, mut max, mut period) = (None, None, None);
+            for part in rest.split(',') {
+                let (k, v) = part.split_once('=')?;
+                match k.trim() {
+                    "min" => min = Some(v.trim().parse().ok()?),
+                    "max" => max = Some(v.trim().parse().ok()?),
+                    "period" => period = Some(parse_duration(v).filter(|d| !d.is_zero())?),
+                    _ => return None,
+                }
+            }
+            let (min, max, period) = (min?, max?, period?);
+            if min > max {
+                return None;
+            }
+            ConcurrencyProfile::Sine { min, max, period }
+        }
+        "linear" => {
+            let (from, to) = rest.split_once("..")?;
+            ConcurrencyProfile::Linear {
+                from: from.trim().parse().ok()?,
+                to: to.trim().parse().ok()?,
+            }
+        }
+        _ => return None,
+    };
+    (profile.peak() >= 1).then_some(profile)
+}
+
+/// "10:30s,50:1m" -> stages; concurrency must be >= 1
+pub fn parse_stages(s: &str) -> Option<Vec<Stage>> {
+    let mut out = Vec::new();
+    for part in s.split(',') {
+        let (c, d) = part.split_once(':')?;
+        let concurrency = c.trim().parse::<usize>().ok().filter(|c| *c > 0)?;
+        let duration = parse_duration(d)?;
+        out.push(Stage {
+            concurrency,
+            duration,
+            duration_target: d.trim().to_string(),
+        });
+    }
+    Some(out)
+}
+
+/// "Name: Value" asserts an exact value; "Name" (or "Name:") only asserts presence
+pub fn parse_expect_header(s: &str) -> Option<(String, Option<String>)> {
+    if !s.contains(':') {
+        let k = s.trim();
+        return (!k.is_empty()).then(|| (k.to_string(), None));
+    }
+    let (k, v) = parse_header(s)?;
+    Some((k, (!v.is_empty()).then_some(v)))
+}
+
+/// "key=value"; the value may be empty and may contain further '=' characters
+pub fn parse_query_param(s: &str) -> Option<(String, String)> {
+    let (k, v) = s.split_once('=')?;
+    let k = k.trim();
+    if k.is_empty() {
+        return None;
+    }
+    Some((k.to_string(), v.trim().to_string()))
+}
+
+/// gRPC length-prefixed message: uncompressed flag, big-endian length, payload.
+pub fn grpc_frame(msg: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(5 + msg.len());
+    out.push(0);
+    out.extend_from_slice(&(msg.len() as u32).to_be_bytes());
+    out.extend_from_slice(msg);
+    out
+}
+
+/// grpc-status from trailers or, for trailers-only replies, the headers.
+fn grpc_status_of(headers: &reqwest::header::HeaderMap) -> Option<u32> {
+    headers
+        .get("grpc-status")?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Reads a --grpc response to the end for its trailers; returns it rebuilt around the body,
+/// or the error that cut the body short.
+async fn read_grpc_response(
+    r: reqwest::Response,
+) -> Result<(reqwest::Response, Bytes, Option<u32>), reqwest::Error> {
+    let in_headers = grpc_status_of(r.headers());
+    let (parts, body) = http::Response::<reqwest::Body>::from(r).into_parts();
+    let collected = body.collect().await?;
+    let trailers = collected.trailers().cloned();
+    let bytes = collected.to_bytes();
+    let status = trailers.as_ref().and_then(grpc_status_of).or(in_headers);
+    let r = reqwest::Response::from(http::Response::from_parts(parts, bytes.clone()));
+    Ok((r, bytes, status))
+}
+
+/// Parses a --form-file value "field=@path".
+pub fn parse_form_file(s: &str) -> Option<(String, String)> {
+    let (name, path) = s.split_once('=')?;
+    let name = name.trim();
+    let path = path.trim().strip_prefix('@')?;
+    if name.is_empty() || path.is_empty() {
+        return None;
+    }
+    Some((name.to_string(), path.to_string()))
+}
+
+/// The --har-file document (HAR 1.2). Failed requests get status 0 and an `_error`.
+pub fn render_har(entries: &[&HarEntry]) -> String {
+    let pairs = |list: &[(String, String)]| -> Vec<Value> {
+        list.iter()
+            .map(|(name, value)| serde_json::json!({ "name": name, "value": value }))
+            .collect()
+    };
+    let entries: Vec<Value> = entries
+        .iter()
+        .map(|e| {
+            let query: Vec<(String, String)> = Url::parse(&e.url)
+                .map(|u| u.query_pairs().into_owned().collect())
+                .unwrap_or_default();
+            let mime = e
+                .response_headers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case("content-type"))
+                .map_or("", |(_, v)| v.as_str());
+            let status_text = e
+                .status
+                .and_then(|c| reqwest::StatusCode::from_u16(c).ok())
+                .and_then(|c| c.canonical_reason())
+                .unwrap_or("");
+            let mut response = serde_json::json!({
+                "status": e.status.unwrap_or(0),
+                "statusText": status_text,
+                "httpVersion": e.http_version,
+                "cookies": [],
+                "headers": pairs(&e.response_headers),
+                "content": {
+                    "size": e.body_bytes,
+                    "mimeType": mime,
+                    "text": e.body_preview,
+                },
+                "redirectURL": "",
+                "headersSize": -1,
+                "bodySize": e.body_bytes,
+            });
+            if let Some(err) = &e.error {
+                response["_error"] = err.as_str().into();
+            }
+            serde_json::json!({
+                "startedDateTime": iso8601_millis(e.started_unix_ms),
+                "time": e.wait_ms + e.receive_ms,
+                "request": {
+                    "method": e.method,
+                    "url": e.url,
+                    "httpVersion": e.http_version,
+                    "cookies": [],
+                    "headers": pairs(&e.request_headers),
+                    "queryString": pairs(&query),
+                    "headersSize": -1,
+                    "bodySize": e.request_body_bytes,
+                },
+                "response": response,
+                "cache": {},
+                "timings": {
+                    "send": 0.0,
+                    "wait": e.wait_ms,
+                    "receive": e.receive_ms,
+                },
+            })
+        })
+        .collect();
+    let doc = serde_json::json!({
+        "log": {
+            "version": "1.2",
+            "creator": { "name": "endpoint_tester", "version": env!("CARGO_PKG_VERSION") },
+            "entries": entries,
+        }
+    });
+    serde_json::to_string_pretty(&doc).unwrap_or_default() + "\n"
+}
+
+/// Milliseconds since the Unix epoch as "2024-05-01T12:00:00.000Z".
+pub fn iso8601_millis(ms: u64) -> String {
+    let (days, rem) = ((ms / 86_400_000) as i64, ms % 86_400_000);
+    // civil-from-days (Howard Hinnant)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        rem / 3_600_000,
+        rem / 60_000 % 60,
+        rem / 1000 % 60,
+        rem % 1000
+    )
+}
+
+/// The --heatmap-csv matrix: a `t_sec` column, then one column per latency bucket named
+/// by its lower edge in ms; windows with fewer buckets are padded with zeros.
+pub fn render_heatmap_csv(samples: &[IntervalSample], bucket_ms: f64) -> String {
+    let cols = samples.iter().map(|sm| sm.buckets.len()).max().unwrap_or(0);
+    // edges from whole micros so 0.1ms buckets print as 0.3, not 0.30000000000000004
+    let bucket_us = (bucket_ms * 1000.0).round() as u64;
+    let mut s = String::from("t_sec");
+    for i in 0..cols as u64 {
+        s.push_str(&format!(",{}", (i * bucket_us) as f64 / 1000.0));
+    }
+    s.push('\n');
+    for sm in samples {
+        s.push_str(&format!("{:.3}", sm.t_sec));
+        for i in 0..cols {
+            s.push_str(&format!(",{}", sm.buckets.get(i).copied().unwrap_or(0)));
+        }
+        s.push('\n');
+    }
+    s
+}
+
+/// Gzip-compresses a request payload (--compress-body gzip).
+pub fn gzip_bytes(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    use std::io::Write;
+    let mut enc = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    enc.write_all(data)?;
+    enc.finish()
+}
+
+/// Per-request values available to `expand_template`.
+#[derive(Debug, Clone, Copy)]
+pub struct TemplateVars {
+    /// 1-based request sequence number (across all workers)
+    pub seq: u64,
+    pub rand: u64,
+    pub uuid: Uuid,
+    /// wall clock when the request was built, in Unix epoch milliseconds
+    pub now_ms: u64,
+}
+
+impl TemplateVars {
+    /// Values for request `seq` and the given templates; the UUID is only drawn when one of
+    /// them uses it, so seeded runs without {{uuid}} keep their RNG sequence.
+    fn draw<'a>(seq: u64, rng: &mut StdRng, templates: impl Iterator<Item = &'a str>) -> Self {
+        let mut uses_uuid = false;
+        for t in templates {
+            uses_uuid |= t.contains("{{uuid}}");
+        }
+        TemplateVars {
+            seq,
+            rand: rng.gen(),
+            uuid: if uses_uuid {
+                new_uuid(rng)
+            } else {
+                Uuid::nil()
+            },
+            now_ms: SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis() as u64),
+        }
+    }
+}
+
+/// The placeholders `expand_template` replaces.
+const TEMPLATE_PLACEHOLDERS: [&str; 4] = ["{{seq}}", "{{rand}}", "{{uuid}}", "{{now_ms}}"];
+
+/// Whether `s` holds one of the `expand_template` placeholders.
+fn uses_template(s: &str) -> bool {
+    s.contains("{{") && TEMPLATE_PLACEHOLDERS.iter().any(|p| s.contains(p))
+}
+
+/// Replaces `{{seq}}`, `{{rand}}`, `{{uuid}}` and `{{now_ms}}`; unknown placeholders are
+/// left untouched.
+pub fn expand_template(s: &str, vars: &TemplateVars) -> String {
+    if !s.contains("{{") {
+        return s.to_string();
+    }
+    s.replace("{{seq}}", &vars.seq.to_string())
+        .replace("{{rand}}", &vars.rand.to_string())
+        .replace("{{uuid}}", &vars.uuid.to_string())
+        .replace("{{now_ms}}", &vars.now_ms.to_string())
+}
+
+/// Replaces `{{env:NAME}}` with what `lookup` returns for NAME, or with the default of
+/// `{{env:NAME:default}}`; a variable that is unset and has no default is the error.
+pub fn expand_env(s: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find("{{env:") {
+        let Some(len) = rest[start..].find("}}") else {
+            break;
+        };
+        let inner = &rest[start + "{{env:".len()..start + len];
+        let (name, default) = match inner.split_once(':') {
+            Some((name, default)) => (name, Some(default)),
+            None => (inner, None),
+        };
+        let value = lookup(name)
+            .or_else(|| default.map(str::to_string))
+            .ok_or_else(|| name.to_string())?;
+        out.push_str(&rest[..start]);
+        out.push_str(&value);
+        rest = &rest[start + len + "}}".len()..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// `expand_env` over the process environment, with the error naming `flag`.
+fn expand_env_for(flag: &str, s: &str) -> anyhow::Result<String> {
+    expand_env(s, |name| std::env::var(name).ok()).map_err(|name| {
+        anyhow::anyhow!(
+            "Invalid {flag}: environment variable {name} is not set (use {{{{env:{name}:default}}}} for a fallback)"
+        )
+    })
+}
+
+/// Replaces `{{name}}` for every variable in `vars` (--scenario captures).
+pub fn expand_vars(s: &str, vars: &BTreeMap<String, String>) -> String {
+    if vars.is_empty() || !s.contains("{{") {
+        return s.to_string();
+    }
+    let mut out = s.to_string();
+    for (name, value) in vars {
+        out = out.replace(&format!("{{{{{name}}}}}"), value);
+    }
+    out
+}
+
+/// One step of a JSONPath-like expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonPathSeg {
+    Key(String),
+    Index(usize),
+}
+
+/// Parses the supported JSONPath subset: `$` followed by `.key` and `[index]`
+/// segments, e.g. `$.data.items[0].id`.
+pub fn parse_json_path(path: &str) -> Option<Vec<JsonPathSeg>> {
+    let mut rest = path.trim().strip_prefix('$')?;
+    let mut segs = Vec::new();
+    while !rest.is_empty() {
+        if let Some(r) = rest.strip_prefix('.') {
+            let end = r.find(['.', '[']).unwrap_or(r.len());
+            if end == 0 {
+                return None;
+            }
+            segs.push(JsonPathSeg::Key(r[..end].to_string()));
+            rest = &r[end..];
+        } else if let Some(r) = rest.strip_prefix('[') {
+            let end = r.find(']')?;
+            segs.push(JsonPathSeg::Index(r[..end].trim().parse().ok()?));
+            rest = &r[end + 1..];
+        } else {
+            return None;
+        }
+    }
+    Some(segs)
+}
+
+pub fn lookup_json_path<'a>(v: &'a Value, segs: &[JsonPathSeg]) -> Option<&'a Value> {
+    segs.iter().try_fold(v, |cur, seg| match seg {
+        JsonPathSeg::Key(k) => cur.get(k),
+        JsonPathSeg::Index(i) => cur.get(i),
+    })
+}
+
+/// --hmac-sign settings.
+#[derive(Clone, PartialEq, Eq)]
+pub struct HmacSign {
+    pub header: String,
+    pub secret: String,
+    pub algo: HmacAlgo,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HmacAlgo {
+    Sha256,
+    Sha512,
+}
+
+impl std::fmt::Debug for HmacSign {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HmacSign")
+            .field("header", &self.header)
+            .field("secret", &"<redacted>")
+            .field("algo", &self.algo)
+            .finish()
+    }
+}
+
+/// "header=X-Signature,secret=abc,algo=sha256"; algo defaults to sha256.
+pub fn parse_hmac_sign(s: &str) -> Option<HmacSign> {
+    let (mut header, mut secret, mut algo) = (None, None, HmacAlgo::Sha256);
+    for part in s.split(',') {
+        let (k, v) = part.split_once('=')?;
+        match k.trim() {
+            "header" => header = Some(v.trim().to_string()),
+            "secret" => secret = Some(v.to_string()),
+            "algo" => {
+                algo = match v.trim().to_ascii_lowercase().as_str() {
+                    "sha256" => HmacAlgo::Sha256,
+                    "sha512" => HmacAlgo::Sha512,
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        }
+    }
+    let header = header.filter(|h| HeaderName::from_bytes(h.as_bytes()).is_ok())?;
+    let secret = secret.filter(|s| !s.is_empty())?;
+    Some(HmacSign {
+        header,
+        secret,
+        algo,
+    })
+}
+
+/// Hex HMAC over "METHOD\npath?query\nbody".
+pub fn sign_request(sign: &HmacSign, method: &Method, url: &Url, body: &[u8]) -> String {
+    let mut canonical = format!("{}\n{}", method.as_str(), url.path());
+    if let Some(q) = url.query() {
+        canonical.push('?');
+        canonical.push_str(q);
+    }
+    canonical.push('\n');
+    let mut canonical = canonical.into_bytes();
+    canonical.extend_from_slice(body);
+
+    let key = sign.secret.as_bytes();
+    let digest = match sign.algo {
+        HmacAlgo::Sha256 => {
+            let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes any key size");
+            mac.update(&canonical);
+            mac.finalize().into_bytes().to_vec()
+        }
+        HmacAlgo::Sha512 => {
+            let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC takes any key size");
+            mac.update(&canonical);
+            mac.finalize().into_bytes().to_vec()
+        }
+    };
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// --unix-socket must name an existing socket.
+#[cfg(unix)]
+fn check_unix_socket(path: &str) -> anyhow::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+    let meta = std::fs::metadata(path)
+        .map_err(|e| anyhow::anyhow!("Invalid --unix-socket {path}: {e}"))?;
+    if !meta.file_type().is_socket() {
+        return Err(anyhow::anyhow!(
+            "Invalid --unix-socket {path}: not a socket"
+        ));
+    }
+    Ok(())
+}
+
+/// Fails unless this host can open `family` sockets and the --url host has an address
+/// of that family; returns the unspecified address to bind.
+async fn check_address_family(url: &Url, family: AddressFamily) -> anyhow::Result<IpAddr> {
+    let (name, local) = match family {
+        AddressFamily::Ipv6 => ("IPv6", IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+        _ => ("IPv4", IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+    };
+    std::net::UdpSocket::bind((local, 0)).map_err(|e| {
+        anyhow::anyhow!("Invalid --address-family: {name} is not available on this host ({e})")
+    })?;
+    let addrs: Vec<IpAddr> = match url.host() {
+        Some(url::Host::Domain(d)) => {
+            let port = url.port_or_known_default().unwrap_or(80);
+            tokio::net::lookup_host((d, port))
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to resolve {d}: {e}"))?
+                .map(|a| a.ip())
+                .collect()
+        }
+        Some(url::Host::Ipv4(ip)) => vec![IpAddr::V4(ip)],
+        Some(url::Host::Ipv6(ip)) => vec![IpAddr::V6(ip)],
+        None => Vec::new(),
+    };
+    if !addrs.iter().any(|ip| ip.is_ipv4() == local.is_ipv4()) {
+        return Err(anyhow::anyhow!(
+            "Invalid --address-family: {} has no {name} address",
+            url.host_str().unwrap_or_default()
+        ));
+    }
+    Ok(local)
+}
+
+#[cfg(not(unix))]
+fn check_unix_socket(_path: &str) -> anyhow::Result<()> {
+    Err(anyhow::anyhow!("--unix-socket is only supported on Unix"))
+}
+
+/// Parses --abort-if-initial-failures "N/M" (0 <= N < M).
+pub fn parse_initial_failures(s: &str) -> Option<(u64, u64)> {
+    let (n, m) = s.split_once('/')?;
+    let (n, m) = (n.trim().parse().ok()?, m.trim().parse().ok()?);
+    (n < m).then_some((n, m))
+}
+
+/// A W3C trace context header for a sampled request:
+/// "00-<32 hex trace id>-<16 hex parent id>-01".
+pub fn traceparent(trace_id: u128, parent_id: u64) -> String {
+    format!("00-{trace_id:032x}-{parent_id:016x}-01")
+}
+
+/// The metrics of a Server-Timing header value that carry a dur= (in ms), in order:
+/// `db;dur=53.2, cache;desc="hit, warm";dur=0.4, miss` gives db and cache.
+pub fn parse_server_timing(value: &str) -> Vec<(String, f64)> {
+    let mut out = Vec::new();
+    for metric in split_unquoted(value, ',') {
+        let mut parts = split_unquoted(metric, ';').into_iter();
+        let name = parts.next().unwrap_or_default().trim();
+        if name.is_empty() {
+            continue;
+        }
+        let dur = parts
+            .filter_map(|p| p.split_once('='))
+            .find(|(k, _)| k.trim().eq_ignore_ascii_case("dur"))
+            .and_then(|(_, v)| v.trim().trim_matches('"').parse::<f64>().ok())
+            .filter(|d| d.is_finite() && *d >= 0.0);
+        if let Some(d) = dur {
+            out.push((name.to_string(), d));
+        }
+    }
+    out
+}
+
+/// Splits on `sep` outside double-quoted strings (which may hold backslash escapes).
+fn split_unquoted(s: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut from, mut quoted, mut escaped) = (0, false, false);
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            c if c == sep && !quoted => {
+                parts.push(&s[from..i]);
+                from = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[from..]);
+    parts
+}
+
+/// Pause after a 429/503 without a usable Retry-After header.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// Longest pause a Retry-After header can impose; larger values are clamped to it.
+pub const MAX_RETRY_AFTER: Duration = Duration::from_secs(300);
+
+/// How often a worker sitting out a Retry-After checks whether the run was stopped.
+const RETRY_AFTER_STOP_POLL: Duration = Duration::from_millis(100);
+
+/// Retry-After in its delay-seconds form ("120"), clamped to `MAX_RETRY_AFTER`; HTTP
+/// dates are not supported.
+pub fn parse_retry_after(s: &str) -> Option<Duration> {
+    let secs = s.trim().parse::<u64>().ok()?;
+    Some(Duration::from_secs(secs).min(MAX_RETRY_AFTER))
+}
+
+/// Byte count with an optional binary suffix: "512", "64k"/"64kb", "1m"/"1mb", "1g"/"1gb".
+pub fn parse_byte_size(s: &str) -> Option<u64> {
+    let s = s.trim().to_lowercase();
+    let s = s.strip_suffix('b').unwrap_or(&s);
+    let (num, mult) = match s.chars().last()? {
+        'k' => (&s[..s.len() - 1], 1u64 << 10),
+        'm' => (&s[..s.len() - 1], 1 << 20),
+        'g' => (&s[..s.len() - 1], 1 << 30),
+        _ => (s, 1),
+    };
+    num.trim().parse::<u64>().ok()?.checked_mul(mult)
+}
+
+/// Parses "min:max" byte sizes (either side may be left empty) into an inclusive range.
+pub fn parse_size_range(s: &str) -> Option<(u64, u64)> {
+    let (min, max) = s.split_once(':')?;
+    let side = |v: &str, open: u64| {
+        if v.trim().is_empty() {
+            Some(open)
+        } else {
+            parse_byte_size(v)
+        }
+    };
+    let (min, max) = (side(min, 0)?, side(max, u64::MAX)?);
+    (min <= max).then_some((min, max))
+}
+
 /// supports suffixes "ms", "s", "m", "h"
 pub fn parse_duration(s: &str) -> Option<Duration> {
     let s = s.trim().to_lowercase();
@@ -526,14 +9758,16 @@ pub fn load_json_payload(args: &RunArgs) -> anyhow::Result<Option<Value>> {
             "Provide only one of --json or --json-file."
         )),
         (Some(s), None) => {
+            let s = expand_env_for("--json", s)?;
             let v: Value =
-                serde_json::from_str(s).map_err(|e| anyhow::anyhow!("Invalid --json: {e}"))?;
+                serde_json::from_str(&s).map_err(|e| anyhow::anyhow!("Invalid --json: {e}"))?;
             Ok(Some(v))
         }
         (None, Some(path)) => {
-            let bytes = std::fs::read(path)
+            let text = std::fs::read_to_string(path)
                 .map_err(|e| anyhow::anyhow!("Failed to read --json-file {path}: {e}"))?;
-            let v: Value = serde_json::from_slice(&bytes)
+            let text = expand_env_for(&format!("--json-file {path}"), &text)?;
+            let v: Value = serde_json::from_str(&text)
                 .map_err(|e| anyhow::anyhow!("Invalid JSON in --json-file {path}: {e}"))?;
             Ok(Some(v))
         }
@@ -1,4 +1,3 @@
-#[tokio::main(flavor = "multi_thread")]
-async fn main() -> anyhow::Result<()> {
-    endpoint_tester::main_entry().await
+fn main() -> anyhow::Result<()> {
+    endpoint_tester::main_entry()
 }
@@ -0,0 +1,145 @@
+//! Live dashboard for `--tui` (built with the optional `tui` feature).
+//!
+//! Runs on its own thread and only reads `LiveStats`; the workers never wait on it.
+
+use crate::LiveStats;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::widgets::{Block, Borders, Paragraph, Sparkline};
+use ratatui::Frame;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+const REDRAW_EVERY: Duration = Duration::from_millis(250);
+/// Length of the rolling window behind rps, error rate and percentiles
+const WINDOW: Duration = Duration::from_secs(1);
+/// Seconds of throughput history in the sparkline
+const HISTORY_LEN: usize = 120;
+
+pub(crate) struct Dashboard {
+    done: Arc<AtomicBool>,
+    handle: JoinHandle<std::io::Result<()>>,
+}
+
+/// What the last closed window looked like.
+#[derive(Default)]
+struct View {
+    completed: u64,
+    errors: u64,
+    rps: f64,
+    error_rate: f64,
+    p50_ms: f64,
+    p99_ms: f64,
+    history: Vec<u64>,
+}
+
+/// Takes over the terminal and redraws until `stop` is called.
+pub(crate) fn start(live: Arc<LiveStats>, title: String) -> Dashboard {
+    let done = Arc::new(AtomicBool::new(false));
+    let handle = {
+        let done = done.clone();
+        std::thread::spawn(move || draw_loop(&live, &title, &done))
+    };
+    Dashboard { done, handle }
+}
+
+impl Dashboard {
+    /// Restores the terminal; the normal report can be printed afterwards.
+    pub(crate) fn stop(self) -> anyhow::Result<()> {
+        self.done.store(true, Ordering::Relaxed);
+        self.handle
+            .join()
+            .map_err(|_| anyhow::anyhow!("TUI thread panicked"))?
+            .map_err(|e| anyhow::anyhow!("TUI failed: {e}"))
+    }
+}
+
+fn draw_loop(live: &LiveStats, title: &str, done: &AtomicBool) -> std::io::Result<()> {
+    let mut terminal = ratatui::init();
+    let started = Instant::now();
+    let mut view = View::default();
+    let mut window_start = Instant::now();
+    let (mut last_completed, mut last_errors) = (0, 0);
+
+    let result = (|| {
+        while !done.load(Ordering::Relaxed) {
+            if event::poll(REDRAW_EVERY)? {
+                if let Event::Key(k) = event::read()? {
+                    let ctrl_c =
+                        k.code == KeyCode::Char('c') && k.modifiers.contains(KeyModifiers::CONTROL);
+                    if k.kind == KeyEventKind::Press && (k.code == KeyCode::Char('q') || ctrl_c) {
+                        // raw mode swallows SIGINT, so quitting is handled here
+                        ratatui::restore();
+                        std::process::exit(130);
+                    }
+                }
+            }
+
+            let elapsed = window_start.elapsed();
+            if elapsed >= WINDOW {
+                let completed = live.completed();
+                let errors = live.errors();
+                let done_in_window = completed - last_completed;
+                let h = live.take_window();
+                view.completed = completed;
+                view.errors = errors;
+                view.rps = done_in_window as f64 / elapsed.as_secs_f64();
+                view.error_rate = if done_in_window > 0 {
+                    (errors - last_errors) as f64 / done_in_window as f64 * 100.0
+                } else {
+                    0.0
+                };
+                view.p50_ms = h.value_at_quantile(0.50) as f64 / 1000.0;
+                view.p99_ms = h.value_at_quantile(0.99) as f64 / 1000.0;
+                if view.history.len() == HISTORY_LEN {
+                    view.history.remove(0);
+                }
+                view.history.push(view.rps.round() as u64);
+                (last_completed, last_errors) = (completed, errors);
+                window_start = Instant::now();
+            }
+
+            terminal.draw(|f| render(f, title, started.elapsed(), &view))?;
+        }
+        Ok(())
+    })();
+
+    ratatui::restore();
+    result
+}
+
+fn render(f: &mut Frame, title: &str, elapsed: Duration, v: &View) {
+    let [stats, chart] =
+        Layout::vertical([Constraint::Length(8), Constraint::Min(3)]).areas(f.area());
+
+    let text = format!(
+        "elapsed: {:.1}s\ncompleted: {}  errors: {}\nthroughput_rps: {:.1}\nerror_rate: {:.2}%\np50_ms: {:.3}  p99_ms: {:.3}\n(q to quit)",
+        elapsed.as_secs_f64(),
+        v.completed,
+        v.errors,
+        v.rps,
+        v.error_rate,
+        v.p50_ms,
+        v.p99_ms,
+    );
+    f.render_widget(
+        Paragraph::new(text).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(" endpoint_tester: {title} ")),
+        ),
+        stats,
+    );
+    f.render_widget(
+        Sparkline::default()
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(" throughput_rps (1 per second) "),
+            )
+            .data(&v.history),
+        chart,
+    );
+}
@@ -1,9 +1,17 @@
 // tests/e2e.rs  (REPLACE ENTIRE FILE)
-use endpoint_tester::{run, RunArgs};
+use endpoint_tester::{
+    compare_methods, join_workers, measure_tls_resumption, render_har, render_heatmap_csv,
+    render_method_comparison, render_ndjson_summary, render_report, render_report_summary, run,
+    run_streaming, AddressFamily, BackoffMode, BodyCompression, BodyFill, HistConfig, MockClock,
+    OnOverload, ProgressFormat, RunArgs, Select, DEFAULT_USER_AGENT, EVENTS_CHANNEL_CAPACITY,
+    HAR_ENTRY_CAP,
+};
 
 use std::net::SocketAddr;
+use std::sync::Arc;
 use tokio::net::TcpListener;
 use tokio::time::Duration;
+use tokio_stream::StreamExt;
 
 use hyper::body::Incoming;
 use hyper::{Request, Response, StatusCode};
@@ -12,6 +20,19 @@ use hyper_util::rt::TokioIo;
 use http_body_util::{BodyExt, Full};
 use hyper::body::Bytes;
 
+/// Idempotency keys seen by `/idem` across all test servers.
+static SEEN_KEYS: std::sync::Mutex<std::collections::BTreeSet<String>> =
+    std::sync::Mutex::new(std::collections::BTreeSet::new());
+
+/// Values seen by `/record` (the X-Record header, else the `r` query param), keyed by the
+/// `run` query param.
+static RECORDED: std::sync::Mutex<std::collections::BTreeMap<String, Vec<String>>> =
+    std::sync::Mutex::new(std::collections::BTreeMap::new());
+
+/// Hits on `/flaky`, keyed by the `key` query param.
+static FLAKY_HITS: std::sync::Mutex<std::collections::BTreeMap<String, u32>> =
+    std::sync::Mutex::new(std::collections::BTreeMap::new());
+
 async fn spawn_test_server() -> SocketAddr {
     let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
     let addr = listener.local_addr().unwrap();
@@ -32,12 +53,212 @@ async fn spawn_test_server() -> SocketAddr {
                                 .body(Full::<Bytes>::from("ok").boxed())
                                 .unwrap(),
                         ),
+                        "/close" => Ok::<_, hyper::Error>(
+                            Response::builder()
+                                .status(StatusCode::OK)
+                                .header("Connection", "close")
+                                .body(Full::<Bytes>::from("ok").boxed())
+                                .unwrap(),
+                        ),
+                        "/server_timing" => Ok::<_, hyper::Error>(
+                            Response::builder()
+                                .status(StatusCode::OK)
+                                .header("Server-Timing", "db;dur=12.5, cache;desc=hit")
+                                .header("Server-Timing", "app;desc=\"render, all\";dur=30")
+                                .body(Full::<Bytes>::from("ok").boxed())
+                                .unwrap(),
+                        ),
+                        "/cached" => Ok::<_, hyper::Error>(
+                            Response::builder()
+                                .status(StatusCode::OK)
+                                .header("Cache-Control", "no-store")
+                                .header("ETag", "\"v1\"")
+                                .body(Full::<Bytes>::from("cached").boxed())
+                                .unwrap(),
+                        ),
+                        "/busy" => Ok::<_, hyper::Error>(
+                            Response::builder()
+                                .status(StatusCode::TOO_MANY_REQUESTS)
+                                .header("Retry-After", "1")
+                                .body(Full::<Bytes>::from("slow down").boxed())
+                                .unwrap(),
+                        ),
+                        "/busy-forever" => Ok::<_, hyper::Error>(
+                            Response::builder()
+                                .status(StatusCode::TOO_MANY_REQUESTS)
+                                .header("Retry-After", u64::MAX.to_string())
+                                .body(Full::<Bytes>::from("slow down").boxed())
+                                .unwrap(),
+                        ),
                         "/fail" => Ok::<_, hyper::Error>(
                             Response::builder()
                                 .status(StatusCode::INTERNAL_SERVER_ERROR)
                                 .body(Full::<Bytes>::from("fail").boxed())
                                 .unwrap(),
                         ),
+                        "/flaky" => {
+                            // 500 for the first `fail` hits of each `key`, then 200
+                            let q = req.uri().query().unwrap_or("").to_string();
+                            let param = |name: &str| {
+                                q.split('&')
+                                    .find_map(|p| p.strip_prefix(&format!("{name}=")[..]))
+                                    .unwrap_or("")
+                                    .to_string()
+                            };
+                            let fail: u32 = param("fail").parse().unwrap_or(0);
+                            let hit = {
+                                let mut hits = FLAKY_HITS.lock().unwrap();
+                                let n = hits.entry(param("key")).or_insert(0);
+                                *n += 1;
+                                *n
+                            };
+                            let status = if hit <= fail {
+                                StatusCode::INTERNAL_SERVER_ERROR
+                            } else {
+                                StatusCode::OK
+                            };
+                            Ok::<_, hyper::Error>(
+                                Response::builder()
+                                    .status(status)
+                                    .body(Full::<Bytes>::from("").boxed())
+                                    .unwrap(),
+                            )
+                        }
+                        "/query" => {
+                            let q = req.uri().query().unwrap_or("").to_string();
+                            let has_seq = q.split('&').any(|p| {
+                                p.strip_prefix("id=")
+                                    .is_some_and(|v| v.parse::<u64>().is_ok_and(|n| n >= 1))
+                            });
+                            let encoded = q.contains("q=a+b%26c");
+                            let status = if has_seq && encoded {
+                                StatusCode::OK
+                            } else {
+                                StatusCode::BAD_REQUEST
+                            };
+                            Ok::<_, hyper::Error>(
+                                Response::builder()
+                                    .status(status)
+                                    .body(Full::<Bytes>::from(q).boxed())
+                                    .unwrap(),
+                            )
+                        }
+                        "/traceparent" => {
+                            // 200 only for a well-formed, sampled W3C traceparent
+                            let tp = req
+                                .headers()
+                                .get("traceparent")
+                                .and_then(|v| v.to_str().ok())
+                                .unwrap_or("");
+                            let parts: Vec<&str> = tp.split('-').collect();
+                            let hex = |s: &str, n: usize| {
+                                s.len() == n && s.bytes().all(|b| b.is_ascii_hexdigit())
+                            };
+                            let ok = parts.len() == 4
+                                && parts[0] == "00"
+                                && hex(parts[1], 32)
+                                && hex(parts[2], 16)
+                                && parts[3] == "01";
+                            let status = if ok {
+                                StatusCode::OK
+                            } else {
+                                StatusCode::BAD_REQUEST
+                            };
+                            Ok::<_, hyper::Error>(
+                                Response::builder()
+                                    .status(status)
+                                    .body(Full::<Bytes>::from(tp.to_string()).boxed())
+                                    .unwrap(),
+                            )
+                        }
+                        p if p.starts_with("/ua/") => {
+                            // 200 only when the User-Agent matches the path suffix
+                            let expected = &p["/ua/".len()..];
+                            let ua = req
+                                .headers()
+                                .get(hyper::header::USER_AGENT)
+                                .and_then(|v| v.to_str().ok())
+                                .unwrap_or("");
+                            let status = if ua == expected {
+                                StatusCode::OK
+                            } else {
+                                StatusCode::BAD_REQUEST
+                            };
+                            Ok::<_, hyper::Error>(
+                                Response::builder()
+                                    .status(status)
+                                    .body(Full::<Bytes>::from(ua.to_string()).boxed())
+                                    .unwrap(),
+                            )
+                        }
+                        p if p.starts_with("/len/") => {
+                            // 200 only when the request body has exactly the given length
+                            let want: usize = p["/len/".len()..].parse().unwrap_or(usize::MAX);
+                            let body = req.into_body().collect().await?.to_bytes();
+                            let status = if body.len() == want {
+                                StatusCode::OK
+                            } else {
+                                StatusCode::BAD_REQUEST
+                            };
+                            Ok::<_, hyper::Error>(
+                                Response::builder()
+                                    .status(status)
+                                    .body(Full::<Bytes>::from(body.len().to_string()).boxed())
+                                    .unwrap(),
+                            )
+                        }
+                        "/form" => {
+                            // 200 only for a multipart body with a "title" field and an upload
+                            let multipart = req
+                                .headers()
+                                .get("content-type")
+                                .and_then(|v| v.to_str().ok())
+                                .is_some_and(|v| v.starts_with("multipart/form-data; boundary="));
+                            let body = req.into_body().collect().await?.to_bytes();
+                            let text = String::from_utf8_lossy(&body);
+                            let ok = multipart
+                                && text.contains("name=\"title\"\r\n\r\nhello\r\n")
+                                && text.contains("name=\"upload\"; filename=\"data.txt\"")
+                                && text.contains("file-contents");
+                            Ok::<_, hyper::Error>(
+                                Response::builder()
+                                    .status(if ok {
+                                        StatusCode::OK
+                                    } else {
+                                        StatusCode::BAD_REQUEST
+                                    })
+                                    .body(Full::<Bytes>::from("").boxed())
+                                    .unwrap(),
+                            )
+                        }
+                        "/cors" => {
+                            let is_preflight = req.method() == hyper::Method::OPTIONS
+                                && req.headers().get("access-control-request-method").is_some()
+                                && req.headers().get("origin").is_some();
+                            let status = if is_preflight {
+                                StatusCode::NO_CONTENT
+                            } else if req.method() == hyper::Method::POST {
+                                StatusCode::OK
+                            } else {
+                                StatusCode::METHOD_NOT_ALLOWED
+                            };
+                            Ok::<_, hyper::Error>(
+                                Response::builder()
+                                    .status(status)
+                                    .header("Access-Control-Allow-Origin", "*")
+                                    .body(Full::<Bytes>::from("").boxed())
+                                    .unwrap(),
+                            )
+                        }
+                        "/hang" => {
+                            tokio::time::sleep(Duration::from_secs(60)).await;
+                            Ok::<_, hyper::Error>(
+                                Response::builder()
+                                    .status(StatusCode::OK)
+                                    .body(Full::<Bytes>::from("late").boxed())
+                                    .unwrap(),
+                            )
+                        }
                         "/sleep" => {
                             tokio::time::sleep(Duration::from_millis(250)).await;
                             Ok::<_, hyper::Error>(
@@ -47,6 +268,181 @@ async fn spawn_test_server() -> SocketAddr {
                                     .unwrap(),
                             )
                         }
+                        "/idem" => {
+                            // 200 for a new UUID key, 409 for a reused one, 400 when missing
+                            let key = req
+                                .headers()
+                                .get("idempotency-key")
+                                .and_then(|v| v.to_str().ok())
+                                .unwrap_or("")
+                                .to_string();
+                            let status = if key.len() != 36 || key.matches('-').count() != 4 {
+                                StatusCode::BAD_REQUEST
+                            } else if SEEN_KEYS.lock().unwrap().insert(key) {
+                                StatusCode::OK
+                            } else {
+                                StatusCode::CONFLICT
+                            };
+                            Ok::<_, hyper::Error>(
+                                Response::builder()
+                                    .status(status)
+                                    .body(Full::<Bytes>::from("").boxed())
+                                    .unwrap(),
+                            )
+                        }
+                        "/vnd" => {
+                            // 200 only for exactly one vendor Content-Type header
+                            let types: Vec<_> = req
+                                .headers()
+                                .get_all(hyper::header::CONTENT_TYPE)
+                                .iter()
+                                .collect();
+                            let status =
+                                if types.len() == 1 && types[0] == "application/vnd.api+json" {
+                                    StatusCode::OK
+                                } else {
+                                    StatusCode::UNSUPPORTED_MEDIA_TYPE
+                                };
+                            Ok::<_, hyper::Error>(
+                                Response::builder()
+                                    .status(status)
+                                    .body(Full::<Bytes>::from("").boxed())
+                                    .unwrap(),
+                            )
+                        }
+                        "/sig" => {
+                            // HMAC-SHA256("k", "POST\n/sig\n{\"a\":1}")
+                            let want =
+                                "a5fa6c0eea95378f0085de4e9ed019e91ded1506df103a83e67e5380ef891c50";
+                            let status =
+                                if req.headers().get("x-signature").is_some_and(|v| v == want) {
+                                    StatusCode::OK
+                                } else {
+                                    StatusCode::UNAUTHORIZED
+                                };
+                            Ok::<_, hyper::Error>(
+                                Response::builder()
+                                    .status(status)
+                                    .body(Full::<Bytes>::from("").boxed())
+                                    .unwrap(),
+                            )
+                        }
+                        "/multi" => {
+                            // 200 only when both X-Multi values arrive, in order
+                            let values: Vec<_> = req
+                                .headers()
+                                .get_all("x-multi")
+                                .iter()
+                                .map(|v| v.to_str().unwrap_or("").to_string())
+                                .collect();
+                            let status = if values == ["a", "b"] {
+                                StatusCode::OK
+                            } else {
+                                StatusCode::BAD_REQUEST
+                            };
+                            Ok::<_, hyper::Error>(
+                                Response::builder()
+                                    .status(status)
+                                    .body(Full::<Bytes>::from("").boxed())
+                                    .unwrap(),
+                            )
+                        }
+                        "/record" => {
+                            let q = req.uri().query().unwrap_or("").to_string();
+                            let param = |name: &str| {
+                                q.split('&')
+                                    .find_map(|p| p.strip_prefix(&format!("{name}=")[..]))
+                                    .unwrap_or("")
+                                    .to_string()
+                            };
+                            let value = req
+                                .headers()
+                                .get("x-record")
+                                .and_then(|v| v.to_str().ok())
+                                .map_or_else(|| param("r"), str::to_string);
+                            RECORDED
+                                .lock()
+                                .unwrap()
+                                .entry(param("run"))
+                                .or_default()
+                                .push(value);
+                            Ok::<_, hyper::Error>(
+                                Response::builder()
+                                    .status(StatusCode::OK)
+                                    .body(Full::<Bytes>::from("").boxed())
+                                    .unwrap(),
+                            )
+                        }
+                        "/etag" => {
+                            let fresh = req
+                                .headers()
+                                .get(hyper::header::IF_NONE_MATCH)
+                                .is_some_and(|v| v == "\"v1\"");
+                            let status = if fresh {
+                                StatusCode::NOT_MODIFIED
+                            } else {
+                                StatusCode::OK
+                            };
+                            Ok::<_, hyper::Error>(
+                                Response::builder()
+                                    .status(status)
+                                    .header("ETag", "\"v1\"")
+                                    .body(Full::<Bytes>::from("").boxed())
+                                    .unwrap(),
+                            )
+                        }
+                        "/login" => Ok::<_, hyper::Error>(
+                            Response::builder()
+                                .status(StatusCode::OK)
+                                .header("Content-Type", "application/json")
+                                .body(Full::<Bytes>::from(r#"{"auth":{"token":"abc123"}}"#).boxed())
+                                .unwrap(),
+                        ),
+                        "/whoami" => {
+                            let authed = req
+                                .headers()
+                                .get(hyper::header::AUTHORIZATION)
+                                .is_some_and(|v| v == "Bearer abc123");
+                            let status = if authed {
+                                StatusCode::OK
+                            } else {
+                                StatusCode::UNAUTHORIZED
+                            };
+                            Ok::<_, hyper::Error>(
+                                Response::builder()
+                                    .status(status)
+                                    .body(Full::<Bytes>::from("").boxed())
+                                    .unwrap(),
+                            )
+                        }
+                        "/gunzip_json" => {
+                            // 200 only for a gzip-encoded JSON object
+                            let (parts, body) = req.into_parts();
+                            let gzip = parts
+                                .headers
+                                .get("content-encoding")
+                                .is_some_and(|v| v == "gzip");
+                            let bytes = body.collect().await?.to_bytes();
+                            let mut s = String::new();
+                            let ok = gzip
+                                && std::io::Read::read_to_string(
+                                    &mut flate2::read::GzDecoder::new(bytes.as_ref()),
+                                    &mut s,
+                                )
+                                .is_ok()
+                                && s.trim_start().starts_with('{');
+                            let status = if ok {
+                                StatusCode::OK
+                            } else {
+                                StatusCode::BAD_REQUEST
+                            };
+                            Ok::<_, hyper::Error>(
+                                Response::builder()
+                                    .status(status)
+                                    .body(Full::<Bytes>::from(s).boxed())
+                                    .unwrap(),
+                            )
+                        }
                         "/echo_json" => {
                             let (_parts, body) = req.into_parts();
 
@@ -93,43 +489,62 @@ async fn e2e_counts_200s() {
     let addr = spawn_test_server().await;
     let url = format!("http://{}/ok", addr);
 
-    let args = RunArgs {
-        url,
-        method: "GET".into(),
-        concurrency: 4,
-        requests: Some(50),
-        duration: None,
-        timeout: "2s".into(),
-        headers: vec![],
-        api_key: None,
-        json: None,
-        json_file: None,
-        progress_every: 0,
-    };
+    let args = RunArgs::builder(url).requests(50).progress_every(0).build();
 
     let res = run(args).await.unwrap();
     assert_eq!(res.completed, 50);
     assert_eq!(res.aggregates.status_class.c2xx, 50);
 }
 
+#[tokio::test]
+async fn e2e_initial_failure_guard_ignores_http_errors() {
+    let addr = spawn_test_server().await;
+    // 5xx answers prove the endpoint is reachable, so the guard stays quiet
+    let args = RunArgs::builder(format!("http://{}/fail", addr))
+        .requests(10)
+        .abort_if_initial_failures(0, 5)
+        .progress_every(0)
+        .build();
+    let res = run(args).await.unwrap();
+    assert_eq!(res.aggregates.status_class.c5xx, 10);
+}
+
+#[tokio::test]
+async fn e2e_trace_file_replays_schedule() {
+    let addr = spawn_test_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let trace = dir.path().join("trace.txt");
+    std::fs::write(
+        &trace,
+        "# captured\n0 GET /ok\n50 GET /fail\n100 GET /ok\n\n150 POST /ok\n",
+    )
+    .unwrap();
+
+    let t = std::time::Instant::now();
+    let args = RunArgs::builder(format!("http://{}/", addr))
+        .concurrency(4)
+        .trace_file(trace.to_str().unwrap())
+        .progress_every(0)
+        .build();
+    let res = run(args).await.unwrap();
+    // the last recorded start is 150ms in
+    assert!(t.elapsed() >= Duration::from_millis(150));
+    assert_eq!(res.completed, 4);
+    let names: Vec<&str> = res.paths.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, ["GET /ok", "GET /fail", "POST /ok"]);
+    assert_eq!(res.paths[0].aggregates.status_exact.get(&200), Some(&2));
+    assert_eq!(res.paths[1].aggregates.status_exact.get(&500), Some(&1));
+    let rp = res.replay.as_ref().unwrap();
+    assert_eq!((rp.scheduled, rp.sent), (4, 4));
+    assert!(render_report(&res).contains("replay: sent=4 of 4 scheduled, start lag p50_ms="));
+}
+
 #[tokio::test]
 async fn e2e_counts_500s() {
     let addr = spawn_test_server().await;
     let url = format!("http://{}/fail", addr);
 
-    let args = RunArgs {
-        url,
-        method: "GET".into(),
-        concurrency: 4,
-        requests: Some(50),
-        duration: None,
-        timeout: "2s".into(),
-        headers: vec![],
-        api_key: None,
-        json: None,
-        json_file: None,
-        progress_every: 0,
-    };
+    let args = RunArgs::builder(url).requests(50).progress_every(0).build();
 
     let res = run(args).await.unwrap();
     assert_eq!(res.completed, 50);
@@ -141,45 +556,2430 @@ async fn e2e_timeout_errors() {
     let addr = spawn_test_server().await;
     let url = format!("http://{}/sleep", addr);
 
-    let args = RunArgs {
-        url,
-        method: "GET".into(),
-        concurrency: 2,
-        requests: Some(10),
-        duration: None,
-        timeout: "50ms".into(),
-        headers: vec![],
-        api_key: None,
-        json: None,
-        json_file: None,
-        progress_every: 0,
-    };
+    let args = RunArgs::builder(url)
+        .concurrency(2)
+        .requests(10)
+        .timeout("50ms")
+        .progress_every(0)
+        .build();
 
     let res = run(args).await.unwrap();
     assert_eq!(res.completed, 10);
     assert_eq!(res.aggregates.net_errors.timeout, 10);
 }
 
+#[tokio::test]
+async fn e2e_requests_per_worker_multiplies_by_concurrency() {
+    let addr = spawn_test_server().await;
+
+    let args = RunArgs::builder(format!("http://{}/ok", addr))
+        .concurrency(3)
+        .requests_per_worker(4)
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.completed, 12);
+    assert_eq!(res.sent, 12);
+    assert_eq!(res.requests_target, Some(12));
+    let out = render_report(&res);
+    assert!(out.contains("requests_target: 12\nrequests_per_worker: 4 (3 workers)\n"));
+}
+
 #[tokio::test]
 async fn e2e_post_json_works() {
     let addr = spawn_test_server().await;
     let url = format!("http://{}/echo_json", addr);
 
-    let args = RunArgs {
-        url,
-        method: "POST".into(),
-        concurrency: 1,
-        requests: Some(5),
-        duration: None,
-        timeout: "2s".into(),
-        headers: vec!["Content-Type: application/json".into()],
-        api_key: None,
-        json: Some(r#"{"hello":"world"}"#.into()),
-        json_file: None,
-        progress_every: 0,
-    };
+    let args = RunArgs::builder(url)
+        .method("POST")
+        .concurrency(1)
+        .requests(5)
+        .header("Content-Type: application/json")
+        .json(r#"{"hello":"world"}"#)
+        .progress_every(0)
+        .build();
 
     let res = run(args).await.unwrap();
     assert_eq!(res.completed, 5);
     assert_eq!(res.aggregates.status_class.c2xx, 5);
 }
+
+#[tokio::test]
+async fn e2e_compress_body_sends_gzip() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/gunzip_json", addr);
+    let json = format!(r#"{{"pad":"{}"}}"#, "a".repeat(4000));
+
+    let args = RunArgs::builder(url)
+        .method("POST")
+        .concurrency(1)
+        .requests(4)
+        .json(json.clone())
+        .compress_body(BodyCompression::Gzip)
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.aggregates.status_class.c2xx, 4);
+    let c = res.body_compression.clone().unwrap();
+    assert_eq!(c.original_bytes, json.len() as u64);
+    assert!(c.compressed_bytes < c.original_bytes / 10);
+    assert_eq!(res.aggregates.bytes_sent, 4 * c.compressed_bytes);
+    assert!(render_report(&res).contains("compress_body: gzip original="));
+}
+
+#[tokio::test]
+async fn e2e_success_body_checks_judge_the_body() {
+    let addr = spawn_test_server().await;
+
+    let base = || {
+        RunArgs::builder(format!("http://{}/ok", addr))
+            .concurrency(1)
+            .requests(3)
+            .progress_every(0)
+    };
+    let res = run(base()
+        .success_body_contains("ok")
+        .success_body_regex("^o.$")
+        .build())
+    .await
+    .unwrap();
+    assert_eq!(res.aggregates.body_checks.pass, 3);
+    assert_eq!(res.aggregates.validation_failures(), 0);
+
+    let res = run(base().success_body_regex("^nope").build())
+        .await
+        .unwrap();
+    assert_eq!(res.aggregates.body_checks.fail, 3);
+    // the status was fine; only the body failed
+    assert_eq!(res.aggregates.status_class.c2xx, 3);
+    assert!(render_report(&res).contains("body_checks: pass=0 fail=3"));
+}
+
+#[tokio::test]
+async fn e2e_expect_body_size_range_judges_the_size() {
+    let addr = spawn_test_server().await;
+
+    let base = || {
+        RunArgs::builder(format!("http://{}/ok", addr))
+            .concurrency(1)
+            .requests(3)
+            .progress_every(0)
+    };
+    let res = run(base().expect_body_size_range("1:2").build())
+        .await
+        .unwrap();
+    assert_eq!(res.aggregates.body_size_checks.pass, 3);
+    assert_eq!(res.aggregates.validation_failures(), 0);
+
+    // "ok" is two bytes, one short of the range
+    let res = run(base().expect_body_size_range("3:").build())
+        .await
+        .unwrap();
+    assert_eq!(res.aggregates.body_size_checks.fail, 3);
+    assert_eq!(res.aggregates.validation_failures(), 3);
+    assert!(render_report(&res).contains("body_size_checks: pass=0 fail=3"));
+}
+
+#[tokio::test]
+async fn e2e_validate_sample_rate_checks_a_fraction() {
+    let addr = spawn_test_server().await;
+
+    let base = || {
+        RunArgs::builder(format!("http://{}/ok", addr))
+            .concurrency(2)
+            .requests(200)
+            .seed(7)
+            .success_body_regex("^nope")
+            .progress_every(0)
+    };
+    let res = run(base().validate_sample_rate(0.25).build())
+        .await
+        .unwrap();
+    let a = &res.aggregates;
+    assert_eq!(a.validated_responses + a.validation_sampled_out, 200);
+    assert!((20..=80).contains(&a.validated_responses));
+    assert_eq!(a.body_checks.fail, a.validated_responses);
+    // every response still counts towards latency and status
+    assert_eq!(a.status_class.c2xx, 200);
+    assert!(render_report(&res).contains(&format!(
+        "validated_responses: {} of 200 (--validate-sample-rate)",
+        a.validated_responses
+    )));
+
+    let res = run(base().validate_sample_rate(0.0).build()).await.unwrap();
+    assert_eq!(res.aggregates.validation_failures(), 0);
+    assert_eq!(res.aggregates.validation_sampled_out, 200);
+
+    let res = run(base().build()).await.unwrap();
+    assert_eq!(res.aggregates.body_checks.fail, 200);
+    assert!(!render_report(&res).contains("validated_responses:"));
+}
+
+#[tokio::test]
+async fn e2e_har_sample_rate_records_every_nth_request() {
+    let addr = spawn_test_server().await;
+
+    let res = run(RunArgs::builder(format!("http://{}/ok?x=1", addr))
+        .concurrency(2)
+        .requests(10)
+        .header("Authorization: Bearer secret")
+        .har_sample_rate(0.5)
+        .progress_every(0)
+        .build())
+    .await
+    .unwrap();
+    assert_eq!(res.har.len(), 5);
+    let e = &res.har[0];
+    assert_eq!(e.method, "GET");
+    assert_eq!(e.status, Some(200));
+    assert_eq!(e.body_preview, "ok");
+    assert_eq!(e.http_version, "HTTP/1.1");
+    assert!(e
+        .request_headers
+        .contains(&("authorization".to_string(), "Bearer <redacted>".to_string())));
+    assert!(e.started_unix_ms > 0);
+
+    let har = render_har(&res.har.iter().collect::<Vec<_>>());
+    assert!(har.contains("\"version\": \"1.2\""));
+    assert!(har.contains("\"name\": \"x\""));
+    // not part of the JSON result
+    assert!(!serde_json::to_string(&res).unwrap().contains("\"har\""));
+}
+
+#[tokio::test]
+async fn e2e_har_keeps_previews_and_stops_sampling_at_the_cap() {
+    let addr = spawn_test_server().await;
+    let json = format!(r#"{{"pad":"{}"}}"#, "a".repeat(10_000));
+
+    let res = run(RunArgs::builder(format!("http://{}/echo_json", addr))
+        .method("POST")
+        .json(json.clone())
+        .concurrency(1)
+        .requests(2)
+        .har_sample_rate(1.0)
+        .progress_every(0)
+        .build())
+    .await
+    .unwrap();
+    let e = &res.har[0];
+    assert_eq!(e.body_bytes, json.len() as u64);
+    assert_eq!(e.body_preview.len(), 4096);
+
+    let res = run(RunArgs::builder(format!("http://{}/ok", addr))
+        .concurrency(4)
+        .requests(HAR_ENTRY_CAP as u64 + 20)
+        .har_sample_rate(1.0)
+        .progress_every(0)
+        .build())
+    .await
+    .unwrap();
+    assert_eq!(res.har.len(), HAR_ENTRY_CAP);
+    assert_eq!(res.completed, HAR_ENTRY_CAP as u64 + 20);
+}
+
+#[tokio::test]
+async fn e2e_latency_per_kb_scales_latency_by_body_size() {
+    let addr = spawn_test_server().await;
+
+    let res = run(RunArgs::builder(format!("http://{}/ok", addr))
+        .concurrency(1)
+        .requests(5)
+        .measure_ttfb(true)
+        .normalize_latency_by_payload_size(true)
+        .progress_every(0)
+        .build())
+    .await
+    .unwrap();
+    let per_kb = &res.aggregates.latency_per_kb_micros;
+    assert_eq!(per_kb.len(), 5);
+    // "ok" is 2 bytes, so a KiB of it takes 512 times as long
+    let max = res.aggregates.latency_micros.max();
+    assert!(per_kb.max() >= max * 500 && per_kb.max() <= max * 520);
+    assert!(render_report(&res).contains("latency_per_kb_ms:"));
+}
+
+#[tokio::test]
+async fn e2e_parse_server_timing_keeps_a_histogram_per_metric() {
+    let addr = spawn_test_server().await;
+
+    let base = || {
+        RunArgs::builder(format!("http://{}/server_timing", addr))
+            .concurrency(1)
+            .requests(4)
+            .progress_every(0)
+    };
+    let res = run(base().build()).await.unwrap();
+    assert!(res.aggregates.server_timing_micros.is_empty());
+
+    let res = run(base().parse_server_timing(true).build()).await.unwrap();
+    let st = &res.aggregates.server_timing_micros;
+    assert_eq!(st.keys().collect::<Vec<_>>(), ["app", "db"]);
+    assert_eq!(st["db"].len(), 4);
+    assert!(st["db"].equivalent(st["db"].max(), 12_500));
+    assert!(st["app"].equivalent(st["app"].max(), 30_000));
+    let report = render_report(&res);
+    assert!(report.contains("server_timing_ms:"));
+    assert!(report.contains("  db: count=4 mean=12.5"));
+}
+
+#[tokio::test]
+async fn e2e_response_schema_counts_violations() {
+    let addr = spawn_test_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let schema = |name: &str, hello_type: &str| {
+        let p = dir.path().join(name);
+        std::fs::write(
+            &p,
+            format!(
+                r#"{{"type":"object","required":["hello"],"properties":{{"hello":{{"type":"{hello_type}"}}}}}}"#
+            ),
+        )
+        .unwrap();
+        p.to_str().unwrap().to_string()
+    };
+    let base = |url: &str| {
+        RunArgs::builder(format!("http://{}{url}", addr))
+            .concurrency(1)
+            .requests(3)
+            .progress_every(0)
+    };
+    let post = || {
+        base("/echo_json")
+            .method("POST")
+            .json(r#"{"hello":"world"}"#)
+    };
+
+    let res = run(post().response_schema(schema("ok.json", "string")).build())
+        .await
+        .unwrap();
+    assert_eq!(res.aggregates.schema_checks.pass, 3);
+
+    let res = run(post()
+        .response_schema(schema("bad.json", "integer"))
+        .build())
+    .await
+    .unwrap();
+    assert_eq!(res.aggregates.schema_checks.fail, 3);
+    assert_eq!(res.aggregates.validation_failures(), 3);
+    // one distinct violation, kept once
+    assert_eq!(res.aggregates.schema_violations.len(), 1);
+    assert!(res.aggregates.schema_violations[0].starts_with("/hello: "));
+    assert!(
+        render_report(&res).contains("schema_checks: pass=0 fail=3 skipped=0\n  violation: /hello")
+    );
+
+    // "ok" with no Content-Type is not JSON, so it is skipped rather than failed
+    let res = run(base("/ok")
+        .response_schema(schema("ok.json", "string"))
+        .build())
+    .await
+    .unwrap();
+    assert_eq!(res.aggregates.schema_skipped, 3);
+    assert_eq!(res.aggregates.validation_failures(), 0);
+}
+
+#[tokio::test]
+async fn e2e_max_validate_bytes_limits_the_judged_body() {
+    let addr = spawn_test_server().await;
+    let json = format!(r#"{{"pad":"{}","tail":"marker"}}"#, "a".repeat(1000));
+
+    let base = || {
+        RunArgs::builder(format!("http://{}/echo_json", addr))
+            .method("POST")
+            .concurrency(1)
+            .requests(2)
+            .json(json.clone())
+            .success_body_contains("marker")
+            .progress_every(0)
+    };
+    let res = run(base().build()).await.unwrap();
+    assert_eq!(res.aggregates.body_checks.pass, 2);
+    let res = run(base().max_validate_bytes("100").build()).await.unwrap();
+    assert_eq!(res.aggregates.body_checks.fail, 2);
+}
+
+#[tokio::test]
+async fn e2e_query_params_are_expanded_and_encoded() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/query", addr);
+
+    let args = RunArgs::builder(url)
+        .concurrency(2)
+        .requests(10)
+        .progress_every(0)
+        .query_param("id={{seq}}")
+        .query_param("q=a b&c")
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.completed, 10);
+    assert_eq!(res.aggregates.status_class.c2xx, 10);
+}
+
+#[tokio::test]
+async fn e2e_user_agent_default_flag_and_header_override() {
+    let addr = spawn_test_server().await;
+    let base = RunArgs::builder(format!("http://{}/ua/{}", addr, DEFAULT_USER_AGENT))
+        .concurrency(1)
+        .requests(3)
+        .progress_every(0)
+        .build();
+
+    let res = run(base.clone()).await.unwrap();
+    assert_eq!(res.aggregates.status_class.c2xx, 3);
+
+    let mut custom = base.clone();
+    custom.url = format!("http://{}/ua/custom-agent", addr);
+    custom.user_agent = "custom-agent".into();
+    let res = run(custom).await.unwrap();
+    assert_eq!(res.aggregates.status_class.c2xx, 3);
+
+    let mut header_wins = base;
+    header_wins.url = format!("http://{}/ua/from-header", addr);
+    header_wins.user_agent = "ignored-agent".into();
+    header_wins.headers = vec!["User-Agent: from-header".into()];
+    let res = run(header_wins).await.unwrap();
+    assert_eq!(res.aggregates.status_class.c2xx, 3);
+}
+
+#[tokio::test]
+async fn e2e_warmup_requests_are_excluded_from_stats() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/ok", addr);
+
+    let args = RunArgs::builder(url)
+        .concurrency(2)
+        .requests(20)
+        .progress_every(0)
+        .warmup(5)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.warmup_discarded, 5);
+    assert_eq!(res.completed, 20);
+    assert_eq!(res.aggregates.status_class.c2xx, 20);
+    assert_eq!(res.aggregates.latency_micros.len(), 20);
+}
+
+#[tokio::test]
+async fn e2e_stages_report_per_stage_and_combined() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/ok", addr);
+
+    let args = RunArgs::builder(url)
+        .concurrency(1)
+        .progress_every(0)
+        .stages("1:100ms,3:100ms")
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.stages.len(), 2);
+    assert_eq!(res.stages[0].concurrency, 1);
+    assert_eq!(res.stages[1].concurrency, 3);
+    assert_eq!(res.concurrency, 3);
+    let per_stage: u64 = res.stages.iter().map(|s| s.completed).sum();
+    assert_eq!(res.completed, per_stage);
+    assert_eq!(res.aggregates.status_class.c2xx, res.completed);
+    let report = render_report(&res);
+    assert!(report.contains("stage 2: concurrency=3"));
+    assert!(res.knee.is_some());
+    assert!(report.contains("\nknee: stage "));
+    assert!(report.contains("  recommended concurrency: "));
+}
+
+#[tokio::test]
+async fn e2e_expect_header_counts_pass_and_fail() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/cached", addr);
+
+    let args = RunArgs::builder(url)
+        .concurrency(2)
+        .requests(6)
+        .progress_every(0)
+        .expect_header("Cache-Control: no-store")
+        .expect_header("ETag")
+        .expect_header("X-Missing")
+        .expect_header("cache-control: public")
+        .build();
+
+    let res = run(args).await.unwrap();
+    let checks = &res.aggregates.header_checks;
+    assert_eq!(checks["Cache-Control"].pass, 6);
+    assert_eq!(checks["ETag"].pass, 6);
+    assert_eq!(checks["X-Missing"].fail, 6);
+    assert_eq!(checks["cache-control"].fail, 6);
+    assert_eq!(res.aggregates.validation_failures(), 12);
+    assert!(render_report(&res).contains("header_checks:"));
+}
+
+#[tokio::test]
+async fn e2e_stream_body_file_uploads_whole_file() {
+    let addr = spawn_test_server().await;
+    let size = 256 * 1024;
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("big.bin");
+    std::fs::write(&path, vec![b'x'; size]).unwrap();
+
+    let args = RunArgs::builder(format!("http://{}/len/{}", addr, size))
+        .method("PUT")
+        .concurrency(2)
+        .requests(4)
+        .stream_body_file(path.to_str().unwrap())
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.aggregates.status_class.c2xx, 4);
+    assert_eq!(res.aggregates.bytes_sent, 4 * size as u64);
+}
+
+#[tokio::test]
+async fn e2e_capture_slow_requests() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/sleep", addr);
+
+    let args = RunArgs::builder(url)
+        .concurrency(3)
+        .requests(3)
+        .capture_slow_over("100ms")
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    let samples = &res.aggregates.slow_samples;
+    assert_eq!(samples.len(), 3);
+    assert!(samples.iter().all(|s| s.status == Some(200)));
+    assert!(samples.iter().all(|s| s.body_snippet == "slow"));
+    assert!(samples.iter().all(|s| s.latency_micros >= 100_000));
+    assert!(render_report(&res).contains("slow_requests:"));
+}
+
+#[tokio::test]
+async fn e2e_top_slow_lists_the_slowest_requests() {
+    let addr = spawn_test_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let paths = dir.path().join("paths.txt");
+    std::fs::write(&paths, "/ok\n/sleep\n").unwrap();
+
+    // four of the eight requests sleep; the three slowest are all among them
+    let args = RunArgs::builder(format!("http://{}/", addr))
+        .concurrency(2)
+        .requests(8)
+        .paths_file(paths.to_str().unwrap())
+        .top_slow(3)
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.top_slow.len(), 3);
+    assert!(res.top_slow.iter().all(|t| t.latency_micros >= 250_000));
+    assert!(res.top_slow.iter().all(|t| t.status == Some(200)));
+    assert!(res
+        .top_slow
+        .windows(2)
+        .all(|w| w[0].latency_micros >= w[1].latency_micros));
+    assert!(res.top_slow.iter().all(|t| t.at_sec <= res.elapsed_sec));
+    assert!(render_report(&res).contains("\ntop_slow:\n  - seq="));
+}
+
+#[tokio::test]
+async fn e2e_latency_budget_times_each_phase() {
+    let addr = spawn_test_server().await;
+    let args = RunArgs::builder(format!("http://{}/ok", addr))
+        .once(true)
+        .latency_budget(true)
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    let w = res.once.as_ref().unwrap().waterfall.clone().unwrap();
+    assert_eq!(w.error, None);
+    assert!(w.dns_ms.is_some() && w.connect_ms.is_some());
+    assert!(w.ttfb_ms.is_some() && w.transfer_ms.is_some());
+    // plain http has no TLS phase
+    assert_eq!(w.tls_ms, None);
+    assert!(w.response_bytes > 2);
+    let report = render_report(&res);
+    assert!(report.contains("latency_budget (the request again, on its own connection):\n  dns "));
+    assert!(report.contains("\n  ttfb "));
+    assert!(report.contains("\n  total "));
+}
+
+#[tokio::test]
+async fn e2e_once_prints_the_single_exchange() {
+    let addr = spawn_test_server().await;
+    let args = RunArgs::builder(format!("http://{}/ok", addr))
+        .once(true)
+        .header("Authorization: Bearer s3cret")
+        .header("X-Trace: abc")
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.completed, 1);
+    let once = res.once.as_ref().unwrap();
+    assert_eq!(once.status, Some(200));
+    assert_eq!(once.body_bytes, 2);
+    assert_eq!(once.body_preview, "ok");
+    let report = render_report(&res);
+    assert!(report.starts_with("== Single request ==\nGET http://"));
+    assert!(report.contains("> authorization: Bearer <redacted>\n"));
+    assert!(report.contains("> x-trace: abc\n"));
+    assert!(report.contains("status: 200 OK\nversion: HTTP/1.1\n"));
+    assert!(report.contains("\ntiming: headers_ms="));
+    assert!(report.ends_with("body: 2 bytes\nok\n"));
+    assert!(!report.contains("s3cret"));
+}
+
+#[tokio::test]
+async fn e2e_cycles_run_until_every_worker_made_k_passes() {
+    let addr = spawn_test_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let paths = dir.path().join("paths.txt");
+    std::fs::write(&paths, "/ok?p=a\n/ok?p=b\n").unwrap();
+
+    let args = RunArgs::builder(format!("http://{}/", addr))
+        .concurrency(3)
+        .cycles(4)
+        .paths_file(paths.to_str().unwrap())
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    let c = res.cycles.as_ref().unwrap();
+    assert_eq!(c.target, 4);
+    assert_eq!(c.min, 4);
+    assert!(c.max >= 4 && c.avg >= 4.0);
+    assert!(res.completed >= 3 * 4 * 2);
+    assert!(render_report(&res).contains("\ncycles: target=4 min=4 max="));
+}
+
+/// TLS server on 127.0.0.1 with a fresh self-signed certificate: answers every request
+/// with an empty 200 and closes. Returns its address and the certificate to trust.
+async fn spawn_tls_server() -> (SocketAddr, tokio_rustls::rustls::RootCertStore) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::rustls::{self, pki_types::PrivateKeyDer};
+
+    let key = rcgen::generate_simple_self_signed(vec!["127.0.0.1".to_string()]).unwrap();
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(key.cert.der().clone()).unwrap();
+    let config = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .unwrap()
+    .with_no_client_auth()
+    .with_single_cert(
+        vec![key.cert.der().clone()],
+        PrivateKeyDer::Pkcs8(key.signing_key.serialize_der().into()),
+    )
+    .unwrap();
+    let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                let Ok(mut tls) = acceptor.accept(stream).await else {
+                    return;
+                };
+                let mut buf = [0u8; 1024];
+                let _ = tls.read(&mut buf).await;
+                let _ = tls
+                    .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+                    .await;
+                let _ = tls.shutdown().await;
+            });
+        }
+    });
+    (addr, roots)
+}
+
+#[tokio::test]
+async fn e2e_measure_tls_resumption_resumes_primed_sessions() {
+    let (addr, roots) = spawn_tls_server().await;
+    let url = reqwest::Url::parse(&format!("https://{addr}/")).unwrap();
+
+    let t = measure_tls_resumption(
+        &url,
+        3,
+        roots,
+        Duration::from_secs(5),
+        HistConfig::default(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(t.full.latency_micros.len(), 3);
+    assert_eq!(t.resumed.latency_micros.len(), 3);
+    assert_eq!(t.resumed_count, 3);
+
+    // the bundled roots do not trust the test certificate
+    let err = run(RunArgs::builder(url.as_str())
+        .requests(1)
+        .measure_tls_resumption(2)
+        .progress_every(0)
+        .build())
+    .await
+    .unwrap_err();
+    assert!(format!("{err}").contains("TLS handshake with 127.0.0.1 failed"));
+
+    let addr = spawn_test_server().await;
+    let mut res = run(RunArgs::builder(format!("http://{addr}/ok"))
+        .requests(1)
+        .progress_every(0)
+        .build())
+    .await
+    .unwrap();
+    res.tls_resumption = Some(t);
+    let report = render_report(&res);
+    assert!(report
+        .contains("\ntls_resumption (handshake only, new connection each):\n  full: count=3 "));
+    assert!(report.contains("\n  resumed_by_server: 3 of 3\n  saving: p50_ms="));
+}
+
+#[tokio::test]
+async fn e2e_measure_preflight_records_options_separately() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/cors", addr);
+
+    let args = RunArgs::builder(url)
+        .method("POST")
+        .concurrency(2)
+        .requests(5)
+        .header("X-Custom: 1")
+        .measure_preflight(true)
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.aggregates.status_class.c2xx, 5);
+    let pre = res.aggregates.preflight.as_ref().unwrap();
+    assert_eq!(pre.status_exact.get(&204), Some(&5));
+    assert_eq!(pre.latency_micros.len(), 5);
+    let out = render_report(&res);
+    assert!(out.contains("preflight_latency_ms:"));
+}
+
+#[tokio::test]
+async fn e2e_duration_run_aborts_stuck_workers_after_grace() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/hang", addr);
+
+    let args = RunArgs::builder(url)
+        .concurrency(2)
+        .duration("100ms")
+        .timeout("30s")
+        .shutdown_grace("200ms")
+        .progress_every(0)
+        .build();
+
+    let started = std::time::Instant::now();
+    let res = run(args).await.unwrap();
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(res.aborted_workers, 2);
+    assert_eq!(res.completed, 0);
+    assert!(render_report(&res).contains("aborted_workers: 2"));
+}
+
+#[tokio::test]
+async fn e2e_worker_panics_are_counted_not_swallowed() {
+    let handles = vec![
+        tokio::spawn(async {}),
+        tokio::spawn(async { panic!("boom") }),
+        tokio::spawn(async {}),
+    ];
+    let mut panics = Vec::new();
+    join_workers(handles, &mut panics).await;
+    assert_eq!(panics, vec!["worker 1 panicked: boom".to_string()]);
+
+    let addr = spawn_test_server().await;
+    let mut res = run(RunArgs::builder(format!("http://{}/ok", addr))
+        .concurrency(1)
+        .requests(1)
+        .progress_every(0)
+        .build())
+    .await
+    .unwrap();
+    assert_eq!(res.worker_panics, 0);
+    assert!(!render_report(&res).contains("worker_panics"));
+    res.worker_panics = 1;
+    assert!(render_report(&res).contains("worker_panics: 1"));
+}
+
+#[tokio::test]
+async fn e2e_idempotency_key_is_unique_per_request() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/idem", addr);
+
+    let args = RunArgs::builder(url.clone())
+        .method("POST")
+        .concurrency(4)
+        .requests(40)
+        .json(r#"{"amount":1}"#)
+        .idempotency_key("Idempotency-Key")
+        .progress_every(0)
+        .build();
+    let res = run(args).await.unwrap();
+    assert_eq!(res.aggregates.status_exact.get(&200), Some(&40));
+
+    // without the flag the header is absent
+    let args = RunArgs::builder(url).requests(2).progress_every(0).build();
+    let res = run(args).await.unwrap();
+    assert_eq!(res.aggregates.status_exact.get(&400), Some(&2));
+}
+
+#[tokio::test]
+async fn e2e_header_values_are_templated_per_request() {
+    let addr = spawn_test_server().await;
+    let before = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+
+    let args = RunArgs::builder(format!("http://{}/record?run=headers", addr))
+        .concurrency(4)
+        .requests(20)
+        .header("X-Record: {{seq}} {{uuid}} {{now_ms}}")
+        .progress_every(0)
+        .build();
+    let res = run(args).await.unwrap();
+    assert_eq!(res.aggregates.status_class.c2xx, 20);
+
+    let seen = RECORDED.lock().unwrap()["headers"].clone();
+    let distinct: std::collections::BTreeSet<_> = seen.iter().collect();
+    assert_eq!(distinct.len(), 20);
+    let mut seqs = Vec::new();
+    for v in &seen {
+        let parts: Vec<&str> = v.split(' ').collect();
+        seqs.push(parts[0].parse::<u64>().unwrap());
+        assert_eq!(parts[1].len(), 36);
+        assert!(parts[2].parse::<u64>().unwrap() >= before);
+    }
+    seqs.sort_unstable();
+    assert_eq!(seqs, (1..=20).collect::<Vec<_>>());
+
+    // a templated Idempotency-Key is a fresh UUID per request
+    let args = RunArgs::builder(format!("http://{}/idem", addr))
+        .requests(10)
+        .header("Idempotency-Key: {{uuid}}")
+        .progress_every(0)
+        .build();
+    let res = run(args).await.unwrap();
+    assert_eq!(res.aggregates.status_exact.get(&200), Some(&10));
+}
+
+#[tokio::test]
+async fn e2e_connection_close_responses_are_tallied() {
+    let addr = spawn_test_server().await;
+    let base = |path: &str| {
+        RunArgs::builder(format!("http://{}{path}", addr))
+            .concurrency(2)
+            .requests(6)
+            .progress_every(0)
+            .build()
+    };
+
+    let res = run(base("/close")).await.unwrap();
+    assert_eq!(res.aggregates.status_class.c2xx, 6);
+    assert_eq!(res.aggregates.connection_close_responses, 6);
+    assert!(render_report(&res).contains("connection_close_responses: 6 (100.0% of responses;"));
+
+    let res = run(base("/ok")).await.unwrap();
+    assert_eq!(res.aggregates.connection_close_responses, 0);
+    assert!(!render_report(&res).contains("connection_close_responses"));
+}
+
+#[tokio::test]
+async fn e2e_first_request_per_worker_counts_as_connection_setup() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/ok", addr);
+
+    let args = RunArgs::builder(url)
+        .concurrency(3)
+        .requests(30)
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.aggregates.connection_setup_micros.len(), 3);
+    assert_eq!(res.aggregates.steady_state_latency().len(), 27);
+    let out = render_report(&res);
+    assert!(out.contains("connection_setup_ms:"));
+    assert!(out.contains("request_ms:"));
+}
+
+#[tokio::test]
+async fn e2e_sample_interval_records_timeline_and_soak_stats() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/ok", addr);
+
+    let args = RunArgs::builder(url)
+        .concurrency(2)
+        .duration("350ms")
+        .sample_interval("100ms")
+        .soak(true)
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert!(res.samples.len() >= 3, "{:?}", res.samples);
+    assert_eq!(
+        res.samples.iter().map(|s| s.requests).sum::<u64>(),
+        res.completed
+    );
+    assert!(res.samples.windows(2).all(|w| w[0].t_sec < w[1].t_sec));
+    assert!(res.soak.is_some());
+    let out = render_report(&res);
+    assert!(out.contains("timeline:"));
+    assert!(out.contains("p99_drift_ms_per_hour:"));
+}
+
+#[tokio::test]
+async fn e2e_heatmap_buckets_count_every_windowed_request() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/ok", addr);
+
+    let args = RunArgs::builder(url)
+        .concurrency(2)
+        .duration("250ms")
+        .sample_interval("100ms")
+        .heatmap_bucket("1ms")
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.heatmap_bucket_ms, Some(1.0));
+    for sm in &res.samples {
+        assert_eq!(sm.buckets.iter().sum::<u64>(), sm.requests);
+    }
+    let csv = render_heatmap_csv(&res.samples, 1.0);
+    let mut lines = csv.lines();
+    assert!(lines.next().unwrap().starts_with("t_sec,0"));
+    assert_eq!(lines.count(), res.samples.len());
+}
+
+#[tokio::test]
+async fn e2e_burst_alternates_load_and_idle() {
+    let addr = spawn_test_server().await;
+
+    // on 0-100ms, off until 250ms, on 250-350ms, off until the 500ms deadline
+    let args = RunArgs::builder(format!("http://{}/ok", addr))
+        .concurrency(2)
+        .duration("500ms")
+        .burst("on=100ms,off=150ms")
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.bursts.len(), 2, "{:?}", res.bursts);
+    assert!(res.bursts.iter().all(|b| b.requests > 0));
+    assert!((res.bursts[1].start_sec - 0.25).abs() < 1e-9);
+    assert_eq!(
+        res.bursts.iter().map(|b| b.requests).sum::<u64>(),
+        res.completed
+    );
+    assert!(render_report(&res).contains("\nbursts:\n  burst 1: start=0.0s on=0.1s"));
+}
+
+#[tokio::test]
+async fn e2e_until_stable_stops_without_a_request_count() {
+    let addr = spawn_test_server().await;
+
+    // a huge tolerance settles on the second window
+    let args = RunArgs::builder(format!("http://{}/ok", addr))
+        .concurrency(2)
+        .until_stable("100ms", 10_000.0)
+        .progress_every(0)
+        .build();
+    let res = run(args).await.unwrap();
+    let st = res.stable.as_ref().unwrap();
+    let n = st.after_requests.unwrap();
+    assert!(n > 0 && n <= res.completed);
+    assert!(st.after_sec.unwrap() >= 0.19 && res.elapsed_sec < 5.0);
+    assert!(res.samples.len() >= 2);
+    assert!(render_report(&res).contains("stable: p99 within 10000% over 0.1s windows after "));
+
+    // the cap ends the run before a second window closes
+    let args = RunArgs::builder(format!("http://{}/ok", addr))
+        .requests(3)
+        .until_stable("10s", 5.0)
+        .progress_every(0)
+        .build();
+    let res = run(args).await.unwrap();
+    assert_eq!(res.stable.as_ref().unwrap().after_requests, None);
+    assert!(render_report(&res).contains("stable: not stabilized"));
+}
+
+#[tokio::test]
+async fn e2e_until_stable_gives_up_after_the_default_cap() {
+    let addr = spawn_test_server().await;
+
+    // one request per 200ms timeout leaves every other 50ms window empty, so p99 never
+    // gets two windows in a row to compare
+    let args = RunArgs::builder(format!("http://{}/hang", addr))
+        .concurrency(1)
+        .timeout("200ms")
+        .until_stable("50ms", 5.0)
+        .progress_every(0)
+        .build();
+    let started = std::time::Instant::now();
+    let res = run(args).await.unwrap();
+    assert!(started.elapsed() < Duration::from_secs(10));
+    let st = res.stable.as_ref().unwrap();
+    assert_eq!(st.after_requests, None);
+    assert_eq!(st.default_cap_sec, Some(3.0));
+    assert!(render_report(&res).contains("; stopped at the default cap of 60 windows, 3s)"));
+}
+
+#[tokio::test]
+async fn e2e_json_respects_user_content_type() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/vnd", addr);
+
+    let args = RunArgs::builder(url.clone())
+        .method("POST")
+        .requests(3)
+        .header("content-type: application/vnd.api+json")
+        .json(r#"{"data":{}}"#)
+        .progress_every(0)
+        .build();
+    let res = run(args).await.unwrap();
+    assert_eq!(res.aggregates.status_exact.get(&200), Some(&3));
+
+    // without the header the default application/json is sent
+    let args = RunArgs::builder(url)
+        .method("POST")
+        .requests(1)
+        .json(r#"{"data":{}}"#)
+        .progress_every(0)
+        .build();
+    let res = run(args).await.unwrap();
+    assert_eq!(res.aggregates.status_exact.get(&415), Some(&1));
+}
+
+#[tokio::test]
+async fn e2e_scenario_runs_steps_in_order_with_per_step_results() {
+    let addr = spawn_test_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("journey.yaml");
+    std::fs::write(
+        &path,
+        r#"
+steps:
+  - name: login
+    method: POST
+    path: /echo_json
+    body: {"user": "demo"}
+    expect_status: 200
+  - name: list
+    path: /ok
+  - name: broken
+    path: /fail
+    expect_status: 200
+"#,
+    )
+    .unwrap();
+
+    let args = RunArgs::builder(format!("http://{}/", addr))
+        .concurrency(1)
+        .requests(9)
+        .scenario(path.to_str().unwrap())
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.completed, 9);
+    let names: Vec<_> = res.steps.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, ["login", "list", "broken"]);
+    for st in &res.steps {
+        assert_eq!(st.aggregates.latency_micros.len(), 3);
+    }
+    assert_eq!(res.steps[0].aggregates.status_exact.get(&200), Some(&3));
+    assert_eq!(res.steps[0].aggregates.status_checks.pass, 3);
+    assert_eq!(res.steps[2].aggregates.status_checks.fail, 3);
+    assert_eq!(res.aggregates.validation_failures(), 3);
+    assert!(render_report(&res).contains("step 1: login (POST"));
+}
+
+#[tokio::test]
+async fn e2e_scenario_captures_token_for_later_steps() {
+    let addr = spawn_test_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("auth.yaml");
+    std::fs::write(
+        &path,
+        r#"
+steps:
+  - name: login
+    method: POST
+    path: /login
+    capture:
+      token: $.auth.token
+      missing: $.auth.refresh
+  - name: whoami
+    path: /whoami
+    headers:
+      Authorization: "Bearer {{token}}"
+    expect_status: 200
+"#,
+    )
+    .unwrap();
+
+    let args = RunArgs::builder(format!("http://{}/", addr))
+        .concurrency(2)
+        .requests(8)
+        .scenario(path.to_str().unwrap())
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.steps[1].aggregates.status_exact.get(&200), Some(&4));
+    assert_eq!(res.steps[1].aggregates.status_checks.fail, 0);
+    assert_eq!(res.aggregates.capture_failures.get("missing"), Some(&4));
+    assert!(!res.aggregates.capture_failures.contains_key("token"));
+    assert!(render_report(&res).contains("capture_failures:"));
+}
+
+#[tokio::test]
+async fn e2e_scenario_capture_named_like_a_placeholder_keeps_its_value() {
+    let addr = spawn_test_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("auth.yaml");
+    std::fs::write(
+        &path,
+        r#"
+steps:
+  - name: login
+    method: POST
+    path: /login
+    capture:
+      seq: $.auth.token
+  - name: whoami
+    path: /whoami
+    headers:
+      Authorization: "Bearer {{seq}}"
+    expect_status: 200
+"#,
+    )
+    .unwrap();
+
+    let res = run(RunArgs::builder(format!("http://{}/", addr))
+        .concurrency(1)
+        .requests(4)
+        .scenario(path.to_str().unwrap())
+        .progress_every(0)
+        .build())
+    .await
+    .unwrap();
+    assert_eq!(res.steps[1].aggregates.status_exact.get(&200), Some(&2));
+}
+
+#[tokio::test]
+async fn e2e_seed_makes_random_choices_reproducible() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/record", addr);
+
+    for (run_id, seed) in [("seed-a", 42), ("seed-b", 42), ("seed-c", 7)] {
+        let args = RunArgs::builder(url.clone())
+            .concurrency(1)
+            .requests(5)
+            .query_param(format!("run={run_id}"))
+            .query_param("r={{rand}}")
+            .seed(seed)
+            .progress_every(0)
+            .build();
+        let res = run(args).await.unwrap();
+        assert_eq!(res.seed, Some(seed));
+    }
+
+    let recorded = RECORDED.lock().unwrap();
+    assert_eq!(recorded["seed-a"].len(), 5);
+    assert_eq!(recorded["seed-a"], recorded["seed-b"]);
+    assert_ne!(recorded["seed-a"], recorded["seed-c"]);
+}
+
+#[tokio::test]
+async fn e2e_max_rps_per_worker_paces_requests() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/ok", addr);
+
+    let args = RunArgs::builder(url.clone())
+        .concurrency(2)
+        .requests(6)
+        .max_rps_per_worker(20.0)
+        .progress_every(0)
+        .build();
+    let res = run(args).await.unwrap();
+    assert_eq!(res.aggregates.status_class.c2xx, 6);
+    // 3 requests per worker need at least 2 gaps of 50ms
+    assert!(res.elapsed_sec >= 0.1, "elapsed {}", res.elapsed_sec);
+
+    // with a duration the cap bounds the total
+    let args = RunArgs::builder(url)
+        .concurrency(2)
+        .duration("300ms")
+        .max_rps_per_worker(10.0)
+        .progress_every(0)
+        .build();
+    let res = run(args).await.unwrap();
+    assert!(res.completed <= 8, "completed {}", res.completed);
+}
+
+#[tokio::test]
+async fn e2e_concurrency_profile_parks_workers_above_the_target() {
+    let addr = spawn_test_server().await;
+
+    let res = run(RunArgs::builder(format!("http://{}/ok", addr))
+        .concurrency_profile("linear:1..4")
+        .duration("2100ms")
+        .progress_every(0)
+        .build())
+    .await
+    .unwrap();
+    assert_eq!(res.concurrency, 4);
+    let targets: Vec<u64> = res.concurrency_timeline.iter().map(|p| p.target).collect();
+    assert_eq!(targets, [2, 4]);
+    for p in &res.concurrency_timeline {
+        assert_eq!(p.active, p.target, "{p:?}");
+    }
+    assert!(render_report(&res).contains("t=1s target=2 active=2"));
+}
+
+#[tokio::test]
+async fn e2e_mock_clock_makes_pacing_exact() {
+    let addr = spawn_test_server().await;
+
+    // one sleeping task, so the schedule is exactly 0s, 0.5s, ..., 9.5s
+    let clock = Arc::new(MockClock::new());
+    let started = std::time::Instant::now();
+    let res = run(RunArgs::builder(format!("http://{}/ok", addr))
+        .concurrency(1)
+        .duration("10s")
+        .max_rps_per_worker(2.0)
+        .clock(clock.clone())
+        .progress_every(0)
+        .build())
+    .await
+    .unwrap();
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(res.completed, 20);
+    assert_eq!(clock.elapsed(), Duration::from_millis(9500));
+    assert_eq!(res.elapsed_sec, 9.5);
+
+    // Retry-After: 1 pauses exactly a second per response
+    let res = run(RunArgs::builder(format!("http://{}/busy", addr))
+        .concurrency(1)
+        .requests(3)
+        .respect_retry_after(true)
+        .clock(Arc::new(MockClock::new()))
+        .progress_every(0)
+        .build())
+    .await
+    .unwrap();
+    assert_eq!(res.aggregates.backoffs, 3);
+    assert_eq!(res.aggregates.backoff_micros, 3_000_000);
+}
+
+#[tokio::test]
+async fn e2e_live_stats_follow_the_run() {
+    let addr = spawn_test_server().await;
+    let live = std::sync::Arc::new(endpoint_tester::LiveStats::new());
+
+    let args = RunArgs::builder(format!("http://{}/fail", addr))
+        .requests(7)
+        .live(live.clone())
+        .progress_every(0)
+        .build();
+    run(args).await.unwrap();
+
+    assert_eq!(live.completed(), 7);
+    assert_eq!(live.errors(), 7);
+    assert_eq!(live.take_window().len(), 7);
+    assert!(live.take_window().is_empty());
+}
+
+#[tokio::test]
+async fn e2e_body_size_sends_generated_payload() {
+    let addr = spawn_test_server().await;
+
+    for fill in [BodyFill::Random, BodyFill::Zero] {
+        let args = RunArgs::builder(format!("http://{}/len/{}", addr, 64 * 1024))
+            .method("POST")
+            .concurrency(2)
+            .requests(4)
+            .body_size("64k")
+            .body_fill(fill)
+            .progress_every(0)
+            .build();
+
+        let res = run(args).await.unwrap();
+        assert_eq!(res.aggregates.status_class.c2xx, 4);
+        assert_eq!(res.aggregates.bytes_sent, 4 * 64 * 1024);
+    }
+}
+
+#[tokio::test]
+async fn e2e_capture_etag_counts_304_cache_hits() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/etag", addr);
+
+    let args = RunArgs::builder(url)
+        .concurrency(1)
+        .requests(5)
+        .capture_etag(true)
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.aggregates.status_exact.get(&200), Some(&1));
+    assert_eq!(res.aggregates.status_exact.get(&304), Some(&4));
+    assert_eq!(res.aggregates.cache_hits, 4);
+    assert_eq!(res.aggregates.cache_misses, 0);
+    assert!(render_report(&res).contains("etag_cache: hits=4 misses=0 hit_rate=100.00%"));
+}
+
+#[tokio::test]
+async fn e2e_measure_handshake_reports_fresh_connections() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/ok", addr);
+
+    let args = RunArgs::builder(url)
+        .concurrency(1)
+        .requests(2)
+        .measure_handshake(3)
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    let h = res.handshake.as_ref().expect("handshake aggregates");
+    assert_eq!(h.latency_micros.len(), 3);
+    assert_eq!(h.status_exact.get(&200), Some(&3));
+    assert_eq!(res.aggregates.latency_micros.len(), 2);
+    assert!(render_report(&res).contains("handshake_ms"));
+}
+
+#[tokio::test]
+async fn e2e_rps_timeline_buckets_completions_per_second() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/ok", addr);
+
+    let args = RunArgs::builder(url)
+        .concurrency(2)
+        .duration("1200ms")
+        .rps_timeline(true)
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.rps_timeline.len(), 2);
+    assert_eq!(res.rps_timeline.iter().sum::<u64>(), res.completed);
+    assert!(render_report(&res).contains("rps_timeline: 2s"));
+}
+
+#[tokio::test]
+async fn e2e_repeated_header_sends_every_value() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/multi", addr);
+
+    let args = RunArgs::builder(url)
+        .concurrency(1)
+        .requests(2)
+        .header("X-Multi: a")
+        .header("X-Multi: b")
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.aggregates.status_exact.get(&200), Some(&2));
+}
+
+#[tokio::test]
+async fn e2e_json_progress_does_not_change_results() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/ok", addr);
+
+    let args = RunArgs::builder(url)
+        .concurrency(2)
+        .requests(6)
+        .progress_every(2)
+        .progress_format(ProgressFormat::Json)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.completed, 6);
+}
+
+#[tokio::test]
+async fn e2e_respect_retry_after_pauses_worker_until_deadline() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/busy", addr);
+
+    let args = RunArgs::builder(url)
+        .concurrency(1)
+        .duration("500ms")
+        .respect_retry_after(true)
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    // Retry-After: 1 outlasts the run, so the single worker sends once
+    assert_eq!(res.completed, 1);
+    assert_eq!(res.aggregates.backoffs, 1);
+    assert!(res.aggregates.backoff_micros >= 300_000);
+    assert!(render_report(&res).contains("retry_after_backoff: count=1"));
+}
+
+#[tokio::test]
+async fn e2e_huge_retry_after_is_clamped_to_the_deadline() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/busy-forever", addr);
+
+    let args = RunArgs::builder(url)
+        .concurrency(1)
+        .duration("300ms")
+        .respect_retry_after(true)
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.completed, 1);
+    assert_eq!(res.worker_panics, 0);
+    assert_eq!(res.aggregates.backoffs, 1);
+}
+
+#[tokio::test]
+async fn e2e_summary_output_is_one_line() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/fail", addr);
+
+    let args = RunArgs::builder(url.clone())
+        .concurrency(1)
+        .requests(3)
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    let line = render_report_summary(&res);
+    assert_eq!(line.lines().count(), 1);
+    assert!(line.starts_with(&format!("url={url} completed=3 rps=")));
+    assert!(line.trim_end().ends_with("errors=0 5xx=3"));
+}
+
+#[tokio::test]
+async fn e2e_paths_file_reports_per_path() {
+    let addr = spawn_test_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let paths = dir.path().join("paths.txt");
+    std::fs::write(&paths, "/ok\n\n# server errors\nfail\n").unwrap();
+
+    let args = RunArgs::builder(format!("http://{}/", addr))
+        .concurrency(1)
+        .requests(4)
+        .paths_file(paths.to_str().unwrap())
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.paths.len(), 2);
+    assert_eq!(res.paths[0].name, "/ok");
+    assert_eq!(res.paths[0].aggregates.status_exact.get(&200), Some(&2));
+    assert_eq!(res.paths[1].url, format!("http://{}/fail", addr));
+    assert_eq!(res.paths[1].aggregates.status_exact.get(&500), Some(&2));
+    assert!(res.steps.is_empty());
+    assert!(render_report(&res).contains("\npaths:\n  /ok requests=2 2xx=2"));
+}
+
+#[tokio::test]
+async fn e2e_method_body_reports_per_entry() {
+    let addr = spawn_test_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let body = dir.path().join("create.json");
+    std::fs::write(&body, "{ \"a\": 1 }\n").unwrap();
+    let entry = format!("POST={}", body.display());
+
+    // /len/7 only accepts the compacted {"a":1}, so the bodiless GET gets a 400
+    let args = RunArgs::builder(format!("http://{}/len/7", addr))
+        .concurrency(1)
+        .requests(6)
+        .method_body(entry.as_str())
+        .method_body(entry.as_str())
+        .method_body("GET")
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.methods.len(), 3);
+    assert_eq!(res.methods[0].name, format!("POST {}", body.display()));
+    assert_eq!(res.methods[0].aggregates.status_exact.get(&200), Some(&2));
+    assert_eq!(res.methods[2].name, "GET");
+    assert_eq!(res.methods[2].aggregates.status_exact.get(&400), Some(&2));
+    assert!(res.paths.is_empty());
+    assert!(render_report(&res).contains("\nmethods:\n  POST "));
+}
+
+#[tokio::test]
+async fn e2e_hosts_file_splits_load_by_weight() {
+    let a = spawn_test_server().await;
+    let b = spawn_test_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let hosts = dir.path().join("hosts.yaml");
+    // only host a sends the User-Agent /ua/shard expects
+    std::fs::write(
+        &hosts,
+        format!(
+            "- url: http://{a}\n  weight: 3\n  headers:\n    User-Agent: shard\n- url: http://{b}/ignored\n"
+        ),
+    )
+    .unwrap();
+
+    let args = RunArgs::builder(format!("http://{a}/ua/shard"))
+        .concurrency(4)
+        .requests(40)
+        .hosts_file(hosts.to_str().unwrap())
+        .select(Select::Sequential)
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.hosts.len(), 2);
+    assert_eq!(res.hosts[0].name, a.to_string());
+    assert_eq!(res.hosts[0].aggregates.status_exact.get(&200), Some(&30));
+    assert_eq!(res.hosts[1].url, format!("http://{b}/ua/shard"));
+    assert_eq!(res.hosts[1].aggregates.status_exact.get(&400), Some(&10));
+    assert!(render_report(&res).contains(&format!("\nhosts:\n  {a} requests=30 2xx=30")));
+}
+
+async fn path_counts(select: Select, concurrency: usize, requests: u64) -> Vec<u64> {
+    let addr = spawn_test_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let paths = dir.path().join("paths.txt");
+    std::fs::write(&paths, "/ok?p=a\n/ok?p=b\n/ok?p=c\n").unwrap();
+
+    let args = RunArgs::builder(format!("http://{}/", addr))
+        .concurrency(concurrency)
+        .requests(requests)
+        .paths_file(paths.to_str().unwrap())
+        .select(select)
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    res.paths
+        .iter()
+        .map(|p| p.aggregates.latency_micros.len())
+        .collect()
+}
+
+#[tokio::test]
+async fn e2e_select_round_robin_spreads_evenly() {
+    // every worker cycles from its own offset, so each path is off by at most one per worker
+    let counts = path_counts(Select::RoundRobin, 3, 60).await;
+    assert_eq!(counts.iter().sum::<u64>(), 60);
+    assert!(counts.iter().all(|&c| (17..=23).contains(&c)), "{counts:?}");
+
+    let counts = path_counts(Select::RoundRobin, 1, 9).await;
+    assert_eq!(counts, vec![3, 3, 3]);
+}
+
+#[tokio::test]
+async fn e2e_select_sequential_hits_every_path_in_turn() {
+    let counts = path_counts(Select::Sequential, 4, 30).await;
+    assert_eq!(counts, vec![10, 10, 10]);
+}
+
+#[tokio::test]
+async fn e2e_hmac_sign_adds_signature_header() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/sig", addr);
+
+    let args = RunArgs::builder(url)
+        .method("POST")
+        .json(r#"{"a":1}"#)
+        .concurrency(1)
+        .requests(2)
+        .hmac_sign("header=X-Signature,secret=k")
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.aggregates.status_exact.get(&200), Some(&2));
+    assert_eq!(
+        res.credential_sources.get("hmac_sign").map(String::as_str),
+        Some("--hmac-sign")
+    );
+}
+
+#[tokio::test]
+async fn e2e_measure_ttfb_records_header_wait_and_keeps_body_for_samples() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/ok", addr);
+
+    let args = RunArgs::builder(url)
+        .concurrency(1)
+        .requests(3)
+        .measure_ttfb(true)
+        .capture_slow_over("0ms")
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    let a = &res.aggregates;
+    assert_eq!(a.ttfb_micros.len(), 3);
+    assert!(a.ttfb_micros.max() <= a.latency_micros.max());
+    assert_eq!(a.slow_samples.len(), 3);
+    assert_eq!(a.slow_samples[0].body_snippet, "ok");
+    assert!(render_report(&res).contains("ttfb_ms:"));
+}
+
+#[tokio::test]
+async fn e2e_open_loop_sends_scheduled_requests() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/ok", addr);
+
+    let args = RunArgs::builder(url)
+        .concurrency(8)
+        .requests(20)
+        .open_loop(200.0)
+        .seed(7)
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.completed, 20);
+    assert_eq!(res.aggregates.corrected_micros.len(), 20);
+    assert!((1..=8).contains(&res.max_in_flight));
+    let out = render_report(&res);
+    assert!(out.contains("open_loop: arrival_rate=200"));
+    assert_eq!(res.target_rps, Some(200.0));
+    assert!(out.contains("rate: target_rps=200.00 achieved_rps="));
+}
+
+#[tokio::test]
+async fn e2e_open_loop_counts_queueing_in_corrected_latency() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/sleep", addr);
+
+    // one slot, 250ms responses, arrivals every ~10ms: later requests wait for the slot
+    let args = RunArgs::builder(url)
+        .concurrency(1)
+        .requests(3)
+        .open_loop(100.0)
+        .seed(7)
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    let a = &res.aggregates;
+    assert_eq!(res.max_in_flight, 1);
+    assert!(a.corrected_micros.max() >= a.latency_micros.max() + 200_000);
+}
+
+#[tokio::test]
+async fn e2e_max_inflight_drops_or_blocks_arrivals() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/sleep", addr);
+
+    // 250ms responses, arrivals every ~10ms, one allowed in flight
+    let base = || {
+        RunArgs::builder(url.clone())
+            .concurrency(4)
+            .open_loop(100.0)
+            .seed(7)
+            .progress_every(0)
+    };
+    let res = run(base()
+        .requests(10)
+        .max_inflight(1, OnOverload::Drop)
+        .build())
+    .await
+    .unwrap();
+    let o = res.overload.as_ref().unwrap();
+    assert!(o.dropped >= 5);
+    assert_eq!(res.completed + o.dropped, 10);
+    assert_eq!(res.max_in_flight, 1);
+    assert!(render_report(&res).contains(&format!(
+        "overload: max_inflight=1 dropped={} blocked=0",
+        o.dropped
+    )));
+
+    let res = run(base()
+        .requests(3)
+        .max_inflight(1, OnOverload::Block)
+        .build())
+    .await
+    .unwrap();
+    let o = res.overload.as_ref().unwrap();
+    assert_eq!(res.completed, 3);
+    assert_eq!((o.dropped, o.blocked), (0, 2));
+    assert_eq!(res.max_in_flight, 1);
+}
+
+#[tokio::test]
+async fn e2e_paced_run_reports_corrected_latency() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/sleep", addr);
+
+    let args = RunArgs::builder(url)
+        .concurrency(1)
+        .requests(2)
+        .max_rps_per_worker(20.0)
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    let a = &res.aggregates;
+    assert_eq!(a.latency_micros.len(), 2);
+    assert!(a.corrected_micros.len() > 2);
+    let out = render_report(&res);
+    assert!(out.contains("corrected_latency_ms:"));
+    // 250ms responses hold the worker well under its 20 rps cap
+    assert_eq!(res.target_rps, Some(20.0));
+    assert!(out.contains("rate: target_rps=20.00 achieved_rps="));
+    assert!(out.contains("(below target: likely limited downstream)"));
+}
+
+#[tokio::test]
+async fn e2e_compare_methods_runs_each_method_separately() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/ok", addr);
+
+    let args = RunArgs::builder(url)
+        .concurrency(1)
+        .requests(3)
+        .compare_method("get")
+        .compare_method("HEAD")
+        .progress_every(0)
+        .build();
+
+    let results = compare_methods(&args).await.unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].method, "GET");
+    assert_eq!(results[1].method, "HEAD");
+    assert!(results.iter().all(|r| r.completed == 3));
+
+    let table = render_method_comparison(&results);
+    assert!(table.lines().any(|l| l.starts_with("GET ")));
+    assert!(table.lines().any(|l| l.starts_with("HEAD ")));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn e2e_unix_socket_target() {
+    let dir = tempfile::tempdir().unwrap();
+    let sock = dir.path().join("app.sock");
+    let listener = tokio::net::UnixListener::bind(&sock).unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let service = hyper::service::service_fn(|req: Request<Incoming>| async move {
+                    // the --url host only ends up in the Host header
+                    let host = req.headers().get("host").cloned();
+                    let status = if host.is_some_and(|h| h == "sidecar.local") {
+                        StatusCode::OK
+                    } else {
+                        StatusCode::BAD_REQUEST
+                    };
+                    Ok::<_, hyper::Error>(
+                        Response::builder()
+                            .status(status)
+                            .body(Full::<Bytes>::from("").boxed())
+                            .unwrap(),
+                    )
+                });
+                let _ = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    });
+
+    let args = RunArgs::builder("http://sidecar.local/health")
+        .concurrency(2)
+        .requests(4)
+        .unix_socket(sock.to_str().unwrap())
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.aggregates.status_exact.get(&200), Some(&4));
+    assert!(render_report(&res).contains("unix_socket: "));
+}
+
+#[tokio::test]
+async fn e2e_warm_compare_reports_cold_and_warm_passes() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/ok", addr);
+
+    let args = RunArgs::builder(url)
+        .concurrency(1)
+        .requests(5)
+        .warm_compare(3)
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    let cold = res.cold.as_ref().expect("cold pass");
+    assert_eq!(cold.latency_micros.len(), 3);
+    assert_eq!(res.aggregates.latency_micros.len(), 5);
+    assert_eq!(res.completed, 5);
+    let report = render_report(&res);
+    assert!(report.contains("  cold: requests=3"));
+    assert!(report.contains("  warm: requests=5"));
+    assert!(report.contains("improvement: p50 x"));
+}
+
+#[tokio::test]
+async fn e2e_socket_options_show_in_report() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/ok", addr);
+
+    let res = run(RunArgs::builder(url.clone())
+        .requests(2)
+        .progress_every(0)
+        .build())
+    .await
+    .unwrap();
+    assert!(render_report(&res).contains("socket: tcp_nodelay=on tcp_keepalive=off\n"));
+
+    let res = run(RunArgs::builder(url)
+        .requests(2)
+        .tcp_nodelay(false)
+        .tcp_keepalive("30s")
+        .progress_every(0)
+        .build())
+    .await
+    .unwrap();
+    assert_eq!(res.completed, 2);
+    assert!(render_report(&res).contains("socket: tcp_nodelay=off tcp_keepalive=30s\n"));
+}
+
+#[tokio::test]
+async fn e2e_address_family_ipv4_resolves_localhost() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://localhost:{}/ok", addr.port());
+
+    let res = run(RunArgs::builder(url)
+        .requests(3)
+        .address_family(AddressFamily::Ipv4)
+        .progress_every(0)
+        .build())
+    .await
+    .unwrap();
+    assert_eq!(res.aggregates.status_exact.get(&200), Some(&3));
+    assert_eq!(res.aggregates.remote_ipv4, 3);
+    assert!(render_report(&res).contains("address_family: ipv4 (responses over ipv4=3 ipv6=0)\n"));
+}
+
+#[tokio::test]
+async fn e2e_apdex_threshold_scores_fast_endpoint() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/ok", addr);
+
+    let args = RunArgs::builder(url)
+        .requests(4)
+        .apdex_threshold("10s")
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    let ap = res.apdex.as_ref().expect("apdex");
+    assert_eq!(ap.satisfied, 4);
+    assert_eq!(ap.score, 1.0);
+    assert!(render_report(&res)
+        .contains("apdex (T=10000ms): 1.000 satisfied=4 tolerating=0 frustrated=0"));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn e2e_request_limit_is_exact_under_contention() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/ok", addr);
+
+    let args = RunArgs::builder(url)
+        .concurrency(128)
+        .requests(2000)
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.completed, 2000);
+    assert_eq!(res.sent, 2000);
+    assert_eq!(res.aggregates.latency_micros.len(), 2000);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn e2e_reports_the_runtime_thread_count() {
+    let addr = spawn_test_server().await;
+    let res = run(RunArgs::builder(format!("http://{}/ok", addr))
+        .requests(4)
+        .progress_every(0)
+        .build())
+    .await
+    .unwrap();
+    assert_eq!(res.runtime_threads, 3);
+    assert!(render_report(&res).contains("\nruntime_threads: 3\n"));
+}
+
+#[tokio::test]
+async fn e2e_expect_100_continue_upload_succeeds() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/len/2048", addr);
+
+    let args = RunArgs::builder(url)
+        .method("POST")
+        .body_size("2k")
+        .expect_100_continue()
+        .requests(3)
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert!(res.expect_100_continue);
+    assert_eq!(res.aggregates.status_exact.get(&200), Some(&3));
+    assert!(render_report(&res).contains("expect_100_continue: header sent"));
+}
+
+#[tokio::test]
+async fn e2e_multipart_form_upload() {
+    let addr = spawn_test_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("data.txt");
+    std::fs::write(&file, "file-contents").unwrap();
+
+    let args = RunArgs::builder(format!("http://{}/form", addr))
+        .method("POST")
+        .form_field("title=hello")
+        .form_file(format!("upload=@{}", file.display()))
+        .requests(3)
+        .progress_every(0)
+        .build();
+
+    let res = run(args).await.unwrap();
+    assert_eq!(res.aggregates.status_exact.get(&200), Some(&3));
+    // "hello" + "file-contents" per request
+    assert_eq!(res.aggregates.bytes_sent, 3 * 18);
+}
+
+#[tokio::test]
+async fn e2e_retries_recover_flaky_endpoint() {
+    let addr = spawn_test_server().await;
+
+    let args = RunArgs::builder(format!("http://{}/flaky?key=retry-ok&fail=2", addr))
+        .requests(1)
+        .retries(3)
+        .retry_backoff("1ms", BackoffMode::Exponential, "20ms")
+        .progress_every(0)
+        .build();
+    let res = run(args).await.unwrap();
+    assert_eq!(res.aggregates.status_exact.get(&200), Some(&1));
+    assert_eq!(res.aggregates.retried.get(&2), Some(&1));
+    assert_eq!(res.aggregates.retries_exhausted, 0);
+    assert!(render_report(&res).contains("retries: 2x=1 exhausted=0"));
+
+    let args = RunArgs::builder(format!("http://{}/fail", addr))
+        .requests(2)
+        .retries(1)
+        .retry_backoff("1ms", BackoffMode::Fixed, "10s")
+        .progress_every(0)
+        .build();
+    let res = run(args).await.unwrap();
+    assert_eq!(res.aggregates.status_exact.get(&500), Some(&2));
+    assert_eq!(res.aggregates.retried.get(&1), Some(&2));
+    assert_eq!(res.aggregates.retries_exhausted, 2);
+}
+
+#[tokio::test]
+async fn e2e_run_streaming_yields_each_request() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/ok", addr);
+
+    let args = RunArgs::builder(url)
+        .concurrency(2)
+        .requests(6)
+        .progress_every(0)
+        .build();
+
+    let (mut events, handle) = run_streaming(args);
+    let mut outcomes = Vec::new();
+    while let Some(o) = events.next().await {
+        outcomes.push(o);
+    }
+    let res = handle.await.unwrap().unwrap();
+
+    assert_eq!(outcomes.len(), 6);
+    assert!(outcomes.iter().all(|o| o.status == Ok(200) && o.step == 0));
+    let mut seqs: Vec<u64> = outcomes.iter().map(|o| o.seq).collect();
+    seqs.sort();
+    assert_eq!(seqs, vec![1, 2, 3, 4, 5, 6]);
+    assert_eq!(res.completed, 6);
+}
+
+#[tokio::test]
+async fn e2e_run_streaming_waits_for_a_slow_reader() {
+    let addr = spawn_test_server().await;
+    let n = EVENTS_CHANNEL_CAPACITY as u64 + 50;
+
+    let args = RunArgs::builder(format!("http://{}/ok", addr))
+        .concurrency(2)
+        .requests(n)
+        .progress_every(0)
+        .build();
+    let (mut events, handle) = run_streaming(args);
+    // nothing is read yet: the workers stop at a full channel instead of buffering more
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(!handle.is_finished());
+
+    let mut count = 0;
+    while events.next().await.is_some() {
+        count += 1;
+    }
+    assert_eq!(count, n);
+    assert_eq!(handle.await.unwrap().unwrap().completed, n);
+}
+
+#[tokio::test]
+async fn e2e_ndjson_stream_ends_with_a_summary_record() {
+    let addr = spawn_test_server().await;
+
+    let args = RunArgs::builder(format!("http://{}/ok", addr))
+        .concurrency(2)
+        .requests(5)
+        .progress_every(0)
+        .build();
+    let (mut events, handle) = run_streaming(args);
+    let mut lines = Vec::new();
+    while let Some(o) = events.next().await {
+        lines.push(o.to_ndjson_line());
+    }
+    let res = handle.await.unwrap().unwrap();
+    let summary = render_ndjson_summary(&res).unwrap();
+    assert!(summary.ends_with('\n') && !summary.trim_end().contains('\n'));
+    lines.push(summary);
+
+    let records: Vec<serde_json::Value> = lines
+        .iter()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(records.len(), 6);
+    for r in &records[..5] {
+        assert_eq!(r["type"], "request");
+        assert_eq!(r["status"], 200);
+    }
+    let last = &records[5];
+    assert_eq!(last["type"], "summary");
+    assert_eq!(last["completed"], 5);
+    assert_eq!(last["aggregates"]["status_class"]["c2xx"], 5);
+}
+
+#[tokio::test]
+async fn e2e_inject_trace_header_streams_trace_ids() {
+    let addr = spawn_test_server().await;
+
+    let args = RunArgs::builder(format!("http://{}/traceparent", addr))
+        .concurrency(2)
+        .requests(6)
+        .inject_trace_header(true)
+        .progress_every(0)
+        .build();
+    let (mut events, handle) = run_streaming(args);
+    let mut ids = std::collections::BTreeSet::new();
+    while let Some(o) = events.next().await {
+        assert_eq!(o.status, Ok(200));
+        let id = o.trace_id.clone().unwrap();
+        assert_eq!(id.len(), 32);
+        assert!(o.to_json_line().contains(&format!("\"trace_id\":\"{id}\"")));
+        ids.insert(id);
+    }
+    handle.await.unwrap().unwrap();
+    assert_eq!(ids.len(), 6);
+}
+
+#[tokio::test]
+async fn e2e_recycle_client_every_opens_fresh_connections() {
+    // counts accepted connections
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let accepted = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = accepted.clone();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            tokio::spawn(async move {
+                let service = hyper::service::service_fn(|_req: Request<Incoming>| async move {
+                    Ok::<_, hyper::Error>(Response::new(Full::<Bytes>::from("ok")))
+                });
+                let _ = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    });
+
+    // each worker rebuilds before its 4th, 7th and 10th request
+    let res = run(RunArgs::builder(format!("http://{addr}/"))
+        .concurrency(2)
+        .requests_per_worker(10)
+        .recycle_client_every(3)
+        .progress_every(0)
+        .build())
+    .await
+    .unwrap();
+    assert_eq!(res.aggregates.status_exact.get(&200), Some(&20));
+    assert_eq!(res.client_recycles, 6);
+    assert!(accepted.load(std::sync::atomic::Ordering::SeqCst) >= 8);
+    assert!(render_report(&res).contains("client_recycles: 6 (every 3 requests per worker)\n"));
+}
+
+#[tokio::test]
+async fn e2e_drained_bodies_keep_connections_alive() {
+    // counts accepted connections; every reply carries a 1 MiB body
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let accepted = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = accepted.clone();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            tokio::spawn(async move {
+                let service = hyper::service::service_fn(|_req: Request<Incoming>| async move {
+                    Ok::<_, hyper::Error>(Response::new(Full::<Bytes>::from(vec![b'x'; 1 << 20])))
+                });
+                let _ = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    });
+    let url = format!("http://{addr}/");
+
+    let res = run(RunArgs::builder(url.clone())
+        .concurrency(1)
+        .requests(20)
+        .progress_every(0)
+        .build())
+    .await
+    .unwrap();
+    assert_eq!(res.aggregates.status_exact.get(&200), Some(&20));
+    // a connection may come back to the pool just after the next request looked for one
+    assert!(accepted.swap(0, std::sync::atomic::Ordering::SeqCst) <= 5);
+    assert!(render_report(&res).contains("response_bodies: drained\n"));
+
+    let res = run(RunArgs::builder(url)
+        .concurrency(1)
+        .requests(20)
+        .no_body_download(true)
+        .progress_every(0)
+        .build())
+    .await
+    .unwrap();
+    assert_eq!(res.aggregates.status_exact.get(&200), Some(&20));
+    assert!(accepted.load(std::sync::atomic::Ordering::SeqCst) >= 15);
+    assert!(render_report(&res).contains("response_bodies: dropped unread (--no-body-download)\n"));
+}
+
+/// Promises a 1000-byte body, sends 10 bytes of it and hangs up.
+async fn spawn_truncating_server() -> SocketAddr {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf).await;
+                let _ = stream
+                    .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 1000\r\n\r\n0123456789")
+                    .await;
+            });
+        }
+    });
+    addr
+}
+
+/// Sends the headers at once, then one byte of the 100-byte body every 100ms.
+async fn spawn_dripping_server() -> SocketAddr {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf).await;
+                let head = b"HTTP/1.1 200 OK\r\ncontent-length: 100\r\n\r\n";
+                if stream.write_all(head).await.is_err() {
+                    return;
+                }
+                for _ in 0..100 {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    if stream.write_all(b"x").await.is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    addr
+}
+
+#[tokio::test]
+async fn e2e_hard_timeout_covers_body_reads() {
+    let addr = spawn_dripping_server().await;
+    let base = || {
+        RunArgs::builder(format!("http://{addr}/"))
+            .concurrency(1)
+            .requests(2)
+            .timeout("30s")
+            .hard_timeout("300ms")
+            .progress_every(0)
+    };
+
+    // the drain and --measure-ttfb both read the body
+    for args in [base().build(), base().measure_ttfb(true).build()] {
+        let started = std::time::Instant::now();
+        let res = run(args).await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(3));
+        assert_eq!(res.aggregates.net_errors.timeout, 2);
+        assert!(res.aggregates.status_exact.is_empty());
+    }
+}
+
+#[tokio::test]
+async fn e2e_truncated_bodies_count_as_body_errors() {
+    let addr = spawn_truncating_server().await;
+    let url = format!("http://{addr}/");
+    let base = || {
+        RunArgs::builder(url.clone())
+            .concurrency(1)
+            .requests(3)
+            .progress_every(0)
+    };
+
+    // the drain, --measure-ttfb, the body checks and --har-file each read the body
+    for args in [
+        base().build(),
+        base().measure_ttfb(true).build(),
+        base().success_body_contains("0123").build(),
+        base().har_sample_rate(1.0).build(),
+    ] {
+        let res = run(args).await.unwrap();
+        assert_eq!(res.aggregates.net_errors.body, 3);
+        assert!(res.aggregates.status_exact.is_empty());
+        assert_eq!(res.aggregates.body_checks.pass, 0);
+    }
+}
+
+#[tokio::test]
+async fn e2e_grpc_unary_calls_use_grpc_status() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let service = hyper::service::service_fn(|req: Request<Incoming>| async move {
+                    let grpc = req
+                        .headers()
+                        .get("content-type")
+                        .is_some_and(|v| v == "application/grpc+proto");
+                    let path = req.uri().path().to_string();
+                    let body = req.into_body().collect().await?.to_bytes();
+                    // a single uncompressed message of the advertised length
+                    let framed = body.len() >= 5
+                        && body[0] == 0
+                        && u32::from_be_bytes([body[1], body[2], body[3], body[4]]) as usize
+                            == body.len() - 5;
+                    let resp = Response::builder()
+                        .status(StatusCode::OK)
+                        .header("content-type", "application/grpc+proto");
+                    if path == "/demo.Svc/Unavailable" {
+                        // trailers-only reply: the status travels in the headers
+                        return Ok::<_, hyper::Error>(
+                            resp.header("grpc-status", "14")
+                                .body(Full::<Bytes>::from("").boxed())
+                                .unwrap(),
+                        );
+                    }
+                    let status = if grpc && framed { "0" } else { "3" };
+                    let mut trailers = hyper::HeaderMap::new();
+                    trailers.insert("grpc-status", status.parse().unwrap());
+                    Ok::<_, hyper::Error>(
+                        resp.body(
+                            Full::<Bytes>::from(body)
+                                .with_trailers(async move { Some(Ok(trailers)) })
+                                .boxed(),
+                        )
+                        .unwrap(),
+                    )
+                });
+                let _ =
+                    hyper::server::conn::http2::Builder::new(hyper_util::rt::TokioExecutor::new())
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+            });
+        }
+    });
+
+    let dir = tempfile::tempdir().unwrap();
+    let msg = dir.path().join("req.bin");
+    std::fs::write(&msg, [0x0a, 0x03, b'b', b'o', b'b']).unwrap();
+
+    let args = RunArgs::builder(format!("http://{addr}/demo.Svc/Hello"))
+        .grpc(true)
+        .body_file(msg.to_str().unwrap())
+        .requests(3)
+        .progress_every(0)
+        .build();
+    let res = run(args).await.unwrap();
+    assert_eq!(res.aggregates.status_exact.get(&200), Some(&3));
+    assert_eq!(res.aggregates.grpc_status.get(&0), Some(&3));
+    assert!(render_report(&res).contains("grpc_status_counts:\n  0: 3\n"));
+
+    let args = RunArgs::builder(format!("http://{addr}/demo.Svc/Unavailable"))
+        .grpc(true)
+        .requests(2)
+        .progress_every(0)
+        .build();
+    let res = run(args).await.unwrap();
+    assert_eq!(res.aggregates.status_exact.get(&200), Some(&2));
+    assert_eq!(res.aggregates.grpc_status.get(&14), Some(&2));
+}
+
+#[tokio::test]
+async fn e2e_grpc_stream_reset_mid_body_is_a_body_error() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                // half a message, then the body fails and the stream is reset
+                let service = hyper::service::service_fn(|_req: Request<Incoming>| async move {
+                    let (tx, rx) = tokio::sync::mpsc::channel(2);
+                    tokio::spawn(async move {
+                        let data = Bytes::from_static(&[0, 0, 0, 0, 9, 1]);
+                        let _ = tx.send(Ok(hyper::body::Frame::data(data))).await;
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        let _ = tx
+                            .send(Err(std::io::Error::other("backend went away")))
+                            .await;
+                    });
+                    Ok::<_, hyper::Error>(
+                        Response::builder()
+                            .status(StatusCode::OK)
+                            .header("content-type", "application/grpc+proto")
+                            .body(http_body_util::StreamBody::new(
+                                tokio_stream::wrappers::ReceiverStream::new(rx),
+                            ))
+                            .unwrap(),
+                    )
+                });
+                let _ =
+                    hyper::server::conn::http2::Builder::new(hyper_util::rt::TokioExecutor::new())
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+            });
+        }
+    });
+
+    let res = run(RunArgs::builder(format!("http://{addr}/demo.Svc/Hello"))
+        .grpc(true)
+        .requests(2)
+        .progress_every(0)
+        .build())
+    .await
+    .unwrap();
+    assert_eq!(res.aggregates.net_errors.body, 2);
+    assert!(res.aggregates.status_exact.is_empty());
+    assert_eq!(res.aggregates.grpc_status_missing, 0);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn e2e_payload_command_per_request_or_cached() {
+    let addr = spawn_test_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let runs = dir.path().join("runs");
+    let cmd = format!("echo run >> {}; printf hello", runs.display());
+    let count_runs = || std::fs::read_to_string(&runs).unwrap().lines().count();
+
+    let args = RunArgs::builder(format!("http://{}/len/5", addr))
+        .method("POST")
+        .concurrency(2)
+        .requests(6)
+        .payload_command(cmd.clone(), false)
+        .progress_every(0)
+        .build();
+    let res = run(args).await.unwrap();
+    assert_eq!(res.aggregates.status_exact.get(&200), Some(&6));
+    // one check at startup, then one per request
+    assert_eq!(count_runs(), 7);
+
+    std::fs::remove_file(&runs).unwrap();
+    let args = RunArgs::builder(format!("http://{}/len/5", addr))
+        .method("POST")
+        .concurrency(2)
+        .requests(6)
+        .payload_command(cmd, true)
+        .progress_every(0)
+        .build();
+    let res = run(args).await.unwrap();
+    assert_eq!(res.aggregates.status_exact.get(&200), Some(&6));
+    // startup check plus once per worker
+    assert_eq!(count_runs(), 3);
+}
+
+#[tokio::test]
+async fn e2e_report_shows_shares_of_completed() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/fail", addr);
+
+    let res = run(RunArgs::builder(url).requests(4).progress_every(0).build())
+        .await
+        .unwrap();
+    let report = render_report(&res);
+    assert!(report.contains("  5xx: 4 (100.0%)\n"));
+    assert!(report.contains("  2xx: 0 (0.0%)\n"));
+    assert!(report.contains("success_rate: 0.00% (2xx and 3xx)\n"));
+    assert!(report.contains("error_rate: 100.00% (network errors and 5xx)\n"));
+
+    // nothing completed: no division by zero
+    let mut empty = res.clone();
+    empty.completed = 0;
+    let report = render_report(&empty);
+    assert!(report.contains("  5xx: 4 (0.0%)\n"));
+    assert!(!report.contains("NaN"));
+}
+
+#[tokio::test]
+async fn e2e_hard_timeout_caps_requests_below_reqwest_timeout() {
+    let addr = spawn_test_server().await;
+    let url = format!("http://{}/hang", addr);
+
+    let args = RunArgs::builder(url)
+        .concurrency(2)
+        .requests(2)
+        .timeout("30s")
+        .hard_timeout("100ms")
+        .capture_slow_over("50ms")
+        .progress_every(0)
+        .build();
+
+    let started = std::time::Instant::now();
+    let res = run(args).await.unwrap();
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(res.aggregates.net_errors.timeout, 2);
+    let sample = res.aggregates.slow_samples.back().unwrap();
+    assert!(sample.error.as_deref().unwrap().contains("--hard-timeout"));
+}
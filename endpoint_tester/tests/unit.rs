@@ -1,7 +1,18 @@
 // tests/unit.rs
+use clap::Parser;
 use endpoint_tester::{
-    parse_duration, parse_header, parse_http_method, Aggregates, NetErrCounts, NetErrKind,
-    StatusClassCounts,
+    apdex, append_path, closes_connection, colorize_report, deserialize_histogram, encode_url,
+    expand_env, expand_template, expand_vars, find_knee, grpc_frame, gzip_bytes, iso8601_millis,
+    lookup_json_path, merge_top_slow, parse_args, parse_burst, parse_byte_size,
+    parse_concurrency_profile, parse_duration, parse_expect_header, parse_form_file, parse_header,
+    parse_hmac_sign, parse_http_method, parse_initial_failures, parse_json_path, parse_method_body,
+    parse_query_param, parse_retry_after, parse_server_timing, parse_size_range, parse_stages,
+    parse_trace_line, render_har, render_heatmap_csv, render_waterfall, serialize_histogram,
+    sign_request, soak_stats, sparkline, traceparent, url_warnings, weighted_slots, Aggregates,
+    Args, BackoffMode, ConcurrencyProfile, HarEntry, HistConfig, HmacAlgo, IntervalSample,
+    JsonPathSeg, LogLevel, NetErrCounts, NetErrKind, ProgressFormat, RequestOutcome, RetryPolicy,
+    RunArgs, SlowSample, StageResult, StatusClassCounts, TemplateVars, TopSlowRequest, Waterfall,
+    ERROR_EXAMPLE_CAP, ERROR_EXAMPLE_OVERFLOW, MAX_RETRY_AFTER, SLOW_SAMPLE_CAP,
 };
 use reqwest::Method;
 use std::time::Duration;
@@ -26,6 +37,137 @@ fn parse_header_invalid() {
     assert!(parse_header(": value").is_none());
 }
 
+#[test]
+fn parse_expect_header_value_or_presence() {
+    assert_eq!(
+        parse_expect_header("Cache-Control: no-store"),
+        Some(("Cache-Control".to_string(), Some("no-store".to_string())))
+    );
+    assert_eq!(
+        parse_expect_header("ETag"),
+        Some(("ETag".to_string(), None))
+    );
+    assert_eq!(
+        parse_expect_header("ETag:"),
+        Some(("ETag".to_string(), None))
+    );
+    assert!(parse_expect_header("").is_none());
+    assert!(parse_expect_header(": x").is_none());
+}
+
+#[test]
+fn parse_query_param_ok_and_invalid() {
+    assert_eq!(
+        parse_query_param("id={{seq}}"),
+        Some(("id".to_string(), "{{seq}}".to_string()))
+    );
+    assert_eq!(
+        parse_query_param("filter=a=b"),
+        Some(("filter".to_string(), "a=b".to_string()))
+    );
+    assert_eq!(
+        parse_query_param("empty="),
+        Some(("empty".to_string(), String::new()))
+    );
+    assert!(parse_query_param("novalue").is_none());
+    assert!(parse_query_param("=x").is_none());
+}
+
+#[test]
+fn waterfall_bars_follow_the_phases() {
+    let w = Waterfall {
+        dns_ms: Some(1.0),
+        connect_ms: Some(1.0),
+        tls_ms: None,
+        ttfb_ms: Some(6.0),
+        transfer_ms: Some(2.0),
+        response_bytes: 120,
+        error: None,
+    };
+    assert_eq!(w.total_ms(), 10.0);
+    let lines: Vec<String> = render_waterfall(&w).lines().map(str::to_string).collect();
+    assert_eq!(lines.len(), 6);
+    let bar = |line: &str| line.split('|').nth(1).unwrap().to_string();
+    assert_eq!(
+        bar(&lines[1]),
+        format!("{}{}", "#".repeat(4), " ".repeat(36))
+    );
+    assert_eq!(
+        bar(&lines[3]),
+        format!("{}{}{}", " ".repeat(8), "#".repeat(24), " ".repeat(8))
+    );
+    assert!(lines[3].starts_with("  ttfb          6.000 ms  60.0% |"));
+    assert_eq!(lines[5], "  total        10.000 ms (120 response bytes)");
+
+    // a phase too short for a mark of its own still gets one
+    let w = Waterfall {
+        dns_ms: Some(0.001),
+        ttfb_ms: Some(100.0),
+        ..Waterfall::default()
+    };
+    assert!(render_waterfall(&w).contains("|#  "));
+}
+
+#[test]
+fn expand_env_reads_variables_and_defaults() {
+    let lookup = |name: &str| (name == "TOKEN").then(|| "s3cret".to_string());
+    assert_eq!(
+        expand_env(r#"{"token":"{{env:TOKEN}}"}"#, lookup),
+        Ok(r#"{"token":"s3cret"}"#.to_string())
+    );
+    // a set variable wins over the default
+    assert_eq!(
+        expand_env("{{env:TOKEN:fallback}}", lookup),
+        Ok("s3cret".to_string())
+    );
+    assert_eq!(
+        expand_env("{{env:REGION:eu-west-1}}/{{env:EMPTY:}}", lookup),
+        Ok("eu-west-1/".to_string())
+    );
+    assert_eq!(
+        expand_env("a {{env:MISSING}} b", lookup),
+        Err("MISSING".to_string())
+    );
+    // other placeholders and unterminated ones are left alone
+    assert_eq!(
+        expand_env("{{seq}} {{env:TOKEN", lookup),
+        Ok("{{seq}} {{env:TOKEN".to_string())
+    );
+}
+
+#[test]
+fn expand_template_placeholders() {
+    let vars = TemplateVars {
+        seq: 7,
+        rand: 42,
+        uuid: uuid::Uuid::from_u128(1),
+        now_ms: 1_700_000_000_000,
+    };
+    assert_eq!(expand_template("id={{seq}}", &vars), "id=7");
+    assert_eq!(expand_template("{{rand}}-{{seq}}", &vars), "42-7");
+    assert_eq!(
+        expand_template("{{uuid}} at {{now_ms}}", &vars),
+        "00000000-0000-0000-0000-000000000001 at 1700000000000"
+    );
+    assert_eq!(expand_template("{{unknown}}", &vars), "{{unknown}}");
+    assert_eq!(expand_template("plain", &vars), "plain");
+}
+
+#[test]
+fn parse_stages_ok_and_invalid() {
+    let st = parse_stages("10:30s, 50:1m").unwrap();
+    assert_eq!(st.len(), 2);
+    assert_eq!(st[0].concurrency, 10);
+    assert_eq!(st[0].duration, Duration::from_secs(30));
+    assert_eq!(st[1].concurrency, 50);
+    assert_eq!(st[1].duration_target, "1m");
+
+    assert!(parse_stages("").is_none());
+    assert!(parse_stages("10").is_none());
+    assert!(parse_stages("0:10s").is_none());
+    assert!(parse_stages("10:forever").is_none());
+}
+
 #[test]
 fn parse_duration_ms_s_m_h() {
     assert_eq!(parse_duration("500ms"), Some(Duration::from_millis(500)));
@@ -49,6 +191,17 @@ fn parse_http_method_allowlist() {
     assert_eq!(parse_http_method("NOPE"), None);
 }
 
+#[test]
+fn parse_method_body_entries() {
+    assert_eq!(
+        parse_method_body("post=create.json"),
+        Some((Method::POST, Some("create.json".to_string())))
+    );
+    assert_eq!(parse_method_body("GET"), Some((Method::GET, None)));
+    assert_eq!(parse_method_body("DELETE="), Some((Method::DELETE, None)));
+    assert_eq!(parse_method_body("NOPE=x.json"), None);
+}
+
 #[test]
 fn status_class_counts() {
     let mut s = StatusClassCounts::default();
@@ -70,9 +223,11 @@ fn net_err_counts_total() {
     n.record(NetErrKind::Timeout);
     n.record(NetErrKind::Timeout);
     n.record(NetErrKind::Connect);
+    n.record(NetErrKind::Dns);
     assert_eq!(n.timeout, 2);
     assert_eq!(n.connect, 1);
-    assert_eq!(n.total(), 3);
+    assert_eq!(n.dns, 1);
+    assert_eq!(n.total(), 4);
 }
 
 #[test]
@@ -88,5 +243,847 @@ fn aggregates_recording_paths() {
     assert_eq!(a.status_class.c2xx, 1);
     assert_eq!(a.status_class.c5xx, 1);
     assert_eq!(a.net_errors.timeout, 1);
-    assert!(a.latency_micros.len() >= 1);
+    assert!(!a.latency_micros.is_empty());
+}
+
+#[test]
+fn aggregates_merge_adds_counts_and_histograms() {
+    let mut a = Aggregates::new().unwrap();
+    a.record_status(200);
+    a.record_error(NetErrKind::Connect);
+    a.record_latency(1000);
+
+    let mut b = Aggregates::new().unwrap();
+    b.record_status(200);
+    b.record_status(503);
+    b.record_latency(3000);
+
+    a.merge(&b).unwrap();
+    assert_eq!(a.status_exact.get(&200), Some(&2));
+    assert_eq!(a.status_exact.get(&503), Some(&1));
+    assert_eq!(a.status_class.c2xx, 2);
+    assert_eq!(a.status_class.c5xx, 1);
+    assert_eq!(a.net_errors.connect, 1);
+    assert_eq!(a.latency_micros.len(), 2);
+}
+
+#[test]
+fn run_args_builder_defaults_match_cli() {
+    let cli = Args::try_parse_from(["endpoint_tester", "--url", "http://x/"]).unwrap();
+    let from_cli = RunArgs::from(cli);
+    let built = RunArgs::builder("http://x/").build();
+    assert_eq!(format!("{from_cli:?}"), format!("{built:?}"));
+
+    let custom = RunArgs::builder("http://x/")
+        .method("POST")
+        .concurrency(8)
+        .requests(10)
+        .header("A: b")
+        .header("C: d")
+        .build();
+    assert_eq!(custom.method, "POST");
+    assert_eq!(custom.concurrency, 8);
+    assert_eq!(custom.requests, Some(10));
+    assert_eq!(custom.headers, vec!["A: b".to_string(), "C: d".to_string()]);
+}
+
+#[test]
+fn cli_url_optional_only_in_merge_and_trend_modes() {
+    assert!(Args::try_parse_from(["endpoint_tester"]).is_err());
+    let a = Args::try_parse_from(["endpoint_tester", "--merge", "a.json", "b.json"]).unwrap();
+    assert_eq!(a.merge, vec!["a.json".to_string(), "b.json".to_string()]);
+    assert!(a.url.is_none());
+    let a = Args::try_parse_from(["endpoint_tester", "--trend", "runs.jsonl"]).unwrap();
+    assert_eq!(a.trend.as_deref(), Some("runs.jsonl"));
+    assert_eq!(a.trend_last, 20);
+    assert!(a.url.is_none());
+}
+
+#[test]
+fn slow_samples_are_bounded_and_keep_the_latest() {
+    let mut a = Aggregates::new().unwrap();
+    for seq in 0..(SLOW_SAMPLE_CAP as u64 + 10) {
+        a.push_slow_sample(SlowSample {
+            seq,
+            latency_micros: 1,
+            status: Some(200),
+            error: None,
+            headers: vec![],
+            body_snippet: String::new(),
+        });
+    }
+    assert_eq!(a.slow_samples.len(), SLOW_SAMPLE_CAP);
+    assert_eq!(a.slow_samples.front().unwrap().seq, 10);
+}
+
+#[test]
+fn latency_per_kb_scales_by_body_size() {
+    let mut a = Aggregates::new().unwrap();
+    a.record_latency_per_kb(2_000, 2048);
+    a.record_latency_per_kb(2_000, 512);
+    a.record_latency_per_kb(2_000, 0);
+    let h = &a.latency_per_kb_micros;
+    assert_eq!(h.len(), 2);
+    assert_eq!(h.min(), 1_000);
+    assert!(h.equivalent(h.max(), 4_000));
+}
+
+#[test]
+fn parse_server_timing_reads_dur_per_metric() {
+    assert_eq!(
+        parse_server_timing(r#"db;dur=53.2, cache;desc="hit, warm; fast";dur=0.4, miss"#),
+        vec![("db".to_string(), 53.2), ("cache".to_string(), 0.4)]
+    );
+    assert_eq!(
+        parse_server_timing(r#"app ; DUR="7" ; desc=x"#),
+        vec![("app".to_string(), 7.0)]
+    );
+    assert_eq!(
+        parse_server_timing(r#"q;desc="say \"hi\", ok";dur=1"#),
+        vec![("q".to_string(), 1.0)]
+    );
+    assert!(parse_server_timing("db;dur=abc, x;dur=-1, ;dur=3, ").is_empty());
+}
+
+#[test]
+fn server_timing_histograms_round_trip_and_merge() {
+    let mut a = Aggregates::new().unwrap();
+    a.record_server_timing("db", 12.5);
+    a.record_server_timing("db", 20.0);
+    let mut b = Aggregates::new().unwrap();
+    b.record_server_timing("db", 1.0);
+    b.record_server_timing("app", 30.0);
+    let b: Aggregates = serde_json::from_str(&serde_json::to_string(&b).unwrap()).unwrap();
+    let app = &b.server_timing_micros["app"];
+    assert!(app.equivalent(app.max(), 30_000));
+    a.merge(&b).unwrap();
+    assert_eq!(a.server_timing_micros["db"].len(), 3);
+    assert_eq!(a.server_timing_micros["app"].len(), 1);
+    // older results have no server timings
+    let mut v = serde_json::to_value(&a).unwrap();
+    v.as_object_mut().unwrap().remove("server_timing_micros");
+    let old: Aggregates = serde_json::from_value(v).unwrap();
+    assert!(old.server_timing_micros.is_empty());
+}
+
+#[test]
+fn error_examples_are_capped_per_kind() {
+    let mut a = Aggregates::new().unwrap();
+    for i in 0..ERROR_EXAMPLE_CAP + 5 {
+        a.record_error_example(NetErrKind::Connect, &format!("refused #{i}"));
+    }
+    a.record_error_example(NetErrKind::Connect, "refused #0");
+    a.record_error_example(NetErrKind::Timeout, &"x".repeat(1000));
+    let connect = &a.error_examples["connect"];
+    assert_eq!(connect.len(), ERROR_EXAMPLE_CAP + 1);
+    assert_eq!(connect["refused #0"], 2);
+    assert_eq!(connect[ERROR_EXAMPLE_OVERFLOW], 5);
+    assert!(a.error_examples["timeout"].keys().all(|m| m.len() < 400));
+
+    let mut b = Aggregates::new().unwrap();
+    b.record_error_example(NetErrKind::Connect, "refused #1");
+    b.record_error_example(NetErrKind::Connect, "something new");
+    a.merge(&b).unwrap();
+    let connect = &a.error_examples["connect"];
+    assert_eq!(connect.len(), ERROR_EXAMPLE_CAP + 1);
+    assert_eq!(connect["refused #1"], 2);
+    assert_eq!(connect[ERROR_EXAMPLE_OVERFLOW], 6);
+}
+
+#[test]
+fn bounded_histogram_saturates_instead_of_dropping() {
+    let mut a = Aggregates::with_hist(HistConfig {
+        sigfigs: 2,
+        max_micros: Some(10_000),
+    })
+    .unwrap();
+    a.record_latency(500);
+    a.record_latency(50_000_000);
+    assert_eq!(a.latency_micros.len(), 2);
+    assert_eq!(a.latency_micros.sigfig(), 2);
+    assert!(a.latency_micros.max() < 50_000_000);
+}
+
+#[test]
+fn cli_quiet_and_verbose_map_to_log_levels() {
+    let parse = |extra: &[&str]| {
+        let mut argv = vec!["endpoint_tester", "--url", "http://x/"];
+        argv.extend_from_slice(extra);
+        Args::try_parse_from(argv).map(RunArgs::from)
+    };
+    assert_eq!(parse(&[]).unwrap().log_level, LogLevel::Normal);
+    assert_eq!(parse(&["--quiet"]).unwrap().log_level, LogLevel::Quiet);
+    assert_eq!(parse(&["--verbose"]).unwrap().log_level, LogLevel::Verbose);
+    assert!(parse(&["--quiet", "--verbose"]).is_err());
+}
+
+#[test]
+fn cli_progress_format_defaults_to_text() {
+    let parse = |extra: &[&str]| {
+        let mut argv = vec!["endpoint_tester", "--url", "http://x/"];
+        argv.extend_from_slice(extra);
+        Args::try_parse_from(argv).map(RunArgs::from)
+    };
+    assert_eq!(parse(&[]).unwrap().progress_format, ProgressFormat::Text);
+    let json = parse(&["--progress-format", "json"]).unwrap();
+    assert_eq!(json.progress_format, ProgressFormat::Json);
+    assert!(parse(&["--progress-format", "xml"]).is_err());
+}
+
+#[test]
+fn cli_worker_threads_must_be_positive() {
+    let parse = |extra: &[&str]| {
+        let mut argv = vec!["endpoint_tester", "--url", "http://x/"];
+        argv.extend_from_slice(extra);
+        Args::try_parse_from(argv)
+    };
+    assert_eq!(parse(&[]).unwrap().worker_threads, None);
+    assert_eq!(
+        parse(&["--worker-threads", "2"]).unwrap().worker_threads,
+        Some(2)
+    );
+    assert!(parse(&["--worker-threads", "0"]).is_err());
+}
+
+#[test]
+fn config_file_matches_the_same_flags_and_yields_to_the_cli() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("run.toml");
+    std::fs::write(
+        &path,
+        r#"
+url = "http://x/"
+concurrency = 20
+duration = "30s"
+header = ["A: b", "C: d"]
+auto-encode-url = true
+max_rps_per_worker = 2.5
+"#,
+    )
+    .unwrap();
+    let path = path.to_str().unwrap();
+    let (from_file, _) = parse_args(["endpoint_tester", "--config", path]).unwrap();
+    let cli = Args::try_parse_from([
+        "endpoint_tester",
+        "--url",
+        "http://x/",
+        "--concurrency",
+        "20",
+        "--duration",
+        "30s",
+        "--header",
+        "A: b",
+        "--header",
+        "C: d",
+        "--auto-encode-url",
+        "--max-rps-per-worker",
+        "2.5",
+    ])
+    .unwrap();
+    assert_eq!(
+        format!("{:?}", RunArgs::from(from_file)),
+        format!("{:?}", RunArgs::from(cli))
+    );
+
+    // flags win, lists included; the rest still comes from the file
+    let (args, _) = parse_args([
+        "endpoint_tester",
+        "--config",
+        path,
+        "--concurrency",
+        "3",
+        "--header",
+        "E: f",
+    ])
+    .unwrap();
+    assert_eq!(args.concurrency, 3);
+    assert_eq!(args.headers, vec!["E: f".to_string()]);
+    assert_eq!(args.duration.as_deref(), Some("30s"));
+}
+
+#[test]
+fn config_file_reads_yaml_and_rejects_unknown_options() {
+    let dir = tempfile::tempdir().unwrap();
+    let yaml = dir.path().join("run.yaml");
+    std::fs::write(&yaml, "url: http://x/\nrequests: 5\nquiet: false\n").unwrap();
+    let (args, _) = parse_args(["endpoint_tester", "--config", yaml.to_str().unwrap()]).unwrap();
+    assert_eq!(args.url.as_deref(), Some("http://x/"));
+    assert_eq!(args.requests, Some(5));
+    assert!(!args.quiet);
+
+    let bad = dir.path().join("bad.toml");
+    std::fs::write(&bad, "url = \"http://x/\"\nconcurency = 2\n").unwrap();
+    let err = parse_args(["endpoint_tester", "--config", bad.to_str().unwrap()]).unwrap_err();
+    assert!(format!("{err}").contains("unknown option concurency"));
+    assert!(parse_args(["endpoint_tester", "--config", "/nonexistent.toml"]).is_err());
+}
+
+#[test]
+fn steady_state_latency_excludes_connection_setup() {
+    let mut a = Aggregates::new().unwrap();
+    a.record_connection_setup(50_000);
+    a.record_connection_setup(40_000);
+    for _ in 0..8 {
+        a.record_latency(1_000);
+    }
+    assert_eq!(a.latency_micros.len(), 10);
+    assert_eq!(a.connection_setup_micros.len(), 2);
+    let steady = a.steady_state_latency();
+    assert_eq!(steady.len(), 8);
+    assert!(steady.max() < 2_000);
+}
+
+#[test]
+fn render_heatmap_csv_pads_rows_to_the_widest_window() {
+    let sample = |t: f64, buckets: Vec<u64>| IntervalSample {
+        t_sec: t,
+        width_sec: 1.0,
+        requests: buckets.iter().sum(),
+        p50_ms: 0.0,
+        p99_ms: 0.0,
+        buckets,
+    };
+    let csv = render_heatmap_csv(&[sample(1.0, vec![3, 1]), sample(2.0, vec![0, 2, 5])], 0.1);
+    assert_eq!(csv, "t_sec,0,0.1,0.2\n1.000,3,1,0\n2.000,0,2,5\n");
+}
+
+#[test]
+fn soak_stats_slope_and_throughput_cv() {
+    // p99 grows by 1ms every 10 minutes at steady throughput
+    let samples: Vec<IntervalSample> = (1..=6)
+        .map(|i| IntervalSample {
+            t_sec: i as f64 * 600.0,
+            width_sec: 600.0,
+            requests: 6000,
+            p50_ms: 5.0,
+            p99_ms: 10.0 + i as f64,
+            buckets: Vec::new(),
+        })
+        .collect();
+    let st = soak_stats(&samples).unwrap();
+    assert!((st.p99_drift_ms_per_hour - 6.0).abs() < 1e-9);
+    assert!(st.throughput_cv.abs() < 1e-9);
+
+    assert!(soak_stats(&samples[..1]).is_none());
+}
+
+#[test]
+fn json_path_parse_and_lookup() {
+    let segs = parse_json_path("$.data.items[1].id").unwrap();
+    assert_eq!(
+        segs,
+        vec![
+            JsonPathSeg::Key("data".into()),
+            JsonPathSeg::Key("items".into()),
+            JsonPathSeg::Index(1),
+            JsonPathSeg::Key("id".into()),
+        ]
+    );
+    let v = serde_json::json!({"data": {"items": [{"id": 1}, {"id": "b"}]}});
+    assert_eq!(lookup_json_path(&v, &segs), Some(&serde_json::json!("b")));
+    assert_eq!(
+        lookup_json_path(&v, &parse_json_path("$").unwrap()),
+        Some(&v)
+    );
+    assert!(lookup_json_path(&v, &parse_json_path("$.data.nope").unwrap()).is_none());
+
+    assert!(parse_json_path("data.items").is_none());
+    assert!(parse_json_path("$..x").is_none());
+    assert!(parse_json_path("$.a[x]").is_none());
+}
+
+#[test]
+fn expand_vars_replaces_known_names_only() {
+    let mut vars = std::collections::BTreeMap::new();
+    vars.insert("token".to_string(), "abc".to_string());
+    assert_eq!(
+        expand_vars("Bearer {{token}} {{other}}", &vars),
+        "Bearer abc {{other}}"
+    );
+}
+
+#[test]
+fn parse_byte_size_suffixes() {
+    assert_eq!(parse_byte_size("512"), Some(512));
+    assert_eq!(parse_byte_size("64k"), Some(64 * 1024));
+    assert_eq!(parse_byte_size("64KB"), Some(64 * 1024));
+    assert_eq!(parse_byte_size("1m"), Some(1 << 20));
+    assert_eq!(parse_byte_size(" 2 GB "), Some(2 << 30));
+    assert_eq!(parse_byte_size("1.5m"), None);
+    assert_eq!(parse_byte_size("k"), None);
+    assert_eq!(parse_byte_size(""), None);
+}
+
+#[test]
+fn parse_size_range_allows_open_ends() {
+    assert_eq!(parse_size_range("1k:64k"), Some((1024, 64 * 1024)));
+    assert_eq!(parse_size_range("100:"), Some((100, u64::MAX)));
+    assert_eq!(parse_size_range(":2"), Some((0, 2)));
+    assert_eq!(parse_size_range("5:5"), Some((5, 5)));
+    assert_eq!(parse_size_range("64k:1k"), None);
+    assert_eq!(parse_size_range("64k"), None);
+    assert_eq!(parse_size_range("x:1"), None);
+}
+
+#[test]
+fn iso8601_millis_formats_utc_dates() {
+    assert_eq!(iso8601_millis(0), "1970-01-01T00:00:00.000Z");
+    assert_eq!(iso8601_millis(951_782_400_000), "2000-02-29T00:00:00.000Z");
+    assert_eq!(
+        iso8601_millis(1_714_564_800_123),
+        "2024-05-01T12:00:00.123Z"
+    );
+}
+
+#[test]
+fn render_har_writes_har_1_2_entries() {
+    let ok = HarEntry {
+        started_unix_ms: 0,
+        method: "GET".to_string(),
+        url: "http://x/a?q=1&r=two".to_string(),
+        status: Some(200),
+        http_version: "HTTP/1.1".to_string(),
+        response_headers: vec![("content-type".to_string(), "text/plain".to_string())],
+        body_bytes: 2,
+        body_preview: "ok".to_string(),
+        wait_ms: 1.5,
+        receive_ms: 0.5,
+        ..HarEntry::default()
+    };
+    let failed = HarEntry {
+        error: Some("timeout".to_string()),
+        ..ok.clone()
+    };
+    let failed = HarEntry {
+        status: None,
+        ..failed
+    };
+    let doc: serde_json::Value = serde_json::from_str(&render_har(&[&ok, &failed])).unwrap();
+    assert_eq!(doc["log"]["version"], "1.2");
+    let e = &doc["log"]["entries"][0];
+    assert_eq!(e["startedDateTime"], "1970-01-01T00:00:00.000Z");
+    assert_eq!(e["time"], 2.0);
+    assert_eq!(e["request"]["queryString"][1]["value"], "two");
+    assert_eq!(e["response"]["statusText"], "OK");
+    assert_eq!(e["response"]["content"]["mimeType"], "text/plain");
+    assert_eq!(e["response"]["content"]["text"], "ok");
+    assert_eq!(e["timings"]["wait"], 1.5);
+    let e = &doc["log"]["entries"][1];
+    assert_eq!(e["response"]["status"], 0);
+    assert_eq!(e["response"]["_error"], "timeout");
+}
+
+#[test]
+fn sparkline_scales_to_largest_value() {
+    assert_eq!(sparkline(&[0, 4, 8]), "▁▄█");
+    assert_eq!(sparkline(&[0, 0]), "▁▁");
+    assert_eq!(sparkline(&[]), "");
+}
+
+#[test]
+fn retry_after_accepts_delay_seconds_only() {
+    assert_eq!(parse_retry_after(" 120 "), Some(Duration::from_secs(120)));
+    assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+    assert_eq!(parse_retry_after("-1"), None);
+}
+
+#[test]
+fn retry_after_clamps_huge_values() {
+    assert_eq!(parse_retry_after("86400"), Some(MAX_RETRY_AFTER));
+    assert_eq!(
+        parse_retry_after("18446744073709551615"),
+        Some(MAX_RETRY_AFTER)
+    );
+    // past u64 it no longer parses and the default pause applies
+    assert_eq!(parse_retry_after("18446744073709551616"), None);
+}
+
+#[test]
+fn append_path_keeps_base_prefix_and_query() {
+    let base = reqwest::Url::parse("http://h/api/").unwrap();
+    assert_eq!(
+        append_path(&base, "/users/1").as_str(),
+        "http://h/api/users/1"
+    );
+    assert_eq!(
+        append_path(&base, "items?page=2").as_str(),
+        "http://h/api/items?page=2"
+    );
+    let root = reqwest::Url::parse("http://h").unwrap();
+    assert_eq!(append_path(&root, "health").as_str(), "http://h/health");
+}
+
+#[test]
+fn url_warnings_flag_what_the_parser_would_rewrite() {
+    assert!(url_warnings("http://h/a%20b?q=1").is_empty());
+    let w = url_warnings(" http://h/a b/{{seq}}?q=50%\\x|y ");
+    assert_eq!(w.len(), 6);
+    assert!(w[0].contains("leading or trailing whitespace"));
+    assert!(w[1].contains("whitespace"));
+    assert!(w[2].contains("braces"));
+    assert!(w[3].contains("backslash"));
+    assert_eq!(w[4], "unencoded characters: |");
+    assert!(w[5].contains("'%'"));
+}
+
+#[test]
+fn encode_url_percent_encodes_flagged_characters() {
+    assert_eq!(
+        encode_url(" http://h/a b/{{seq}}?q=50%&r=%41 "),
+        "http://h/a%20b/%7B%7Bseq%7D%7D?q=50%25&r=%41"
+    );
+    assert_eq!(encode_url("http://h/x\\y"), "http://h/x%5Cy");
+    assert_eq!(encode_url("http://h/ok"), "http://h/ok");
+}
+
+#[test]
+fn merge_top_slow_keeps_the_slowest_first() {
+    let req = |seq: u64, ms: u64| TopSlowRequest {
+        seq,
+        latency_micros: ms * 1000,
+        at_sec: seq as f64,
+        status: Some(200),
+        error: None,
+    };
+    let mut top = vec![req(1, 5), req(2, 30)];
+    merge_top_slow(&mut top, [req(3, 10), req(4, 30), req(5, 1)], 3);
+    let seqs: Vec<u64> = top.iter().map(|t| t.seq).collect();
+    assert_eq!(seqs, [2, 4, 3]);
+}
+
+#[test]
+fn find_knee_picks_the_best_throughput_per_latency() {
+    let stage = |concurrency: usize, completed: u64, mean_micros: u64| {
+        let mut aggregates = Aggregates::new().unwrap();
+        aggregates.record_latency(mean_micros);
+        StageResult {
+            concurrency,
+            duration_target: "1s".into(),
+            elapsed_sec: 1.0,
+            sent: completed,
+            completed,
+            aggregates,
+        }
+    };
+    let stages = [
+        stage(1, 100, 10_000),
+        stage(4, 380, 10_500),
+        stage(8, 400, 20_000),
+    ];
+    let knee = find_knee(&stages).unwrap();
+    assert_eq!((knee.stage, knee.concurrency), (2, 4));
+    let next = knee.next.unwrap();
+    assert_eq!((next.stage, next.concurrency), (3, 8));
+    assert!((next.rps_gain_pct - 5.26).abs() < 0.01);
+    assert!(next.latency_gain_pct > 90.0);
+
+    // still climbing at the top: no higher stage to compare with
+    let knee = find_knee(&stages[..2]).unwrap();
+    assert_eq!(knee.concurrency, 4);
+    assert!(knee.next.is_none());
+    assert!(find_knee(&stages[..1]).is_none());
+}
+
+#[test]
+fn hmac_sign_parses_and_signs_canonical_string() {
+    let sign = parse_hmac_sign("header=X-Signature,secret=topsecret").unwrap();
+    assert_eq!(sign.header, "X-Signature");
+    assert_eq!(sign.algo, HmacAlgo::Sha256);
+    assert!(!format!("{sign:?}").contains("topsecret"));
+
+    let url = reqwest::Url::parse("http://h/a?b=1").unwrap();
+    assert_eq!(
+        sign_request(&sign, &Method::GET, &url, b""),
+        "641dbaaaebc257e2e57910451b9000bc76f128f3b4fa48527263ba7ff3734e89"
+    );
+    let sha512 = parse_hmac_sign("algo=SHA512, header=X-Sig, secret=topsecret").unwrap();
+    assert!(sign_request(&sha512, &Method::GET, &url, b"").starts_with("fed53846df134214"));
+
+    assert!(parse_hmac_sign("header=X-Sig").is_none());
+    assert!(parse_hmac_sign("header=Bad Header,secret=s").is_none());
+    assert!(parse_hmac_sign("header=X-Sig,secret=s,algo=md5").is_none());
+}
+
+#[test]
+fn histogram_serialization_round_trips_and_merges_exactly() {
+    let mut a = Aggregates::with_hist(HistConfig {
+        sigfigs: 2,
+        max_micros: None,
+    })
+    .unwrap();
+    for v in [120, 450, 450, 9_000, 2_500_000] {
+        a.record_latency(v);
+    }
+    let h = &a.latency_micros;
+    let back = deserialize_histogram(&serialize_histogram(h).unwrap()).unwrap();
+    assert_eq!(&back, h);
+    assert_eq!(back.sigfig(), 2);
+    assert_eq!(back.value_at_quantile(0.5), h.value_at_quantile(0.5));
+
+    // a whole RunResult field goes through the same encoding
+    let json = serde_json::to_string(&a).unwrap();
+    let loaded: Aggregates = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded.latency_micros, a.latency_micros);
+
+    let mut merged = back.clone();
+    merged.add(h).unwrap();
+    assert_eq!(merged.len(), 10);
+    assert_eq!(merged.max(), h.max());
+    assert!(deserialize_histogram("not base64!").is_err());
+}
+
+#[test]
+fn auto_resizing_histograms_keep_large_values() {
+    let mut a = Aggregates::new().unwrap();
+    a.record_latency(250_000);
+    a.record_connection_setup(1_500_000);
+    assert!(a.latency_micros.max() >= 1_499_000);
+    assert!(a.connection_setup_micros.min() >= 1_499_000);
+
+    let mut bounded = Aggregates::with_hist(HistConfig {
+        sigfigs: 3,
+        max_micros: Some(1_000),
+    })
+    .unwrap();
+    bounded.record_latency(5_000);
+    assert!(bounded.latency_micros.max() <= 1_001);
+}
+
+#[test]
+fn corrected_interval_backfills_requests_delayed_by_a_slow_one() {
+    let mut a = Aggregates::new().unwrap();
+    a.record_corrected_interval(250_000, 50_000);
+    // 250ms plus the four requests that should have started at 50ms steps behind it
+    assert_eq!(a.corrected_micros.len(), 5);
+    assert!(a.corrected_micros.min() <= 50_100);
+    a.record_corrected_interval(10_000, 50_000);
+    assert_eq!(a.corrected_micros.len(), 6);
+}
+
+#[test]
+fn apdex_buckets_latencies_by_threshold() {
+    let mut a = Aggregates::new().unwrap();
+    // T = 100ms: two satisfied (one exactly at T), one tolerating, one frustrated
+    for micros in [50_000, 100_000, 300_000, 450_000] {
+        a.record_latency(micros);
+    }
+    let ap = apdex(&a.latency_micros, Duration::from_millis(100));
+    assert_eq!((ap.satisfied, ap.tolerating, ap.frustrated), (2, 1, 1));
+    assert!((ap.score - 0.625).abs() < 1e-9);
+    assert_eq!(ap.threshold_ms, 100.0);
+
+    let empty = Aggregates::new().unwrap();
+    assert_eq!(
+        apdex(&empty.latency_micros, Duration::from_millis(100)).score,
+        0.0
+    );
+}
+
+#[test]
+fn parse_form_file_needs_at_path() {
+    assert_eq!(
+        parse_form_file("upload=@/tmp/a.bin"),
+        Some(("upload".to_string(), "/tmp/a.bin".to_string()))
+    );
+    assert_eq!(parse_form_file("upload=/tmp/a.bin"), None);
+    assert_eq!(parse_form_file("=@/tmp/a.bin"), None);
+    assert_eq!(parse_form_file("upload=@"), None);
+}
+
+#[test]
+fn retry_delay_fixed_or_jittered_exponential() {
+    use rand::SeedableRng;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+    let mut p = RetryPolicy {
+        retries: 5,
+        base: Duration::from_millis(100),
+        max: Duration::from_millis(500),
+        mode: BackoffMode::Fixed,
+    };
+    assert_eq!(p.delay(3, &mut rng), Duration::from_millis(100));
+
+    p.mode = BackoffMode::Exponential;
+    for _ in 0..100 {
+        assert!(p.delay(1, &mut rng) <= Duration::from_millis(200));
+        // 100ms * 2^4 is capped at 500ms
+        assert!(p.delay(4, &mut rng) <= Duration::from_millis(500));
+        assert!(p.delay(40, &mut rng) <= Duration::from_millis(500));
+    }
+}
+
+#[test]
+fn grpc_frame_prefixes_flag_and_length() {
+    assert_eq!(grpc_frame(b"hi"), vec![0, 0, 0, 0, 2, b'h', b'i']);
+    assert_eq!(grpc_frame(b""), vec![0, 0, 0, 0, 0]);
+}
+
+#[test]
+fn gzip_bytes_round_trips() {
+    let data = b"hello hello hello hello".repeat(50);
+    let gz = gzip_bytes(&data).unwrap();
+    assert_eq!(&gz[..2], &[0x1f, 0x8b]);
+    let mut out = Vec::new();
+    std::io::Read::read_to_end(&mut flate2::read::GzDecoder::new(&gz[..]), &mut out).unwrap();
+    assert_eq!(out, data);
+}
+
+#[test]
+fn traceparent_follows_w3c_format() {
+    let tp = traceparent(0x4bf92f3577b34da6a3ce929d0e0e4736, 0x00f067aa0ba902b7);
+    assert_eq!(
+        tp,
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+    );
+    // ids are zero-padded to their fixed widths
+    assert_eq!(
+        traceparent(1, 2),
+        "00-00000000000000000000000000000001-0000000000000002-01"
+    );
+}
+
+#[test]
+fn closes_connection_reads_the_connection_tokens() {
+    use reqwest::header::{HeaderMap, HeaderValue, CONNECTION};
+    let mut h = HeaderMap::new();
+    assert!(!closes_connection(&h));
+    h.insert(CONNECTION, HeaderValue::from_static("keep-alive"));
+    assert!(!closes_connection(&h));
+    h.insert(CONNECTION, HeaderValue::from_static("Upgrade, Close"));
+    assert!(closes_connection(&h));
+    h.insert(CONNECTION, HeaderValue::from_static("closed"));
+    assert!(!closes_connection(&h));
+}
+
+#[test]
+fn request_outcome_json_line() {
+    let o = RequestOutcome {
+        seq: 3,
+        step: 0,
+        timestamp: std::time::UNIX_EPOCH + Duration::from_millis(1_500),
+        latency_micros: 2_500,
+        status: Err(NetErrKind::Timeout),
+        trace_id: Some("ab".repeat(16)),
+    };
+    let v: serde_json::Value = serde_json::from_str(&o.to_json_line()).unwrap();
+    assert_eq!(v["seq"], 3);
+    assert_eq!(v["timestamp_ms"], 1500);
+    assert_eq!(v["latency_ms"], 2.5);
+    assert_eq!(v["error"], "timeout");
+    assert!(v.get("status").is_none());
+    assert_eq!(v["trace_id"], "ab".repeat(16));
+    assert!(v.get("type").is_none());
+
+    // the --stream-ndjson record is the same object, tagged
+    let mut tagged: serde_json::Value = serde_json::from_str(&o.to_ndjson_line()).unwrap();
+    assert_eq!(tagged["type"], "request");
+    tagged.as_object_mut().unwrap().remove("type");
+    assert_eq!(tagged, v);
+}
+
+#[test]
+fn parse_initial_failures_n_of_m() {
+    assert_eq!(parse_initial_failures("3/5"), Some((3, 5)));
+    assert_eq!(parse_initial_failures(" 0 / 10 "), Some((0, 10)));
+    assert_eq!(parse_initial_failures("5/5"), None);
+    assert_eq!(parse_initial_failures("3"), None);
+    assert_eq!(parse_initial_failures("a/5"), None);
+}
+
+#[test]
+fn weighted_slots_interleave_by_weight() {
+    assert_eq!(weighted_slots(&[2, 1]), vec![0, 1, 0]);
+    // reduced by the gcd: one cycle of 7 + 3
+    let slots = weighted_slots(&[70, 30]);
+    assert_eq!(slots.len(), 10);
+    assert_eq!(slots.iter().filter(|&&i| i == 1).count(), 3);
+    assert_eq!(weighted_slots(&[5]), vec![0]);
+    // large weights with a common divisor still make a short cycle
+    assert_eq!(weighted_slots(&[3_000_000_000, 1_000_000_000]).len(), 4);
+}
+
+#[test]
+fn parse_burst_on_and_off() {
+    assert_eq!(
+        parse_burst("on=10s,off=20s"),
+        Some((Duration::from_secs(10), Duration::from_secs(20)))
+    );
+    assert_eq!(
+        parse_burst("off=1m, on=500ms"),
+        Some((Duration::from_millis(500), Duration::from_secs(60)))
+    );
+    assert_eq!(parse_burst("on=10s"), None);
+    assert_eq!(parse_burst("on=0s,off=1s"), None);
+    assert_eq!(parse_burst("on=1s,idle=1s"), None);
+}
+
+#[test]
+fn concurrency_profiles_parse_and_shape() {
+    let sine = parse_concurrency_profile("sine:min=10,max=100,period=60s").unwrap();
+    assert_eq!(
+        sine,
+        ConcurrencyProfile::Sine {
+            min: 10,
+            max: 100,
+            period: Duration::from_secs(60)
+        }
+    );
+    let total = Duration::from_secs(120);
+    assert_eq!(sine.peak(), 100);
+    assert_eq!(sine.target(Duration::ZERO, total), 10);
+    assert_eq!(sine.target(Duration::from_secs(15), total), 55);
+    assert_eq!(sine.target(Duration::from_secs(30), total), 100);
+    assert_eq!(sine.target(Duration::from_secs(60), total), 10);
+
+    let down = parse_concurrency_profile("linear:100..10").unwrap();
+    assert_eq!(down.peak(), 100);
+    assert_eq!(down.target(Duration::from_secs(60), total), 55);
+    assert_eq!(down.target(Duration::from_secs(500), total), 10);
+
+    assert_eq!(
+        parse_concurrency_profile("sine:min=10,max=5,period=1s"),
+        None
+    );
+    assert_eq!(parse_concurrency_profile("sine:min=1,max=5"), None);
+    assert_eq!(
+        parse_concurrency_profile("sine:min=1,max=5,period=0s"),
+        None
+    );
+    assert_eq!(parse_concurrency_profile("linear:0..0"), None);
+    assert_eq!(parse_concurrency_profile("square:1..5"), None);
+}
+
+#[test]
+fn parse_trace_line_fields() {
+    let e = parse_trace_line("1500 post /api/orders?x=1").unwrap();
+    assert_eq!(e.offset_ms, 1500);
+    assert_eq!(e.method, Method::POST);
+    assert_eq!(e.path, "/api/orders?x=1");
+    assert!(parse_trace_line("-5 GET /").is_none());
+    assert!(parse_trace_line("5 FETCH /").is_none());
+    assert!(parse_trace_line("5 GET").is_none());
+    assert!(parse_trace_line("5 GET / extra").is_none());
+}
+
+#[test]
+fn colorize_report_highlights_key_lines() {
+    let text = "error_rate: 25.00% (network errors and 5xx)\n\nstatus_class_counts:\n  2xx: 3 (75.0%)\n  4xx: 0 (0.0%)\n  5xx: 1 (25.0%)\n\nnetwork_error_counts:\n  timeout: 0 (0.0%)\n\nlatency_ms:\n  p50: 85.000\n  p99: 250.000\n  max: 90.000\n";
+    let out = colorize_report(text, Some(100.0));
+    assert!(out.starts_with("\x1b[31merror_rate: 25.00% (network errors and 5xx)\x1b[0m\n"));
+    assert!(out.contains("\x1b[32m  2xx: 3 (75.0%)\x1b[0m\n"));
+    assert!(out.contains("\n  4xx: 0 (0.0%)\n"));
+    assert!(out.contains("\x1b[31m  5xx: 1 (25.0%)\x1b[0m\n"));
+    assert!(out.contains("\n  timeout: 0 (0.0%)\n"));
+    // over 80% of the target is yellow, over it red
+    assert!(out.contains("\x1b[33m  p50: 85.000\x1b[0m\n"));
+    assert!(out.contains("\x1b[31m  p99: 250.000\x1b[0m\n"));
+
+    // without a target latencies stay plain, and stripping the codes gives the text back
+    let out = colorize_report(text, None);
+    assert!(out.contains("\n  p99: 250.000\n"));
+    assert_eq!(
+        out.replace("\x1b[31m", "")
+            .replace("\x1b[32m", "")
+            .replace("\x1b[0m", ""),
+        text
+    );
 }